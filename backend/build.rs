@@ -0,0 +1,4 @@
+// Build lại khi thêm/sửa file migration (sqlx::migrate! nhúng chúng vào binary)
+fn main() {
+    println!("cargo:rerun-if-changed=migrations");
+}
@@ -0,0 +1,126 @@
+-- Bảng lõi: user, danh mục, sản phẩm, đơn hàng, giỏ hàng, liên hệ, cấu hình
+
+CREATE TABLE users (
+  id varchar(11) NOT NULL,
+  email varchar(255) NOT NULL,
+  password_hash varchar(255),  -- NULL với tài khoản chỉ đăng nhập qua Google/Facebook
+  name varchar(255),
+  given_name varchar(100),
+  family_name varchar(100),
+  picture text,
+  phone varchar(20),
+  address text,
+  role varchar(32) NOT NULL DEFAULT 'user',
+  status enum('active','locked') NOT NULL DEFAULT 'active',
+  login_count int DEFAULT 0,
+  points int NOT NULL DEFAULT 0,
+  level enum('BRONZE','SILVER','GOLD','DIAMOND') NOT NULL DEFAULT 'BRONZE',
+  email_verified tinyint(1) NOT NULL DEFAULT 0,
+  created_at datetime DEFAULT CURRENT_TIMESTAMP,
+  updated_at datetime DEFAULT CURRENT_TIMESTAMP ON UPDATE CURRENT_TIMESTAMP,
+  PRIMARY KEY (id),
+  UNIQUE KEY email (email)
+) ENGINE=InnoDB DEFAULT CHARSET=utf8mb4;
+
+
+CREATE TABLE categories (
+  id varchar(11) NOT NULL,
+  name varchar(255) NOT NULL,
+  PRIMARY KEY (id)
+) ENGINE=InnoDB DEFAULT CHARSET=utf8mb4;
+
+
+CREATE TABLE products (
+  id varchar(11) NOT NULL,
+  category_id varchar(11) NOT NULL,
+  name varchar(255) NOT NULL,
+  price decimal(15,2) NOT NULL,
+  stock int NOT NULL DEFAULT 0,
+  images json,                        -- Mảng URL ảnh ["/storages/..."]
+  description text,
+  specs json,                         -- Thông số kỹ thuật dạng {"CPU": "...", ...}
+  rating decimal(3,2) DEFAULT 0,      -- Trung bình các đánh giá đã duyệt
+  review_count int DEFAULT 0,
+  is_deleted tinyint(1) NOT NULL DEFAULT 0, -- Xóa mềm
+  created_at datetime DEFAULT CURRENT_TIMESTAMP,
+  PRIMARY KEY (id),
+  KEY category_idx (category_id),
+  FOREIGN KEY (category_id) REFERENCES categories(id)
+) ENGINE=InnoDB DEFAULT CHARSET=utf8mb4;
+
+
+CREATE TABLE orders (
+  id varchar(11) NOT NULL,
+  user_id varchar(11) NOT NULL,
+  total_amount decimal(15,2) NOT NULL,
+  discount_amount decimal(15,2) DEFAULT 0,
+  final_amount decimal(15,2) NOT NULL,
+  points_earned int NOT NULL DEFAULT 0,
+  status enum('pending','shipping','completed','cancelled') NOT NULL DEFAULT 'pending',
+  shipping_name varchar(255),
+  shipping_phone varchar(20),
+  shipping_address text,
+  note text,
+  created_at datetime DEFAULT CURRENT_TIMESTAMP,
+  PRIMARY KEY (id),
+  KEY user_idx (user_id),
+  FOREIGN KEY (user_id) REFERENCES users(id)
+) ENGINE=InnoDB DEFAULT CHARSET=utf8mb4;
+
+
+CREATE TABLE order_items (
+  id varchar(11) NOT NULL,
+  order_id varchar(11) NOT NULL,
+  product_id varchar(11) NOT NULL,
+  quantity int NOT NULL,
+  price decimal(15,2) NOT NULL,
+  PRIMARY KEY (id),
+  KEY order_idx (order_id),
+  KEY product_idx (product_id),
+  FOREIGN KEY (order_id) REFERENCES orders(id),
+  FOREIGN KEY (product_id) REFERENCES products(id)
+) ENGINE=InnoDB DEFAULT CHARSET=utf8mb4;
+
+
+-- Giỏ hàng lưu server: mỗi user 1 dòng / sản phẩm (upsert theo khóa chính)
+CREATE TABLE cart_items (
+  user_id varchar(11) NOT NULL,
+  product_id varchar(11) NOT NULL,
+  quantity int NOT NULL,
+  created_at datetime DEFAULT CURRENT_TIMESTAMP,
+  updated_at datetime DEFAULT CURRENT_TIMESTAMP ON UPDATE CURRENT_TIMESTAMP,
+  PRIMARY KEY (user_id, product_id),
+  FOREIGN KEY (user_id) REFERENCES users(id),
+  FOREIGN KEY (product_id) REFERENCES products(id)
+) ENGINE=InnoDB DEFAULT CHARSET=utf8mb4;
+
+
+-- Tin nhắn liên hệ (user_id NULL nếu khách chưa đăng nhập)
+CREATE TABLE contacts (
+  id varchar(11) NOT NULL,
+  user_id varchar(11),
+  email varchar(255) NOT NULL,
+  message text NOT NULL,
+  status varchar(20) NOT NULL DEFAULT 'pending', -- pending | processed
+  created_at datetime DEFAULT CURRENT_TIMESTAMP,
+  PRIMARY KEY (id),
+  KEY user_idx (user_id),
+  FOREIGN KEY (user_id) REFERENCES users(id)
+) ENGINE=InnoDB DEFAULT CHARSET=utf8mb4;
+
+
+-- Cấu hình key/value, admin sửa qua /api/admin/settings
+CREATE TABLE settings (
+  id varchar(64) NOT NULL,
+  value text,
+  PRIMARY KEY (id)
+) ENGINE=InnoDB DEFAULT CHARSET=utf8mb4;
+
+INSERT INTO settings (id, value) VALUES
+  ('site_name', 'ElectroShop'),
+  ('contact_email', ''),
+  ('hotline', ''),
+  ('point_ratio', '1000'),
+  ('level_silver', '1000'),
+  ('level_gold', '5000'),
+  ('level_diamond', '10000');
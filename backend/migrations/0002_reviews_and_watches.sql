@@ -0,0 +1,68 @@
+-- Đánh giá sản phẩm, vote hữu ích, trả lời của admin, đăng ký theo dõi sản phẩm
+
+CREATE TABLE reviews (
+  id varchar(11) NOT NULL,
+  user_id varchar(11) NOT NULL,
+  product_id varchar(11) NOT NULL,
+  rating int NOT NULL,
+  content text,
+  verified_purchase tinyint(1) NOT NULL DEFAULT 0,
+  status enum('pending','approved','rejected','hidden') NOT NULL DEFAULT 'pending',
+  images json,
+  helpful_count int NOT NULL DEFAULT 0,
+  created_at datetime DEFAULT CURRENT_TIMESTAMP,
+  updated_at datetime,
+  PRIMARY KEY (id),
+  UNIQUE KEY user_product (user_id, product_id),
+  KEY product_status_idx (product_id, status),
+  FOREIGN KEY (user_id) REFERENCES users(id),
+  FOREIGN KEY (product_id) REFERENCES products(id)
+) ENGINE=InnoDB DEFAULT CHARSET=utf8mb4;
+
+
+CREATE TABLE review_votes (
+  review_id varchar(11) NOT NULL,
+  user_id varchar(11) NOT NULL,
+  created_at datetime DEFAULT CURRENT_TIMESTAMP,
+  PRIMARY KEY (review_id, user_id),
+  FOREIGN KEY (review_id) REFERENCES reviews(id),
+  FOREIGN KEY (user_id) REFERENCES users(id)
+) ENGINE=InnoDB DEFAULT CHARSET=utf8mb4;
+
+
+CREATE TABLE review_replies (
+  id varchar(11) NOT NULL,
+  review_id varchar(11) NOT NULL,
+  admin_id varchar(11) NOT NULL,
+  content text NOT NULL,
+  created_at datetime DEFAULT CURRENT_TIMESTAMP,
+  updated_at datetime,
+  PRIMARY KEY (id),
+  UNIQUE KEY review_idx (review_id),
+  FOREIGN KEY (review_id) REFERENCES reviews(id),
+  FOREIGN KEY (admin_id) REFERENCES users(id)
+) ENGINE=InnoDB DEFAULT CHARSET=utf8mb4;
+
+
+-- Cấu hình kiểm duyệt đánh giá
+-- review_banned_words: danh sách từ cấm, phân tách bằng dấu phẩy
+-- review_require_approval: "true" để mọi đánh giá đều chờ duyệt
+INSERT INTO settings (id, value) VALUES
+  ('review_banned_words', ''),
+  ('review_require_approval', 'false');
+
+
+CREATE TABLE product_watches (
+  id varchar(11) NOT NULL,
+  product_id varchar(11) NOT NULL,
+  user_id varchar(11),
+  email varchar(255) NOT NULL,
+  kind enum('back_in_stock','price_drop') NOT NULL,
+  status enum('active','fulfilled') DEFAULT 'active',
+  created_at datetime DEFAULT CURRENT_TIMESTAMP,
+  fulfilled_at datetime,
+  PRIMARY KEY (id),
+  KEY product_kind_idx (product_id, kind, status),
+  FOREIGN KEY (product_id) REFERENCES products(id),
+  FOREIGN KEY (user_id) REFERENCES users(id)
+) ENGINE=InnoDB DEFAULT CHARSET=utf8mb4;
@@ -0,0 +1,56 @@
+-- Đăng nhập: token email/OTP, liên kết provider, phiên (refresh token)
+
+-- Token xác thực email / đặt lại mật khẩu / OTP đăng nhập (chỉ lưu hash SHA-256)
+CREATE TABLE auth_tokens (
+  id varchar(11) NOT NULL,
+  user_id varchar(11),
+  email varchar(255) NOT NULL,
+  purpose enum('verify_email','reset_password','login_otp') NOT NULL,
+  token_hash char(64) NOT NULL,
+  attempts int DEFAULT 0,
+  expires_at datetime NOT NULL,
+  used_at datetime,
+  created_at datetime DEFAULT CURRENT_TIMESTAMP,
+  PRIMARY KEY (id),
+  KEY token_idx (token_hash),
+  KEY email_purpose_idx (email, purpose),
+  FOREIGN KEY (user_id) REFERENCES users(id)
+) ENGINE=InnoDB DEFAULT CHARSET=utf8mb4;
+
+
+-- Liên kết nhiều provider đăng nhập (google, facebook, ...) vào cùng một user
+CREATE TABLE user_identities (
+  id varchar(11) NOT NULL,
+  user_id varchar(11) NOT NULL,
+  provider varchar(32) NOT NULL,
+  subject varchar(255) NOT NULL,
+  email varchar(255),
+  created_at datetime DEFAULT CURRENT_TIMESTAMP,
+  PRIMARY KEY (id),
+  UNIQUE KEY provider_subject (provider, subject),
+  KEY user_idx (user_id),
+  FOREIGN KEY (user_id) REFERENCES users(id)
+) ENGINE=InnoDB DEFAULT CHARSET=utf8mb4;
+
+
+-- Phiên đăng nhập: mỗi dòng là một refresh token (lưu hash SHA-256).
+-- Các token xoay vòng từ cùng một lần đăng nhập chung family_id.
+CREATE TABLE sessions (
+  id varchar(11) NOT NULL,
+  family_id varchar(11) NOT NULL,
+  user_id varchar(11) NOT NULL,
+  token_hash char(64) NOT NULL,
+  device varchar(100),
+  ip varchar(45),
+  user_agent varchar(512),
+  created_at datetime DEFAULT CURRENT_TIMESTAMP,
+  last_used_at datetime,
+  expires_at datetime NOT NULL,
+  revoked_at datetime,
+  replaced_by varchar(11),
+  PRIMARY KEY (id),
+  UNIQUE KEY token_idx (token_hash),
+  KEY family_idx (family_id),
+  KEY user_idx (user_id),
+  FOREIGN KEY (user_id) REFERENCES users(id)
+) ENGINE=InnoDB DEFAULT CHARSET=utf8mb4;
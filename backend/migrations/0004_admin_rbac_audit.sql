@@ -0,0 +1,52 @@
+-- Quản trị: phân quyền theo role (RBAC) + nhật ký thao tác
+
+-- role -> tập quyền. '*' = toàn quyền
+CREATE TABLE roles (
+  name varchar(32) NOT NULL,
+  description varchar(255),
+  PRIMARY KEY (name)
+) ENGINE=InnoDB DEFAULT CHARSET=utf8mb4;
+
+CREATE TABLE role_permissions (
+  role varchar(32) NOT NULL,
+  permission varchar(64) NOT NULL,
+  PRIMARY KEY (role, permission),
+  FOREIGN KEY (role) REFERENCES roles(name) ON DELETE CASCADE
+) ENGINE=InnoDB DEFAULT CHARSET=utf8mb4;
+
+INSERT INTO roles (name, description) VALUES
+  ('user', 'Khách hàng'),
+  ('staff', 'Nhân viên bán hàng'),
+  ('warehouse', 'Nhân viên kho'),
+  ('support', 'Chăm sóc khách hàng'),
+  ('admin', 'Quản trị viên');
+
+INSERT INTO role_permissions (role, permission) VALUES
+  ('admin', '*'),
+  ('staff', 'orders.read'), ('staff', 'orders.update_status'), ('staff', 'products.write'),
+  ('staff', 'inventory.write'), ('staff', 'users.read'), ('staff', 'analytics.read'),
+  ('staff', 'contacts.read'), ('staff', 'reviews.read'), ('staff', 'reviews.moderate'), ('staff', 'reviews.reply'),
+  ('warehouse', 'orders.read'), ('warehouse', 'orders.update_status'), ('warehouse', 'inventory.write'),
+  ('support', 'orders.read'), ('support', 'users.read'), ('support', 'contacts.read'), ('support', 'contacts.write'),
+  ('support', 'reviews.read'), ('support', 'reviews.moderate'), ('support', 'reviews.reply');
+
+ALTER TABLE users ADD CONSTRAINT users_role_fk FOREIGN KEY (role) REFERENCES roles(name);
+
+
+CREATE TABLE admin_audit_logs (
+  id varchar(11) NOT NULL,
+  actor_id varchar(11) NOT NULL,
+  action varchar(64) NOT NULL,
+  target_type varchar(32) NOT NULL,
+  target_id varchar(64) NOT NULL,
+  before_data json,
+  after_data json,
+  reason text,
+  ip varchar(45),
+  created_at datetime DEFAULT CURRENT_TIMESTAMP,
+  PRIMARY KEY (id),
+  KEY target_idx (target_type, target_id),
+  KEY actor_idx (actor_id),
+  KEY created_idx (created_at),
+  FOREIGN KEY (actor_id) REFERENCES users(id)
+) ENGINE=InnoDB DEFAULT CHARSET=utf8mb4;
@@ -0,0 +1,34 @@
+-- Thư viện media: mỗi file upload (theo nội dung) chỉ lưu 1 lần + đếm tham chiếu để dọn file mồ côi
+
+CREATE TABLE media (
+  id varchar(11) NOT NULL,
+  content_hash char(64) NOT NULL,      -- SHA-256 của file gốc client gửi lên
+  url varchar(512) NOT NULL,           -- URL chính (bản large), khớp products.images[].url / reviews.images[]
+  image json NOT NULL,                 -- { url, width, height, variants } trả về cho client
+  blob_keys json NOT NULL,             -- Key các file trong storage, GC xóa theo danh sách này
+  original_name varchar(255),
+  content_type varchar(32) NOT NULL,   -- Định dạng gốc (đã sniff)
+  size int NOT NULL,                   -- Dung lượng file gốc (byte)
+  uploaded_by varchar(11),
+  created_at datetime DEFAULT CURRENT_TIMESTAMP,
+  last_used_at datetime DEFAULT CURRENT_TIMESTAMP, -- Lần cuối upload lại / thêm / bỏ tham chiếu; GC tính thời gian ân hạn từ đây
+  PRIMARY KEY (id),
+  UNIQUE KEY content_hash_uq (content_hash),
+  UNIQUE KEY url_uq (url),
+  KEY last_used_idx (last_used_at),
+  FOREIGN KEY (uploaded_by) REFERENCES users(id) ON DELETE SET NULL
+) ENGINE=InnoDB DEFAULT CHARSET=utf8mb4;
+
+-- Nơi đang dùng media: số dòng của 1 media = số tham chiếu
+CREATE TABLE media_refs (
+  media_id varchar(11) NOT NULL,
+  ref_type varchar(16) NOT NULL,       -- product | review | return
+  ref_id varchar(11) NOT NULL,
+  created_at datetime DEFAULT CURRENT_TIMESTAMP,
+  PRIMARY KEY (media_id, ref_type, ref_id),
+  KEY ref_idx (ref_type, ref_id),
+  FOREIGN KEY (media_id) REFERENCES media(id)
+) ENGINE=InnoDB DEFAULT CHARSET=utf8mb4;
+
+INSERT INTO role_permissions (role, permission) VALUES
+  ('staff', 'media.read');
@@ -0,0 +1,15 @@
+-- Email theo template: bản admin sửa (ghi đè bản mặc định trong backend/templates/email) + ngôn ngữ nhận mail của user
+
+CREATE TABLE email_templates (
+  name varchar(64) NOT NULL,           -- VD: order_shipping
+  lang varchar(5) NOT NULL,            -- vi | en
+  subject text NOT NULL,
+  html_body mediumtext NOT NULL,
+  text_body mediumtext NOT NULL,
+  updated_by varchar(11),
+  updated_at datetime DEFAULT CURRENT_TIMESTAMP ON UPDATE CURRENT_TIMESTAMP,
+  PRIMARY KEY (name, lang),
+  FOREIGN KEY (updated_by) REFERENCES users(id) ON DELETE SET NULL
+) ENGINE=InnoDB DEFAULT CHARSET=utf8mb4;
+
+ALTER TABLE users ADD COLUMN language varchar(5) NOT NULL DEFAULT 'vi';
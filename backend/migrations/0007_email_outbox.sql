@@ -0,0 +1,27 @@
+-- Hàng đợi email: ghi cùng transaction với thay đổi nghiệp vụ (đơn hàng, trả lời đánh giá...),
+-- worker nền gửi dần, lỗi thì thử lại theo backoff, quá số lần thì chuyển dead chờ admin gửi lại
+
+CREATE TABLE email_outbox (
+  id varchar(11) NOT NULL,
+  to_email varchar(255) NOT NULL,
+  template varchar(64) NOT NULL,       -- Tên template đã render (order_shipping, login_otp...)
+  lang varchar(5) NOT NULL,
+  subject text NOT NULL,
+  html_body mediumtext NOT NULL,
+  text_body mediumtext NOT NULL,
+  status enum('pending','sending','sent','dead') NOT NULL DEFAULT 'pending',
+  attempts int NOT NULL DEFAULT 0,     -- Số lần gửi lỗi
+  last_error text,
+  next_attempt_at datetime NOT NULL DEFAULT CURRENT_TIMESTAMP,
+  claim_token varchar(11),             -- Worker nào đang gửi (chạy nhiều instance không gửi trùng)
+  locked_until datetime,               -- Worker chết giữa chừng -> hết hạn thì instance khác nhận lại
+  created_at datetime DEFAULT CURRENT_TIMESTAMP,
+  sent_at datetime,
+  PRIMARY KEY (id),
+  KEY status_next_idx (status, next_attempt_at),
+  KEY claim_idx (claim_token),
+  KEY to_email_idx (to_email)
+) ENGINE=InnoDB DEFAULT CHARSET=utf8mb4;
+
+INSERT INTO role_permissions (role, permission) VALUES
+  ('support', 'emails.read'), ('support', 'emails.resend');
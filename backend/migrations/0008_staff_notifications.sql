@@ -0,0 +1,7 @@
+-- Email báo nhân viên: danh sách người nhận (phân cách bằng dấu phẩy, trống = tắt) + ngưỡng sắp hết hàng
+
+INSERT INTO settings (id, value) VALUES
+  ('notify_new_order_emails', ''),
+  ('notify_contact_emails', ''),
+  ('notify_low_stock_emails', ''),
+  ('low_stock_threshold', '5');
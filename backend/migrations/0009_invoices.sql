@@ -0,0 +1,10 @@
+-- Hóa đơn PDF: thông tin pháp lý của cửa hàng in trên hóa đơn + thuế suất GTGT (%, giá bán đã gồm thuế)
+
+INSERT INTO settings (id, value) VALUES
+  ('company_name', ''),
+  ('company_address', ''),
+  ('company_tax_code', ''),
+  ('invoice_vat_rate', '10');
+
+-- File đính kèm của email, lưu tham chiếu (worker dựng nội dung lúc gửi), vd [{"type":"invoice","order_id":"..."}]
+ALTER TABLE email_outbox ADD COLUMN attachments json NULL AFTER text_body;
@@ -0,0 +1,31 @@
+// src/db.rs
+// Migration DB: các file trong backend/migrations được nhúng vào binary lúc build
+use sqlx::migrate::Migrator;
+use sqlx::MySqlPool;
+
+pub static MIGRATOR: Migrator = sqlx::migrate!("./migrations");
+
+/// Chạy các migration chưa áp dụng (ghi lại trong bảng _sqlx_migrations)
+pub async fn run_migrations(pool: &MySqlPool) -> Result<(), sqlx::migrate::MigrateError> {
+    let applied: Vec<(i64,)> = sqlx::query_as("SELECT version FROM _sqlx_migrations WHERE success = TRUE")
+        .fetch_all(pool)
+        .await
+        .unwrap_or_default(); // Bảng chưa có -> DB mới
+
+    let pending: Vec<_> = MIGRATOR
+        .iter()
+        .filter(|m| !applied.iter().any(|(v,)| *v == m.version))
+        .collect();
+
+    if pending.is_empty() {
+        println!("✅ \x1b[32mDB đã ở phiên bản mới nhất\x1b[0m");
+        return Ok(());
+    }
+
+    for m in &pending {
+        println!("⏳ Migration {} - {}", m.version, m.description);
+    }
+    MIGRATOR.run(pool).await?;
+    println!("✅ \x1b[32mĐã chạy {} migration\x1b[0m", pending.len());
+    Ok(())
+}
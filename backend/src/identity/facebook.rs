@@ -0,0 +1,102 @@
+// src/identity/facebook.rs
+// Facebook Login: client gửi access token, server kiểm tra qua Graph API.
+// Các provider kiểu OAuth access token khác (Zalo, ...) làm tương tự và đăng ký trong IdentityProviders::from_config.
+use super::{IdentityError, IdentityProvider, VerifiedIdentity};
+use axum::async_trait;
+use reqwest::Client;
+use serde::Deserialize;
+
+const GRAPH_URL: &str = "https://graph.facebook.com/v19.0";
+
+#[derive(Debug, Deserialize)]
+struct DebugTokenResponse {
+    data: DebugTokenData,
+}
+
+#[derive(Debug, Deserialize)]
+struct DebugTokenData {
+    app_id: Option<String>,
+    is_valid: bool,
+    user_id: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+struct FacebookProfile {
+    id: String,
+    name: Option<String>,
+    email: Option<String>,
+    picture: Option<FacebookPicture>,
+}
+
+#[derive(Debug, Deserialize)]
+struct FacebookPicture {
+    data: FacebookPictureData,
+}
+
+#[derive(Debug, Deserialize)]
+struct FacebookPictureData {
+    url: Option<String>,
+}
+
+pub struct FacebookProvider {
+    app_id: String,
+    app_secret: String,
+    http: Client,
+}
+
+impl FacebookProvider {
+    pub fn new(app_id: String, app_secret: String) -> Self {
+        FacebookProvider { app_id, app_secret, http: Client::new() }
+    }
+}
+
+#[async_trait]
+impl IdentityProvider for FacebookProvider {
+    fn name(&self) -> &'static str {
+        "facebook"
+    }
+
+    async fn verify(&self, credential: &str) -> Result<VerifiedIdentity, IdentityError> {
+        // 1. Token phải hợp lệ và được cấp cho đúng app của mình
+        let app_token = format!("{}|{}", self.app_id, self.app_secret);
+        let debug: DebugTokenResponse = self.http
+            .get(format!("{}/debug_token", GRAPH_URL))
+            .query(&[("input_token", credential), ("access_token", app_token.as_str())])
+            .send()
+            .await
+            .map_err(|e| IdentityError::Upstream(e.to_string()))?
+            .json()
+            .await
+            .map_err(|e| IdentityError::InvalidToken(e.to_string()))?;
+
+        if !debug.data.is_valid || debug.data.app_id.as_deref() != Some(self.app_id.as_str()) {
+            return Err(IdentityError::InvalidToken("Token không thuộc app này".to_string()));
+        }
+
+        // 2. Lấy thông tin user
+        let profile: FacebookProfile = self.http
+            .get(format!("{}/me", GRAPH_URL))
+            .query(&[("fields", "id,name,email,picture"), ("access_token", credential)])
+            .send()
+            .await
+            .map_err(|e| IdentityError::Upstream(e.to_string()))?
+            .json()
+            .await
+            .map_err(|e| IdentityError::InvalidToken(e.to_string()))?;
+
+        if debug.data.user_id.as_deref() != Some(profile.id.as_str()) {
+            return Err(IdentityError::InvalidToken("User không khớp".to_string()));
+        }
+
+        // Graph API không cho biết email đã được xác nhận hay chưa (tài khoản đăng ký bằng số điện
+        // thoại có thể gắn email bất kỳ) -> coi là chưa xác thực, không dùng để liên kết tài khoản
+        Ok(VerifiedIdentity {
+            provider: self.name().to_string(),
+            subject: profile.id,
+            email: profile.email,
+            email_verified: false,
+            name: profile.name,
+            picture: profile.picture.and_then(|p| p.data.url),
+        })
+    }
+}
@@ -0,0 +1,92 @@
+// src/identity/fake.rs
+// Provider giả cho test/e2e không cần mạng. Credential có dạng:
+//   fake:<email>            -> subject = email
+//   fake:<subject>:<email>  -> subject tùy chọn (để test liên kết nhiều tài khoản)
+use super::{IdentityError, IdentityProvider, VerifiedIdentity};
+use axum::async_trait;
+
+pub struct FakeIdentityProvider;
+
+#[async_trait]
+impl IdentityProvider for FakeIdentityProvider {
+    fn name(&self) -> &'static str {
+        "fake"
+    }
+
+    async fn verify(&self, credential: &str) -> Result<VerifiedIdentity, IdentityError> {
+        let rest = credential
+            .strip_prefix("fake:")
+            .ok_or_else(|| IdentityError::InvalidToken("Sai định dạng".to_string()))?;
+
+        let (subject, email) = match rest.split_once(':') {
+            Some((subject, email)) => (subject.to_string(), email.to_string()),
+            None => (rest.to_string(), rest.to_string()),
+        };
+
+        if !email.contains('@') {
+            return Err(IdentityError::InvalidToken("Email không hợp lệ".to_string()));
+        }
+
+        Ok(VerifiedIdentity {
+            provider: self.name().to_string(),
+            subject,
+            name: email.split('@').next().map(|s| s.to_string()),
+            email: Some(email),
+            email_verified: true,
+            picture: None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::config::IdentityConfig;
+    use crate::identity::IdentityProviders;
+
+    fn providers(fake_enabled: bool) -> IdentityProviders {
+        IdentityProviders::from_config(&IdentityConfig { fake_enabled, ..Default::default() })
+    }
+
+    #[test]
+    fn registered_only_when_enabled() {
+        assert!(providers(false).get("fake").is_none());
+        assert_eq!(providers(true).names(), vec!["fake"]);
+    }
+
+    #[tokio::test]
+    async fn login_credential_uses_email_as_subject() {
+        let provider = providers(true).get("fake").unwrap();
+        let identity = provider.verify("fake:a@example.com").await.unwrap();
+
+        assert_eq!(identity.provider, "fake");
+        assert_eq!(identity.subject, "a@example.com");
+        assert_eq!(identity.email.as_deref(), Some("a@example.com"));
+        assert!(identity.email_verified);
+        assert_eq!(identity.name.as_deref(), Some("a"));
+    }
+
+    #[tokio::test]
+    async fn link_credential_keeps_subject_separate_from_email() {
+        let provider = providers(true).get("fake").unwrap();
+        let first = provider.verify("fake:sub-1:a@example.com").await.unwrap();
+        let second = provider.verify("fake:sub-2:a@example.com").await.unwrap();
+
+        // Cùng email nhưng khác subject -> 2 danh tính riêng trong user_identities
+        assert_eq!(first.subject, "sub-1");
+        assert_eq!(second.subject, "sub-2");
+        assert_eq!(first.email, second.email);
+    }
+
+    #[tokio::test]
+    async fn rejects_malformed_credentials() {
+        let provider = FakeIdentityProvider;
+        for credential in ["a@example.com", "google:a@example.com", "fake:no-email", "fake:sub:no-email"] {
+            assert!(
+                matches!(provider.verify(credential).await, Err(IdentityError::InvalidToken(_))),
+                "{}",
+                credential
+            );
+        }
+    }
+}
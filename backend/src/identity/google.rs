@@ -0,0 +1,310 @@
+// src/identity/google.rs
+// Xác thực Google ID token ngay tại server bằng JWKS (không gọi tokeninfo mỗi lần đăng nhập).
+use super::{IdentityError, IdentityProvider, VerifiedIdentity};
+use axum::async_trait;
+use jsonwebtoken::{decode, decode_header, jwk::JwkSet, Algorithm, DecodingKey, Validation};
+use reqwest::Client;
+use serde::Deserialize;
+use std::time::{Duration, Instant};
+use tokio::sync::{Mutex, RwLock};
+
+const GOOGLE_JWKS_URL: &str = "https://www.googleapis.com/oauth2/v3/certs";
+const GOOGLE_ISSUERS: [&str; 2] = ["accounts.google.com", "https://accounts.google.com"];
+// Dùng khi Google không trả Cache-Control max-age
+const DEFAULT_JWKS_TTL: Duration = Duration::from_secs(3600);
+// Gặp `kid` lạ thì tải lại JWKS, nhưng không quá 1 lần / khoảng này: token rác với kid ngẫu nhiên
+// không làm server dội request sang Google
+const MIN_REFETCH_INTERVAL: Duration = Duration::from_secs(60);
+
+#[derive(Debug, Deserialize)]
+struct GoogleIdClaims {
+    sub: String,
+    email: Option<String>,
+    #[serde(default)]
+    email_verified: bool,
+    name: Option<String>,
+    picture: Option<String>,
+}
+
+struct CachedJwks {
+    keys: JwkSet,
+    /// None = khóa cố định (không bao giờ tải lại)
+    expires_at: Option<Instant>,
+}
+
+pub struct GoogleProvider {
+    client_id: String,
+    http: Client,
+    jwks: RwLock<Option<CachedJwks>>,
+    /// Lần tải JWKS gần nhất (kể cả lỗi). Giữ lock trong lúc tải để chỉ 1 request gọi Google
+    last_fetch: Mutex<Option<Instant>>,
+}
+
+impl GoogleProvider {
+    pub fn new(client_id: String) -> Self {
+        GoogleProvider {
+            client_id,
+            http: Client::new(),
+            jwks: RwLock::new(None),
+            last_fetch: Mutex::new(None),
+        }
+    }
+
+    /// Dùng bộ khóa cố định thay vì tải từ Google (để test offline với token tự ký)
+    #[cfg(test)]
+    pub fn with_jwks(client_id: String, keys: JwkSet) -> Self {
+        GoogleProvider {
+            client_id,
+            http: Client::new(),
+            jwks: RwLock::new(Some(CachedJwks { keys, expires_at: None })),
+            last_fetch: Mutex::new(None),
+        }
+    }
+
+    async fn fetch_jwks(&self) -> Result<CachedJwks, IdentityError> {
+        let res = self.http
+            .get(GOOGLE_JWKS_URL)
+            .send()
+            .await
+            .map_err(|e| IdentityError::Upstream(e.to_string()))?;
+
+        // Cache-Control: public, max-age=19845, must-revalidate, no-transform
+        let ttl = res
+            .headers()
+            .get(reqwest::header::CACHE_CONTROL)
+            .and_then(|v| v.to_str().ok())
+            .and_then(|v| {
+                v.split(',')
+                    .find_map(|part| part.trim().strip_prefix("max-age="))
+                    .and_then(|secs| secs.parse::<u64>().ok())
+            })
+            .map(Duration::from_secs)
+            .unwrap_or(DEFAULT_JWKS_TTL);
+
+        let keys: JwkSet = res
+            .error_for_status()
+            .map_err(|e| IdentityError::Upstream(e.to_string()))?
+            .json()
+            .await
+            .map_err(|e| IdentityError::Upstream(e.to_string()))?;
+
+        Ok(CachedJwks { keys, expires_at: Some(Instant::now() + ttl) })
+    }
+
+    /// Khóa trong cache: Ok(Some) = có, Ok(None) = cần tải lại (chưa có / hết hạn / `kid` lạ)
+    async fn cached_key(&self, kid: &str) -> Result<Option<DecodingKey>, IdentityError> {
+        let cache = self.jwks.read().await;
+        let Some(cached) = cache.as_ref() else {
+            return Ok(None);
+        };
+        let fresh = cached.expires_at.is_none_or(|exp| exp > Instant::now());
+        match cached.keys.find(kid) {
+            Some(jwk) if fresh => DecodingKey::from_jwk(jwk)
+                .map(Some)
+                .map_err(|e| IdentityError::InvalidToken(e.to_string())),
+            // Khóa cố định: không tải lại
+            _ if cached.expires_at.is_none() => Err(unknown_key()),
+            _ => Ok(None),
+        }
+    }
+
+    /// Lấy khóa theo `kid`; tải lại JWKS khi cache hết hạn hoặc gặp `kid` lạ (Google xoay khóa),
+    /// tối đa 1 lần / MIN_REFETCH_INTERVAL
+    async fn decoding_key(&self, kid: &str) -> Result<DecodingKey, IdentityError> {
+        if let Some(key) = self.cached_key(kid).await? {
+            return Ok(key);
+        }
+
+        let mut last_fetch = self.last_fetch.lock().await;
+        // Request khác vừa tải xong trong lúc chờ lock
+        if let Some(key) = self.cached_key(kid).await? {
+            return Ok(key);
+        }
+        if last_fetch.is_some_and(|at| at.elapsed() < MIN_REFETCH_INTERVAL) {
+            return Err(unknown_key());
+        }
+        *last_fetch = Some(Instant::now());
+
+        let fetched = self.fetch_jwks().await?;
+        let key = fetched
+            .keys
+            .find(kid)
+            .map(DecodingKey::from_jwk)
+            .transpose()
+            .map_err(|e| IdentityError::InvalidToken(e.to_string()))?;
+
+        *self.jwks.write().await = Some(fetched);
+        key.ok_or_else(unknown_key)
+    }
+}
+
+fn unknown_key() -> IdentityError {
+    IdentityError::InvalidToken("Không tìm thấy khóa".to_string())
+}
+
+#[async_trait]
+impl IdentityProvider for GoogleProvider {
+    fn name(&self) -> &'static str {
+        "google"
+    }
+
+    async fn verify(&self, credential: &str) -> Result<VerifiedIdentity, IdentityError> {
+        let header = decode_header(credential)
+            .map_err(|e| IdentityError::InvalidToken(e.to_string()))?;
+        let kid = header
+            .kid
+            .ok_or_else(|| IdentityError::InvalidToken("Thiếu kid".to_string()))?;
+
+        let key = self.decoding_key(&kid).await?;
+
+        // Validation mặc định đã kiểm tra exp
+        let mut validation = Validation::new(Algorithm::RS256);
+        validation.set_audience(&[&self.client_id]);
+        validation.set_issuer(&GOOGLE_ISSUERS);
+
+        let claims = decode::<GoogleIdClaims>(credential, &key, &validation)
+            .map_err(|e| IdentityError::InvalidToken(e.to_string()))?
+            .claims;
+
+        if claims.email.is_none() || !claims.email_verified {
+            return Err(IdentityError::EmailNotVerified);
+        }
+
+        Ok(VerifiedIdentity {
+            provider: self.name().to_string(),
+            subject: claims.sub,
+            email: claims.email,
+            email_verified: claims.email_verified,
+            name: claims.name,
+            picture: claims.picture,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use jsonwebtoken::{encode, EncodingKey, Header};
+    use serde_json::json;
+
+    // Khóa RSA chỉ dùng cho test, modulus tương ứng nằm trong JWKS bên dưới
+    const TEST_KEY_PEM: &str = include_str!("testdata/google_test_key.pem");
+    const TEST_KEY_N: &str = "jroaIXEzmdiQgoT-ZjhTKh7Qli85m0zYayG-BQFYaaSta77uRcxWvNILRNFnK8BoU6Eyauq8LN0AWNyliOqrqwYGkbWPRqfzTwmMEG4iMv7U5RttvQul9304xf5XYg4U85nxNnCJem-w6LDzPhFIWKT_enZXiHb_MbBs6RxQ1au0c4cwBFcLnvNLHQnvYs9eRJEY3_CJy1Sa5s_f3O0NO1HeoAvU54H8LUhzYfIGqO4yTOh1-j4YRKjW-8LYZZprOIpdfhTHJfTfZIdlbvpeGCN4RHZ1GY1y9rF5C7rJs-PAU2o-BNWViNmheJLYIZxvugICG4YxfMsnUelqDFyMyQ";
+    const KID: &str = "test-key";
+    const CLIENT_ID: &str = "client-123.apps.googleusercontent.com";
+
+    fn provider() -> GoogleProvider {
+        let keys: JwkSet = serde_json::from_value(json!({
+            "keys": [{ "kty": "RSA", "kid": KID, "alg": "RS256", "use": "sig", "n": TEST_KEY_N, "e": "AQAB" }]
+        }))
+        .unwrap();
+        GoogleProvider::with_jwks(CLIENT_ID.to_string(), keys)
+    }
+
+    fn claims() -> serde_json::Value {
+        json!({
+            "iss": "https://accounts.google.com",
+            "aud": CLIENT_ID,
+            "sub": "1234567890",
+            "email": "a@example.com",
+            "email_verified": true,
+            "name": "A",
+            "exp": chrono::Utc::now().timestamp() + 3600,
+        })
+    }
+
+    fn sign(claims: &serde_json::Value, kid: &str) -> String {
+        let mut header = Header::new(Algorithm::RS256);
+        header.kid = Some(kid.to_string());
+        let key = EncodingKey::from_rsa_pem(TEST_KEY_PEM.as_bytes()).unwrap();
+        encode(&header, claims, &key).unwrap()
+    }
+
+    async fn verify_claims(claims: serde_json::Value) -> Result<VerifiedIdentity, IdentityError> {
+        provider().verify(&sign(&claims, KID)).await
+    }
+
+    #[tokio::test]
+    async fn accepts_valid_token() {
+        let identity = verify_claims(claims()).await.unwrap();
+        assert_eq!(identity.provider, "google");
+        assert_eq!(identity.subject, "1234567890");
+        assert_eq!(identity.email.as_deref(), Some("a@example.com"));
+        assert!(identity.email_verified);
+        assert_eq!(identity.name.as_deref(), Some("A"));
+    }
+
+    #[tokio::test]
+    async fn accepts_issuer_without_scheme() {
+        let mut c = claims();
+        c["iss"] = json!("accounts.google.com");
+        assert!(verify_claims(c).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn rejects_tampered_signature() {
+        // Giữ header + chữ ký của token hợp lệ nhưng thay payload
+        let token = sign(&claims(), KID);
+        let mut other = claims();
+        other["sub"] = json!("attacker");
+        let forged = sign(&other, KID);
+
+        let parts: Vec<&str> = token.split('.').collect();
+        let forged_payload = forged.split('.').nth(1).unwrap();
+        let tampered = format!("{}.{}.{}", parts[0], forged_payload, parts[2]);
+
+        assert!(matches!(provider().verify(&tampered).await, Err(IdentityError::InvalidToken(_))));
+    }
+
+    #[tokio::test]
+    async fn rejects_unknown_kid() {
+        let token = sign(&claims(), "other-key");
+        assert!(matches!(provider().verify(&token).await, Err(IdentityError::InvalidToken(_))));
+    }
+
+    #[tokio::test]
+    async fn unknown_kid_does_not_refetch_within_min_interval() {
+        // Cache tải từ Google (còn hạn) và vừa tải xong: kid lạ bị từ chối ngay, không gọi mạng
+        let fixed = provider();
+        let keys = fixed.jwks.into_inner().unwrap().keys;
+        let google = GoogleProvider::new(CLIENT_ID.to_string());
+        *google.jwks.write().await = Some(CachedJwks { keys, expires_at: Some(Instant::now() + DEFAULT_JWKS_TTL) });
+        *google.last_fetch.lock().await = Some(Instant::now());
+
+        assert!(google.verify(&sign(&claims(), KID)).await.is_ok());
+        let token = sign(&claims(), "other-key");
+        assert!(matches!(google.verify(&token).await, Err(IdentityError::InvalidToken(_))));
+    }
+
+    #[tokio::test]
+    async fn rejects_wrong_audience() {
+        let mut c = claims();
+        c["aud"] = json!("someone-else.apps.googleusercontent.com");
+        assert!(matches!(verify_claims(c).await, Err(IdentityError::InvalidToken(_))));
+    }
+
+    #[tokio::test]
+    async fn rejects_wrong_issuer() {
+        let mut c = claims();
+        c["iss"] = json!("https://evil.example.com");
+        assert!(matches!(verify_claims(c).await, Err(IdentityError::InvalidToken(_))));
+    }
+
+    #[tokio::test]
+    async fn rejects_expired_token() {
+        let mut c = claims();
+        c["exp"] = json!(chrono::Utc::now().timestamp() - 3600);
+        assert!(matches!(verify_claims(c).await, Err(IdentityError::InvalidToken(_))));
+    }
+
+    #[tokio::test]
+    async fn rejects_unverified_or_missing_email() {
+        let mut c = claims();
+        c["email_verified"] = json!(false);
+        assert!(matches!(verify_claims(c).await, Err(IdentityError::EmailNotVerified)));
+
+        let mut c = claims();
+        c.as_object_mut().unwrap().remove("email");
+        assert!(matches!(verify_claims(c).await, Err(IdentityError::EmailNotVerified)));
+    }
+}
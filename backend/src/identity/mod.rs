@@ -0,0 +1,104 @@
+// src/identity/mod.rs
+// Trừu tượng hóa nhà cung cấp danh tính (Google, Facebook, ...) cho đăng nhập mạng xã hội.
+// Mỗi provider chỉ cần xác thực `credential` phía client gửi lên và trả về VerifiedIdentity.
+pub mod google;
+pub mod facebook;
+pub mod fake;
+
+use axum::async_trait;
+use std::collections::HashMap;
+use crate::config::IdentityConfig;
+use std::fmt;
+use std::sync::Arc;
+
+pub use self::facebook::FacebookProvider;
+pub use self::fake::FakeIdentityProvider;
+pub use self::google::GoogleProvider;
+
+/// Danh tính đã được provider xác thực
+#[derive(Debug, Clone)]
+pub struct VerifiedIdentity {
+    pub provider: String,
+    /// ID duy nhất của user phía provider (`sub` của Google, `id` của Facebook)
+    pub subject: String,
+    pub email: Option<String>,
+    pub email_verified: bool,
+    pub name: Option<String>,
+    pub picture: Option<String>,
+}
+
+#[derive(Debug)]
+pub enum IdentityError {
+    /// Token sai chữ ký, hết hạn, sai aud/iss...
+    InvalidToken(String),
+    /// Provider yêu cầu email đã xác thực
+    EmailNotVerified,
+    /// Không gọi được provider (mạng, JWKS...)
+    Upstream(String),
+}
+
+impl fmt::Display for IdentityError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            IdentityError::InvalidToken(msg) => write!(f, "Token không hợp lệ: {}", msg),
+            IdentityError::EmailNotVerified => write!(f, "Email chưa được xác thực"),
+            IdentityError::Upstream(msg) => write!(f, "Lỗi kết nối provider: {}", msg),
+        }
+    }
+}
+
+#[async_trait]
+pub trait IdentityProvider: Send + Sync {
+    /// Tên provider, dùng làm khóa trong `user_identities.provider` và trong payload login
+    fn name(&self) -> &'static str;
+
+    /// Xác thực credential (ID token / access token) và trả về danh tính
+    async fn verify(&self, credential: &str) -> Result<VerifiedIdentity, IdentityError>;
+}
+
+/// Danh sách provider đang bật, lưu trong AppState
+#[derive(Clone, Default)]
+pub struct IdentityProviders {
+    providers: HashMap<&'static str, Arc<dyn IdentityProvider>>,
+}
+
+impl IdentityProviders {
+    pub fn register(&mut self, provider: Arc<dyn IdentityProvider>) {
+        self.providers.insert(provider.name(), provider);
+    }
+
+    pub fn get(&self, name: &str) -> Option<Arc<dyn IdentityProvider>> {
+        self.providers.get(name).cloned()
+    }
+
+    pub fn names(&self) -> Vec<&'static str> {
+        let mut names: Vec<&'static str> = self.providers.keys().copied().collect();
+        names.sort();
+        names
+    }
+
+    /// Bật provider theo cấu hình:
+    /// - GOOGLE_CLIENT_ID -> Google
+    /// - FACEBOOK_APP_ID + FACEBOOK_APP_SECRET -> Facebook
+    /// - IDENTITY_FAKE_ENABLED=true (Config đã chặn trong production) -> fake, cho test không cần mạng
+    pub fn from_config(config: &IdentityConfig) -> Self {
+        let mut providers = IdentityProviders::default();
+
+        match &config.google_client_id {
+            Some(client_id) => {
+                providers.register(Arc::new(GoogleProvider::new(client_id.clone())));
+            }
+            None => println!("⚠️  \x1b[33mChưa set GOOGLE_CLIENT_ID, tắt đăng nhập Google\x1b[0m"),
+        }
+
+        if let (Some(app_id), Some(app_secret)) = (&config.facebook_app_id, &config.facebook_app_secret) {
+            providers.register(Arc::new(FacebookProvider::new(app_id.clone(), app_secret.clone())));
+        }
+
+        if config.fake_enabled {
+            providers.register(Arc::new(FakeIdentityProvider));
+        }
+
+        providers
+    }
+}
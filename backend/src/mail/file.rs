@@ -0,0 +1,38 @@
+// src/mail/file.rs
+// Ghi mỗi email thành 1 file .eml trong thư mục (dev): mở bằng Thunderbird / Outlook để xem như mail thật.
+use chrono::Local;
+use std::path::PathBuf;
+
+use super::{ DeliveryError, MailTransport, OutgoingEmail };
+use crate::utils::suid;
+
+pub struct FileTransport {
+    dir: PathBuf,
+}
+
+impl FileTransport {
+    /// Tạo thư mục nếu chưa có
+    pub fn new(dir: &str) -> Result<Self, String> {
+        let dir = PathBuf::from(dir);
+        if !dir.exists() {
+            std::fs::create_dir_all(&dir).map_err(|e| format!("Không tạo được thư mục {}: {}", dir.display(), e))?;
+            println!("Đã tạo thư mục {}", dir.display());
+        }
+        Ok(FileTransport { dir })
+    }
+}
+
+impl MailTransport for FileTransport {
+    fn name(&self) -> &'static str {
+        "file"
+    }
+
+    fn send(&self, email: &OutgoingEmail) -> Result<(), DeliveryError> {
+        let message = email.to_message()?;
+        // Tên file theo thời gian để ls ra đúng thứ tự gửi
+        let path = self.dir.join(format!("{}-{}.eml", Local::now().format("%Y%m%d-%H%M%S"), suid()));
+        std::fs::write(&path, message.formatted()).map_err(|e| DeliveryError::temporary(e.to_string()))?;
+        println!("Đã ghi email tới {} vào {}", email.to, path.display());
+        Ok(())
+    }
+}
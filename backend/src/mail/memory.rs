@@ -0,0 +1,44 @@
+// src/mail/memory.rs
+// Giữ email đã gửi trong bộ nhớ để test kiểm tra nội dung (không cần tài khoản SMTP thật).
+use std::sync::{ Arc, Mutex, OnceLock };
+
+use super::{ DeliveryError, MailTransport, OutgoingEmail };
+
+#[derive(Clone, Default)]
+pub struct MemoryTransport {
+    sent: Arc<Mutex<Vec<OutgoingEmail>>>,
+}
+
+static GLOBAL: OnceLock<MemoryTransport> = OnceLock::new();
+
+impl MemoryTransport {
+    /// Bản dùng chung cho cả tiến trình: `[mail] transport = "memory"` gửi vào đây
+    pub fn global() -> &'static MemoryTransport {
+        GLOBAL.get_or_init(MemoryTransport::default)
+    }
+}
+
+// Chỉ test dùng để đọc lại email đã gửi
+#[cfg(test)]
+impl MemoryTransport {
+    /// Các email đã gửi, cũ trước
+    pub fn messages(&self) -> Vec<OutgoingEmail> {
+        self.sent.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    /// Lấy ra và xóa các email đã gửi
+    pub fn take(&self) -> Vec<OutgoingEmail> {
+        std::mem::take(&mut *self.sent.lock().unwrap_or_else(|e| e.into_inner()))
+    }
+}
+
+impl MailTransport for MemoryTransport {
+    fn name(&self) -> &'static str {
+        "memory"
+    }
+
+    fn send(&self, email: &OutgoingEmail) -> Result<(), DeliveryError> {
+        self.sent.lock().unwrap_or_else(|e| e.into_inner()).push(email.clone());
+        Ok(())
+    }
+}
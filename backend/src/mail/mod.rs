@@ -0,0 +1,103 @@
+// src/mail/mod.rs
+// Trừu tượng hóa cách gửi email: SMTP thật, ghi file .eml (dev) hoặc giữ trong bộ nhớ (test).
+// Worker outbox chỉ gọi `MailTransport::send`, transport chọn theo [mail].transport.
+pub mod file;
+pub mod memory;
+pub mod smtp;
+
+use crate::config::{ MailConfig, SmtpConfig };
+use lettre::message::header::ContentType;
+use lettre::message::{ Attachment, Mailbox, MultiPart };
+use lettre::Message;
+use std::fmt;
+use std::sync::Arc;
+
+pub use self::file::FileTransport;
+pub use self::memory::MemoryTransport;
+pub use self::smtp::SmtpMailTransport;
+
+/// 1 email đã render, sẵn sàng gửi
+#[derive(Debug, Clone)]
+pub struct OutgoingEmail {
+    pub from: Mailbox,
+    pub to: Mailbox,
+    pub subject: String,
+    pub html: String,
+    pub text: String,
+    pub attachments: Vec<EmailAttachment>,
+}
+
+/// File đính kèm đã có nội dung (vd hóa đơn PDF)
+#[derive(Debug, Clone)]
+pub struct EmailAttachment {
+    pub filename: String,
+    pub content_type: String,
+    pub data: Vec<u8>,
+}
+
+impl OutgoingEmail {
+    /// Message MIME: phần HTML + phần text thuần (cho client không hiển thị HTML), có file đính kèm thì
+    /// bọc thêm multipart/mixed
+    pub fn to_message(&self) -> Result<Message, DeliveryError> {
+        let body = MultiPart::alternative_plain_html(self.text.clone(), self.html.clone());
+        let body = if self.attachments.is_empty() {
+            body
+        } else {
+            let mut mixed = MultiPart::mixed().multipart(body);
+            for file in &self.attachments {
+                let content_type = ContentType::parse(&file.content_type)
+                    .map_err(|e| DeliveryError::permanent(format!("Content-Type không hợp lệ: {}", e)))?;
+                mixed = mixed.singlepart(Attachment::new(file.filename.clone()).body(file.data.clone(), content_type));
+            }
+            mixed
+        };
+        Message::builder()
+            .from(self.from.clone())
+            .to(self.to.clone())
+            .subject(self.subject.as_str())
+            .multipart(body)
+            .map_err(|e| DeliveryError::permanent(e.to_string()))
+    }
+}
+
+/// Lỗi gửi 1 email. `permanent`: thử lại cũng không được (địa chỉ sai, server từ chối 5xx)
+#[derive(Debug)]
+pub struct DeliveryError {
+    pub permanent: bool,
+    pub message: String,
+}
+
+impl DeliveryError {
+    pub fn permanent(message: String) -> Self {
+        DeliveryError { permanent: true, message }
+    }
+
+    pub fn temporary(message: String) -> Self {
+        DeliveryError { permanent: false, message }
+    }
+}
+
+impl fmt::Display for DeliveryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.message)
+    }
+}
+
+/// Blocking: worker gọi trong spawn_blocking
+pub trait MailTransport: Send + Sync {
+    /// Tên transport ("smtp" | "file" | "memory"), dùng khi log
+    fn name(&self) -> &'static str;
+
+    fn send(&self, email: &OutgoingEmail) -> Result<(), DeliveryError>;
+}
+
+/// Tạo transport theo cấu hình (đã validate trong Config::load).
+/// SMTP chưa có tài khoản -> None (không gửi, email nằm chờ trong outbox)
+pub fn from_config(mail: &MailConfig, smtp: &SmtpConfig) -> Result<Option<Arc<dyn MailTransport>>, String> {
+    match mail.transport.as_str() {
+        "file" => Ok(Some(Arc::new(FileTransport::new(&mail.file_dir)?))),
+        "memory" => Ok(Some(Arc::new(MemoryTransport::global().clone()))),
+        _ if !smtp.is_configured() => Ok(None),
+        _ => Ok(Some(Arc::new(SmtpMailTransport::new(smtp)?))),
+    }
+}
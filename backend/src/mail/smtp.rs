@@ -0,0 +1,52 @@
+// src/mail/smtp.rs
+// Gửi qua SMTP server bất kỳ (Gmail, SES, Mailpit/MailHog chạy local...).
+// Transport dựng 1 lần và giữ pool kết nối, không mở kết nối mới cho mỗi email.
+use lettre::transport::smtp::authentication::Credentials;
+use lettre::{ SmtpTransport, Transport };
+use std::time::Duration;
+
+use super::{ DeliveryError, MailTransport, OutgoingEmail };
+use crate::config::SmtpConfig;
+
+// Timeout mỗi lệnh SMTP. Relay treo thì báo lỗi tạm thời sớm thay vì giữ khóa outbox
+// (utils::outbox::CLAIM_TIMEOUT_SECS) đến hết hạn rồi bị instance khác gửi trùng
+const SMTP_TIMEOUT: Duration = Duration::from_secs(20);
+
+pub struct SmtpMailTransport {
+    transport: SmtpTransport,
+}
+
+impl SmtpMailTransport {
+    pub fn new(config: &SmtpConfig) -> Result<Self, String> {
+        // tls: TLS ngay từ đầu (port 465) | starttls: nâng cấp TLS (port 587) | none: không mã hóa (server test local)
+        let builder = match config.tls.as_str() {
+            "starttls" => SmtpTransport::starttls_relay(&config.host),
+            "none" => Ok(SmtpTransport::builder_dangerous(&config.host)),
+            _ => SmtpTransport::relay(&config.host),
+        };
+        let mut builder = builder.map_err(|e| format!("Lỗi cấu hình SMTP {}: {}", config.host, e))?;
+
+        if let (Some(username), Some(password)) = (&config.username, &config.password) {
+            builder = builder.credentials(Credentials::new(username.clone(), password.clone()));
+        }
+        if let Some(port) = config.port {
+            builder = builder.port(port);
+        }
+        builder = builder.timeout(Some(SMTP_TIMEOUT));
+        Ok(SmtpMailTransport { transport: builder.build() })
+    }
+}
+
+impl MailTransport for SmtpMailTransport {
+    fn name(&self) -> &'static str {
+        "smtp"
+    }
+
+    fn send(&self, email: &OutgoingEmail) -> Result<(), DeliveryError> {
+        let message = email.to_message()?;
+        self.transport.send(&message).map(|_| ()).map_err(|e| DeliveryError {
+            permanent: e.is_permanent(),
+            message: e.to_string(),
+        })
+    }
+}
@@ -1,7 +1,7 @@
 use axum::{
     extract::Request, // Chỉ cần Request cho middleware
     middleware::{ self, Next },
-    response::{ Html, Response },
+    response::Response,
     routing::Router,
     http::{Method, HeaderValue, header},
 };
@@ -12,19 +12,30 @@ use dotenvy::dotenv;
 use std::io::{ self, Write };
 use chrono::Local;
 use regex::Regex;
-use std::fs;
 use std::path::Path;
+use std::sync::Arc;
 
 // 1. Khai báo và Import Module Route
 mod utils;
 mod routes;
+mod identity;
+mod config;
+mod db;
+mod storage;
+mod mail;
+mod seed;
 // use routes::{ auth, user };
-use routes::{ auth, categories, products, orders, admin, reviews, upload, cart, contact };
+use routes::{ auth, categories, products, orders, admin, rbac, reviews, upload, cart, contact };
 
 // 2. Phục hồi AppState (AppState cần pub để được dùng trong module user)
 #[derive(Clone)]
 pub struct AppState {
     pub db: MySqlPool,
+    pub config: Arc<config::Config>,
+    pub identity: Arc<identity::IdentityProviders>,
+    pub auth_cache: Arc<auth::AuthCache>,
+    pub permissions: Arc<rbac::PermissionCache>,
+    pub storage: Arc<dyn storage::BlobStore>,
 }
 
 // Lưu ý: User struct đã được chuyển sang src/routes/user.rs để giữ main.rs gọn gàng.
@@ -61,7 +72,7 @@ fn redraw(logs: &Vec<String>) {
         let content = format!("\x1b[90m[{}]\x1b[0m {}", time, entry);
 
         let visible = visible_len(&format!("[{}] {}", time, entry));
-        let padding = if max_len > visible { max_len - visible } else { 0 };
+        let padding = max_len.saturating_sub(visible);
 
         println!("│ {}{} │", content, " ".repeat(padding));
     }
@@ -103,32 +114,130 @@ async fn my_logging_middleware(req: Request, next: Next) -> Response {
 }
 
 // ==- KHẮC PHỤC LỖI E0601: MAIN FUNCTION NOT FOUND ==-
+// Lệnh hỗ trợ:
+//   (không tham số)  chạy server
+//   migrate          chạy migration rồi thoát
+//   seed [--products N] [--users N] [--orders N] [--seed S]   chạy migration + sinh dữ liệu demo
 #[tokio::main]
 async fn main() {
     dotenv().ok();
-    print!("\x1b[2J\x1b[H");
-    let port: u16 = std::env
-        ::var("PORT")
-        .ok()
-        .and_then(|s| s.parse().ok())
-        .unwrap_or(3000);
-    let database_url = std::env::var("DATABASE_URL").expect("Chưa set DATABASE_URL trong .env");
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let command = args.first().cloned();
+    let seed_opts = match command.as_deref() {
+        None | Some("migrate") => None,
+        Some("seed") => match seed::SeedOptions::parse(&args[1..]) {
+            Ok(opts) => Some(opts),
+            Err(e) => {
+                eprintln!("{}", e);
+                std::process::exit(2);
+            }
+        },
+        Some(other) => {
+            eprintln!("Lệnh không hợp lệ: {}. Dùng: my_rust_module [migrate | seed --products N]", other);
+            std::process::exit(2);
+        }
+    };
+    if command.is_none() {
+        print!("\x1b[2J\x1b[H");
+    }
+    // Đọc + kiểm tra cấu hình trước khi làm gì khác, sai thì dừng luôn
+    let config = match config::Config::load() {
+        Ok(c) => c,
+        Err(e) => {
+            eprintln!("\x1b[31m{}\x1b[0m", e);
+            std::process::exit(1);
+        }
+    };
+    // Gửi mail: SMTP, ghi file .eml hoặc giữ trong bộ nhớ, theo [mail].transport
+    match mail::from_config(&config.mail, &config.smtp) {
+        Ok(Some(transport)) => {
+            println!("✉️  Gửi email qua: {}", transport.name());
+            utils::email::init(transport, config.smtp.sender_address());
+        }
+        Ok(None) => {}
+        Err(e) => {
+            eprintln!("\x1b[31mKhông khởi tạo được mail transport: {}\x1b[0m", e);
+            std::process::exit(1);
+        }
+    }
 
     // Khắc phục lỗi DB connect (thêm .connect)
     let pool = MySqlPoolOptions::new()
-        .max_connections(5)
-        .connect(&database_url).await
+        .max_connections(config.database.max_connections)
+        .min_connections(config.database.min_connections)
+        .acquire_timeout(std::time::Duration::from_secs(config.database.acquire_timeout_secs))
+        .connect(&config.database.url).await
         .expect("\x1b[31mKhông thể kết nối đến MySQL\x1b[0m");
     println!("✅ \x1b[32mĐã kết nối MySQL thành công!\x1b[0m");
 
-    let state = AppState { db: pool };
+    if command.is_some() || config.database.auto_migrate {
+        if let Err(e) = db::run_migrations(&pool).await {
+            eprintln!("\x1b[31mLỗi migration: {}\x1b[0m", e);
+            std::process::exit(1);
+        }
+    }
+
+    if let Some(opts) = seed_opts {
+        if config.is_production() {
+            eprintln!("\x1b[31mKhông seed dữ liệu demo trong production\x1b[0m");
+            std::process::exit(1);
+        }
+        if let Err(e) = seed::run(&pool, &opts).await {
+            eprintln!("\x1b[31m{}\x1b[0m", e);
+            std::process::exit(1);
+        }
+    }
+    if command.is_some() {
+        return;
+    }
+
+    let identity = identity::IdentityProviders::from_config(&config.identity);
+    println!("🔑 Provider đăng nhập: {:?}", identity.names());
+
+    // File upload: ổ đĩa local hoặc S3-compatible, theo [storage].backend
+    let blob_store = match storage::from_config(&config.storage) {
+        Ok(store) => store,
+        Err(e) => {
+            eprintln!("\x1b[31mKhông khởi tạo được storage: {}\x1b[0m", e);
+            std::process::exit(1);
+        }
+    };
+    println!("🗄️  Storage upload: {} ({})", blob_store.name(), blob_store.public_base());
+
+    // Template email admin đã sửa (bảng email_templates), đồng bộ định kỳ giữa các instance
+    if let Err(e) = utils::templates::load_overrides(&pool).await {
+        println!("⚠️  \x1b[33mKhông nạp được email template từ DB, dùng bản mặc định: {:?}\x1b[0m", e);
+    }
+    utils::templates::spawn_override_refresh(pool.clone());
+
+    // Gửi email từ outbox (thử lại khi lỗi). Chưa cấu hình gửi mail thì email nằm chờ, cấu hình xong sẽ được gửi
+    if utils::email::is_enabled() {
+        utils::outbox::spawn_worker(pool.clone());
+    } else {
+        println!("⚠️  \x1b[33mKhông chạy worker gửi email, email được giữ trong bảng email_outbox\x1b[0m");
+    }
+
+    // Dọn file upload không còn sản phẩm / đánh giá nào dùng
+    utils::media::spawn_gc(pool.clone(), blob_store.clone(), config.storage.media_gc_grace_hours);
 
-    // Cấu hình CORS (Đã sửa lỗi allow_any_origin)
-    // main.rs
-   let frontend_url = "http://localhost:8080"; 
+    let config = Arc::new(config);
+    let state = AppState {
+        db: pool,
+        config: config.clone(),
+        identity: Arc::new(identity),
+        auth_cache: Arc::new(auth::AuthCache::default()),
+        permissions: Arc::new(rbac::PermissionCache::default()),
+        storage: blob_store,
+    };
+
+    // Cấu hình CORS (origin đã được validate trong Config::load)
+    let origins: Vec<HeaderValue> = config.cors.origins
+        .iter()
+        .map(|o| o.parse::<HeaderValue>().unwrap())
+        .collect();
 
     let cors_layer = CorsLayer::new()
-        .allow_origin(frontend_url.parse::<HeaderValue>().unwrap()) 
+        .allow_origin(origins)
         .allow_methods([Method::GET, Method::POST, Method::PUT, Method::DELETE])
         .allow_headers([header::CONTENT_TYPE, header::AUTHORIZATION])
         .allow_credentials(true);
@@ -136,14 +245,8 @@ async fn main() {
     //     .allow_methods(tower_http::cors::Any)
     //     .allow_headers(tower_http::cors::Any);
 
-    // Kiểm tra folder storages nếu chưa có thì tạo
-    if !Path::new("storages").exists() {
-        fs::create_dir_all("storages").expect("Không tạo được folder storages");
-        println!("Đã tạo thư mục storages");
-    }
-
-    let spa_service = ServeDir::new("public").fallback(ServeFile::new("public/index.html"));
-    let spa_storages = ServeDir::new("storages");
+    let public_dir = Path::new(&config.storage.public_dir);
+    let spa_service = ServeDir::new(public_dir).fallback(ServeFile::new(public_dir.join("index.html")));
 
     let app = Router::new()
         // Auth
@@ -161,20 +264,26 @@ async fn main() {
         .nest("/api/cart", cart::cart_routes())
         .nest("/api/contact", contact::contact_routes())
 
-        // Các route còn lại trong main
+        .fallback_service(spa_service);
 
-        .nest_service("/storages", spa_storages)
+    // Backend local: tự phục vụ file upload. Backend S3: client tải thẳng từ bucket/CDN
+    let app = if config.storage.backend == "local" {
+        app.nest_service(storage::local::LOCAL_PUBLIC_BASE, ServeDir::new(&config.storage.upload_dir))
+    } else {
+        app
+    };
 
-        .fallback_service(spa_service)
+    let app = app
 
         // Áp dụng Middleware và CORS
         .layer(middleware::from_fn(my_logging_middleware))
         .layer(cors_layer)
         .with_state(state);
 
-    let addr_str = format!("localhost:{}", port);
+    let addr_str = format!("{}:{}", config.server.host, config.server.port);
     println!("🚀 \x1b[34mServer đang lắng nghe trên http://{}\x1b[0m", addr_str);
 
     let listener = tokio::net::TcpListener::bind(&addr_str).await.unwrap();
-    axum::serve(listener, app).await.unwrap();
+    // ConnectInfo để lấy IP client cho bảng sessions
+    axum::serve(listener, app.into_make_service_with_connect_info::<std::net::SocketAddr>()).await.unwrap();
 }
@@ -1,67 +1,32 @@
 use axum::{
-    extract::{ State, FromRequestParts, Path, Json },
-    http::{ StatusCode, request::Parts },
+    extract::{ State, Path, Json, Query },
+    http::StatusCode,
     response::IntoResponse,
+    http::HeaderMap,
     Router,
-    routing::{ get, put, post, delete },
-    async_trait,
+    routing::{ get, put, post },
 };
 use crate::AppState;
-use crate::routes::auth::AuthUser;
+use crate::routes::auth::{ ClientInfo, UserResponse };
+use crate::routes::rbac::{ self, perm, Permission, RequirePermission };
+use crate::routes::{ email_outbox, email_templates };
+use crate::routes::orders::{ OrderHistory, order_email_items, update_user_level };
+use crate::utils::audit::{ self, record_audit, AuditEntry };
 use crate::utils::suid;
+use crate::utils::image::StoredImage;
+use crate::utils::media::{ self, REF_PRODUCT };
+use crate::routes::products::notify_product_watchers;
+use crate::routes::reviews::recompute_product_rating;
 // --- IMPORT QUAN TRỌNG ĐỂ SỬA LỖI ---
 use serde::{ Deserialize, Serialize };
-use sqlx::FromRow;
+use sqlx::{ FromRow, MySql, QueryBuilder, Transaction };
 // ------------------------------------
 use rust_decimal::Decimal;
 use serde_json::Value;
-use crate::utils::email::{ send_order_shipping_email, send_order_thank_you_email };
-use rust_decimal::prelude::ToPrimitive;
-// --- MIDDLEWARE ---
-pub struct AdminUser(pub String);
-
-// --- HELPER FORMAT TIỀN TỆ (Thay thế cho {:,.0}) ---
-fn format_money(amount: f64) -> String {
-    let s = (amount as i64).to_string();
-    let mut result = String::new();
-    let len = s.len();
-    for (i, c) in s.chars().enumerate() {
-        if i > 0 && (len - i) % 3 == 0 {
-            result.push(','); // Thêm dấu phẩy ngăn cách
-        }
-        result.push(c);
-    }
-    format!("{} đ", result)
-}
-
-#[async_trait]
-impl<S> FromRequestParts<S> for AdminUser where S: Send + Sync, AppState: From<S>, S: Clone {
-    type Rejection = (StatusCode, Json<serde_json::Value>);
-
-    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
-        let auth_user = AuthUser::from_request_parts(parts, state).await?;
-
-        let app_state = AppState::from(state.clone());
-        let role: (String,) = sqlx
-            ::query_as("SELECT role FROM users WHERE id = ?")
-            .bind(&auth_user.user_id)
-            .fetch_one(&app_state.db).await
-            .map_err(|_| (
-                StatusCode::UNAUTHORIZED,
-                Json(serde_json::json!({"message": "User không tồn tại"})),
-            ))?;
-
-        if role.0 == "admin" {
-            Ok(AdminUser(auth_user.user_id))
-        } else {
-            Err((
-                StatusCode::FORBIDDEN,
-                Json(serde_json::json!({"message": "Không có quyền Admin"})),
-            ))
-        }
-    }
-}
-
+use crate::utils::{ recipient_language, send_order_shipping_email, send_order_thank_you_email, send_review_reply_email, EmailError, Money };
+use crate::utils::notify;
+use crate::utils::outbox;
+use crate::utils::invoice;
 // --- HANDLERS: ORDERS ---
 
 #[derive(Deserialize)]
@@ -69,9 +34,9 @@ struct UpdateStatusReq {
     status: String,
 }
 
-async fn get_all_orders(State(state): State<AppState>, _: AdminUser) -> impl IntoResponse {
+async fn get_all_orders(State(state): State<AppState>, _: RequirePermission<perm::OrdersRead>) -> impl IntoResponse {
     let orders = sqlx
-        ::query_as::<_, crate::routes::orders::OrderHistory>(
+        ::query_as::<_, OrderHistory>(
             "SELECT id, final_amount, status, points_earned, created_at FROM orders ORDER BY created_at DESC"
         )
         .fetch_all(&state.db).await;
@@ -85,83 +50,99 @@ async fn get_all_orders(State(state): State<AppState>, _: AdminUser) -> impl Int
 // src/routes/admin.rs
 
 // --- HÀM QUAN TRỌNG: UPDATE ORDER STATUS ---
+
+// Đổi trạng thái + cộng/trừ điểm + email + audit trong transaction của handler.
+// Lỗi bất kỳ bước nào -> Err để handler rollback cả cụm. Không có đơn -> Ok(false)
+async fn apply_order_status(
+    tx: &mut Transaction<'_, MySql>,
+    actor_id: &str,
+    ip: Option<&str>,
+    id: &str,
+    new_status: &str
+) -> Result<bool, EmailError> {
+    // 1. Lấy thông tin đơn hàng cũ (khóa dòng để 2 admin đổi cùng lúc không cộng điểm 2 lần)
+    let order_info: Option<(String, String, i32, String, Money)> = sqlx::query_as(
+        "SELECT o.status, o.user_id, o.points_earned, u.email, o.final_amount 
+         FROM orders o 
+         JOIN users u ON o.user_id = u.id 
+         WHERE o.id = ?
+         FOR UPDATE"
+    )
+    .bind(id)
+    .fetch_optional(&mut **tx).await?;
+
+    let Some((old_status, user_id, points, email, final_amount)) = order_info else {
+        return Ok(false);
+    };
+
+    // Case 1: Chuyển sang SHIPPING -> Gửi mail
+    if old_status != "shipping" && new_status == "shipping" {
+        // Dữ liệu từng dòng sản phẩm, template tự escape tên sản phẩm
+        let email_items = order_email_items(&mut *tx, id).await?;
+
+        let lang = recipient_language(&mut **tx, &email).await;
+        send_order_shipping_email(&mut **tx, email.clone(), lang, id.to_string(), email_items, final_amount).await?;
+    }
+
+    // Case 2: Hoàn thành -> Cộng điểm
+    if old_status != "completed" && new_status == "completed" {
+        sqlx::query("UPDATE users SET points = points + ? WHERE id = ?")
+            .bind(points).bind(&user_id).execute(&mut **tx).await?;
+
+        let lang = recipient_language(&mut **tx, &email).await;
+        send_order_thank_you_email(&mut **tx, email.clone(), lang, id.to_string(), points).await?;
+    }
+    // Case 3: Hủy hoàn thành -> Trừ điểm
+    else if old_status == "completed" && new_status != "completed" {
+        sqlx::query("UPDATE users SET points = points - ? WHERE id = ?")
+            .bind(points).bind(&user_id).execute(&mut **tx).await?;
+    }
+
+    // Cập nhật trạng thái
+    sqlx::query("UPDATE orders SET status = ? WHERE id = ?")
+        .bind(new_status).bind(id).execute(&mut **tx).await?;
+
+    record_audit(&mut **tx, AuditEntry {
+        actor_id,
+        action: "orders.update_status",
+        target_type: "order",
+        target_id: id,
+        before: Some(serde_json::json!({ "status": old_status })),
+        after: Some(serde_json::json!({ "status": new_status })),
+        reason: None,
+        ip,
+    }).await?;
+
+    Ok(true)
+}
+
 async fn update_order_status(
     State(state): State<AppState>, 
-    _: AdminUser, 
+    admin: RequirePermission<perm::OrdersUpdateStatus>, 
+    client: ClientInfo,
     Path(id): Path<String>,
     Json(payload): Json<UpdateStatusReq>
 ) -> impl IntoResponse {
-    let mut tx = state.db.begin().await.unwrap();
+    let mut tx = match state.db.begin().await {
+        Ok(tx) => tx,
+        Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, Json("Error")).into_response(),
+    };
 
-    // 1. Lấy thông tin đơn hàng cũ
-    let order_info: Option<(String, String, i32, String, Decimal)> = sqlx::query_as(
-        "SELECT o.status, o.user_id, o.points_earned, u.email, o.final_amount 
-         FROM orders o 
-         JOIN users u ON o.user_id = u.id 
-         WHERE o.id = ?"
-    )
-    .bind(&id)
-    .fetch_optional(&mut *tx).await.unwrap_or(None);
-
-    if let Some((old_status, user_id, points, email, final_amount)) = order_info {
-        let new_status = payload.status.as_str();
-
-        // Case 1: Chuyển sang SHIPPING -> Gửi mail
-        if old_status != "shipping" && new_status == "shipping" {
-            let items: Vec<(String, i32, Decimal)> = sqlx::query_as(
-                "SELECT p.name, oi.quantity, oi.price 
-                 FROM order_items oi 
-                 JOIN products p ON oi.product_id = p.id 
-                 WHERE oi.order_id = ?"
-            )
-            .bind(&id)
-            .fetch_all(&mut *tx).await.unwrap_or(vec![]);
-
-            // Tạo HTML Table Rows
-            let items_html = items.iter().map(|(name, qty, price)| {
-                let total_line = price * Decimal::from(*qty);
-                
-                // SỬA LỖI Ở ĐÂY: Dùng hàm helper format_money thay vì {:,.0}
-                let price_str = format_money(price.to_f64().unwrap_or(0.0));
-                let total_str = format_money(total_line.to_f64().unwrap_or(0.0));
-                
-                // Dùng {{ }} để escape ngoặc nhọn của CSS
-                format!(
-                    "<tr>
-                        <td style='padding: 8px; border-bottom: 1px solid #ddd;'>{}</td>
-                        <td style='padding: 8px; border-bottom: 1px solid #ddd; text-align: center;'>{}</td>
-                        <td style='padding: 8px; border-bottom: 1px solid #ddd; text-align: right;'>{}</td>
-                        <td style='padding: 8px; border-bottom: 1px solid #ddd; text-align: right;'>{}</td>
-                    </tr>", 
-                    name, qty, price_str, total_str
-                )
-            }).collect::<Vec<String>>().join("");
-
-            // SỬA LỖI Ở ĐÂY: Dùng hàm helper
-            let total_bill_str = format_money(final_amount.to_f64().unwrap_or(0.0));
-
-            send_order_shipping_email(email.clone(), id.clone(), items_html, total_bill_str);
-        }
-
-        // Case 2: Hoàn thành -> Cộng điểm
-        if old_status != "completed" && new_status == "completed" {
-            let _ = sqlx::query("UPDATE users SET points = points + ? WHERE id = ?")
-                .bind(points).bind(&user_id).execute(&mut *tx).await;
-            
-            send_order_thank_you_email(email.clone(), id.clone(), points);
-        } 
-        // Case 3: Hủy hoàn thành -> Trừ điểm
-        else if old_status == "completed" && new_status != "completed" {
-            let _ = sqlx::query("UPDATE users SET points = points - ? WHERE id = ?")
-                .bind(points).bind(&user_id).execute(&mut *tx).await;
-        }
-
-        // Cập nhật trạng thái
-        let _ = sqlx::query("UPDATE orders SET status = ? WHERE id = ?")
-            .bind(new_status).bind(&id).execute(&mut *tx).await;
+    match apply_order_status(&mut tx, &admin.user_id, client.ip.as_deref(), &id, &payload.status).await {
+        Ok(true) => {}
+        Ok(false) => {
+            let _ = tx.rollback().await;
+            return (StatusCode::NOT_FOUND, Json("Không tìm thấy đơn hàng")).into_response();
+        }
+        Err(e) => {
+            println!("Lỗi update_order_status: {:?}", e);
+            let _ = tx.rollback().await;
+            return (StatusCode::INTERNAL_SERVER_ERROR, Json("Lỗi cập nhật đơn hàng")).into_response();
+        }
     }
 
     if tx.commit().await.is_ok() {
+        outbox::wake();
         (StatusCode::OK, Json("Updated")).into_response()
     } else {
         (StatusCode::INTERNAL_SERVER_ERROR, Json("Error")).into_response()
@@ -174,28 +155,79 @@ async fn update_order_status(
 struct CreateProductReq {
     category_id: String,
     name: String,
-    price: Decimal,
+    price: Money,
     stock: i32,
-    images: Option<Vec<String>>,
+    images: Option<Vec<StoredImage>>,
     description: Option<String>,
     specs: Option<Value>,
 }
 
+// Ảnh chụp trạng thái sản phẩm để ghi audit (trước/sau khi sửa)
+#[derive(Debug, Serialize, FromRow)]
+struct ProductSnapshot {
+    category_id: String,
+    name: String,
+    price: Money,
+    stock: i32,
+    images: Option<sqlx::types::Json<Vec<StoredImage>>>,
+    description: Option<String>,
+    specs: Option<sqlx::types::Json<Value>>,
+    is_deleted: Option<bool>,
+}
+
+// Giá sản phẩm: không âm, không lẻ hơn đơn vị nhỏ nhất của tiền (VND: 1 đồng)
+fn valid_price(price: &Money) -> bool {
+    !price.is_negative() && !price.has_sub_minor_units()
+}
+
+async fn product_snapshot(
+    tx: &mut Transaction<'_, MySql>,
+    id: &str
+) -> Result<Option<ProductSnapshot>, sqlx::Error> {
+    sqlx
+        ::query_as::<_, ProductSnapshot>(
+            "SELECT category_id, name, price, stock, images, description, specs, is_deleted
+             FROM products WHERE id = ? FOR UPDATE"
+        )
+        .bind(id)
+        .fetch_optional(&mut **tx).await
+}
+
 async fn create_product(
     State(state): State<AppState>,
-    _: AdminUser,
+    admin: RequirePermission<perm::ProductsWrite>,
+    client: ClientInfo,
     Json(payload): Json<CreateProductReq>
 ) -> impl IntoResponse {
+    if !valid_price(&payload.price) {
+        return (StatusCode::BAD_REQUEST, Json("Giá không hợp lệ (không âm, không lẻ dưới 1 đồng)")).into_response();
+    }
     let id = suid();
 
     let images_json = sqlx::types::Json(payload.images.unwrap_or(vec![]));
+    let image_urls: Vec<String> = images_json.iter().map(|img| img.url.clone()).collect();
     let specs_json = sqlx::types::Json(payload.specs.unwrap_or(serde_json::json!({})));
 
+    let after = serde_json::json!({
+        "category_id": payload.category_id,
+        "name": payload.name,
+        "price": payload.price,
+        "stock": payload.stock,
+        "images": images_json,
+        "description": payload.description,
+        "specs": specs_json,
+    });
+
+    let mut tx = match state.db.begin().await {
+        Ok(tx) => tx,
+        Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, Json("Error")).into_response(),
+    };
+
     let res = sqlx
         ::query(
             "INSERT INTO products (id, category_id, name, price, stock, images, description, specs) VALUES (?, ?, ?, ?, ?, ?, ?, ?)"
         )
-        .bind(id)
+        .bind(&id)
         .bind(payload.category_id)
         .bind(payload.name)
         .bind(payload.price)
@@ -203,27 +235,72 @@ async fn create_product(
         .bind(images_json)
         .bind(payload.description)
         .bind(specs_json)
-        .execute(&state.db).await;
+        .execute(&mut *tx).await;
 
-    match res {
+    if let Err(e) = res {
+        println!("Lỗi create_product: {:?}", e);
+        let _ = tx.rollback().await;
+        return (StatusCode::INTERNAL_SERVER_ERROR, Json("Error DB")).into_response();
+    }
+
+    if let Err(e) = media::sync_refs(&mut tx, REF_PRODUCT, &id, &image_urls).await {
+        println!("Lỗi create_product (media): {:?}", e);
+        let _ = tx.rollback().await;
+        return (StatusCode::INTERNAL_SERVER_ERROR, Json("Error DB")).into_response();
+    }
+
+    let audit = record_audit(&mut *tx, AuditEntry {
+        actor_id: &admin.user_id,
+        action: "products.create",
+        target_type: "product",
+        target_id: &id,
+        before: None,
+        after: Some(after),
+        reason: None,
+        ip: client.ip.as_deref(),
+    }).await;
+
+    if audit.is_err() {
+        let _ = tx.rollback().await;
+        return (StatusCode::INTERNAL_SERVER_ERROR, Json("Lỗi ghi audit log")).into_response();
+    }
+
+    match tx.commit().await {
         Ok(_) => (StatusCode::CREATED, Json("Created")).into_response(),
-        Err(e) => {
-            println!("Lỗi create_product: {:?}", e);
-            (StatusCode::INTERNAL_SERVER_ERROR, Json("Error DB")).into_response()
-        }
+        Err(_) => (StatusCode::INTERNAL_SERVER_ERROR, Json("Error DB")).into_response(),
     }
 }
 
 // Thêm hàm update_product để hỗ trợ sửa sản phẩm (PUT)
 async fn update_product(
     State(state): State<AppState>,
-    _: AdminUser,
+    admin: RequirePermission<perm::ProductsWrite>,
+    client: ClientInfo,
     Path(id): Path<String>,
     Json(payload): Json<CreateProductReq>
 ) -> impl IntoResponse {
+    if !valid_price(&payload.price) {
+        return (StatusCode::BAD_REQUEST, Json("Giá không hợp lệ (không âm, không lẻ dưới 1 đồng)")).into_response();
+    }
     let images_json = sqlx::types::Json(payload.images.unwrap_or(vec![]));
+    let image_urls: Vec<String> = images_json.iter().map(|img| img.url.clone()).collect();
     let specs_json = sqlx::types::Json(payload.specs.unwrap_or(serde_json::json!({})));
 
+    let mut tx = match state.db.begin().await {
+        Ok(tx) => tx,
+        Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, Json("Error")).into_response(),
+    };
+
+    // Lưu lại bản cũ để ghi audit + báo cho người đăng ký theo dõi (tồn kho & giá)
+    let old = match product_snapshot(&mut tx, &id).await {
+        Ok(Some(p)) => p,
+        Ok(None) => return (StatusCode::NOT_FOUND, Json("Không tìm thấy sản phẩm")).into_response(),
+        Err(e) => {
+            println!("Lỗi update_product: {:?}", e);
+            return (StatusCode::INTERNAL_SERVER_ERROR, Json("Error DB")).into_response();
+        }
+    };
+
     let res = sqlx
         ::query(
             "UPDATE products SET category_id=?, name=?, price=?, stock=?, images=?, description=?, specs=? WHERE id=?"
@@ -235,53 +312,492 @@ async fn update_product(
         .bind(images_json)
         .bind(payload.description)
         .bind(specs_json)
-        .bind(id)
-        .execute(&state.db).await;
+        .bind(&id)
+        .execute(&mut *tx).await;
 
-    match res {
-        Ok(_) => (StatusCode::OK, Json("Updated")).into_response(),
-        Err(e) => {
-            println!("Lỗi update_product: {:?}", e);
-            (StatusCode::INTERNAL_SERVER_ERROR, Json("Error DB")).into_response()
+    if let Err(e) = res {
+        println!("Lỗi update_product: {:?}", e);
+        let _ = tx.rollback().await;
+        return (StatusCode::INTERNAL_SERVER_ERROR, Json("Error DB")).into_response();
+    }
+
+    // Ảnh bị bỏ khỏi sản phẩm sẽ được GC dọn sau thời gian ân hạn
+    if let Err(e) = media::sync_refs(&mut tx, REF_PRODUCT, &id, &image_urls).await {
+        println!("Lỗi update_product (media): {:?}", e);
+        let _ = tx.rollback().await;
+        return (StatusCode::INTERNAL_SERVER_ERROR, Json("Error DB")).into_response();
+    }
+
+    let new = match product_snapshot(&mut tx, &id).await {
+        Ok(Some(p)) => p,
+        _ => {
+            let _ = tx.rollback().await;
+            return (StatusCode::INTERNAL_SERVER_ERROR, Json("Error DB")).into_response();
         }
+    };
+
+    // Vừa chạm ngưỡng sắp hết hàng -> báo nhân viên kho
+    if let Err(e) = notify::stock_changed(&mut tx, &id, old.stock, new.stock).await {
+        println!("Lỗi update_product (low stock): {:?}", e);
+        let _ = tx.rollback().await;
+        return (StatusCode::INTERNAL_SERVER_ERROR, Json("Error DB")).into_response();
+    }
+
+    // Chỉ lưu các trường thay đổi (VD: giá 10tr -> 9tr)
+    let (before, after) = audit::diff(&serde_json::json!(old), &serde_json::json!(new));
+    let audit = record_audit(&mut *tx, AuditEntry {
+        actor_id: &admin.user_id,
+        action: "products.update",
+        target_type: "product",
+        target_id: &id,
+        before: Some(before),
+        after: Some(after),
+        reason: None,
+        ip: client.ip.as_deref(),
+    }).await;
+
+    if audit.is_err() {
+        let _ = tx.rollback().await;
+        return (StatusCode::INTERNAL_SERVER_ERROR, Json("Lỗi ghi audit log")).into_response();
+    }
+
+    if tx.commit().await.is_err() {
+        return (StatusCode::INTERNAL_SERVER_ERROR, Json("Error DB")).into_response();
+    }
+
+    outbox::wake();
+    notify_product_watchers(&state.db, &id, old.stock, new.stock, old.price, new.price).await;
+    (StatusCode::OK, Json("Updated")).into_response()
+}
+
+// Điều chỉnh tồn kho (nhập thêm hàng / trừ hàng hỏng), delta có thể âm
+#[derive(Deserialize)]
+struct AdjustStockReq {
+    delta: i32,
+    reason: Option<String>,
+}
+
+async fn adjust_stock(
+    State(state): State<AppState>,
+    admin: RequirePermission<perm::InventoryWrite>,
+    client: ClientInfo,
+    Path(id): Path<String>,
+    Json(payload): Json<AdjustStockReq>
+) -> impl IntoResponse {
+    let mut tx = match state.db.begin().await {
+        Ok(tx) => tx,
+        Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, Json("Error")).into_response(),
+    };
+
+    let old: Option<(i32, Money)> = sqlx
+        ::query_as("SELECT stock, price FROM products WHERE id = ? FOR UPDATE")
+        .bind(&id)
+        .fetch_optional(&mut *tx).await
+        .unwrap_or(None);
+
+    let (old_stock, price) = match old {
+        Some(row) => row,
+        None => return (StatusCode::NOT_FOUND, Json("Không tìm thấy sản phẩm")).into_response(),
+    };
+
+    let new_stock = match old_stock.checked_add(payload.delta) {
+        Some(v) => v,
+        None => return (StatusCode::BAD_REQUEST, Json("Số lượng điều chỉnh không hợp lệ")).into_response(),
+    };
+    if new_stock < 0 {
+        return (StatusCode::BAD_REQUEST, Json("Tồn kho không đủ")).into_response();
+    }
+
+    let res = sqlx
+        ::query("UPDATE products SET stock = ? WHERE id = ?")
+        .bind(new_stock)
+        .bind(&id)
+        .execute(&mut *tx).await;
+
+    let res = match res {
+        Ok(_) => notify::stock_changed(&mut tx, &id, old_stock, new_stock).await,
+        Err(e) => Err(e.into()),
+    };
+    if let Err(e) = res {
+        println!("Lỗi adjust_stock: {:?}", e);
+        let _ = tx.rollback().await;
+        return (StatusCode::INTERNAL_SERVER_ERROR, Json("Error DB")).into_response();
+    }
+
+    let audit = record_audit(&mut *tx, AuditEntry {
+        actor_id: &admin.user_id,
+        action: "products.adjust_stock",
+        target_type: "product",
+        target_id: &id,
+        before: Some(serde_json::json!({ "stock": old_stock })),
+        after: Some(serde_json::json!({ "stock": new_stock, "delta": payload.delta })),
+        reason: payload.reason.as_deref(),
+        ip: client.ip.as_deref(),
+    }).await;
+
+    if audit.is_err() {
+        let _ = tx.rollback().await;
+        return (StatusCode::INTERNAL_SERVER_ERROR, Json("Lỗi ghi audit log")).into_response();
+    }
+
+    if tx.commit().await.is_err() {
+        return (StatusCode::INTERNAL_SERVER_ERROR, Json("Error DB")).into_response();
     }
+
+    outbox::wake();
+    notify_product_watchers(&state.db, &id, old_stock, new_stock, price, price).await;
+    (StatusCode::OK, Json(serde_json::json!({ "stock": new_stock }))).into_response()
 }
 
 // src/routes/admin.rs
 
 async fn delete_product(
     State(state): State<AppState>,
-    _: AdminUser,
+    admin: RequirePermission<perm::ProductsWrite>,
+    client: ClientInfo,
     Path(id): Path<String>
 ) -> impl IntoResponse {
+    let mut tx = match state.db.begin().await {
+        Ok(tx) => tx,
+        Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, Json("Error")).into_response(),
+    };
+
+    let old = match product_snapshot(&mut tx, &id).await {
+        Ok(Some(p)) => p,
+        Ok(None) => return (StatusCode::NOT_FOUND, Json("Không tìm thấy sản phẩm")).into_response(),
+        Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, Json("Error DB")).into_response(),
+    };
+
     // THAY ĐỔI: Không dùng DELETE nữa, chuyển sang UPDATE
+    // (giữ tham chiếu media: đơn hàng cũ / khôi phục sản phẩm vẫn cần ảnh)
     let res = sqlx
         ::query("UPDATE products SET is_deleted = TRUE WHERE id = ?")
-        .bind(id)
-        .execute(&state.db).await;
+        .bind(&id)
+        .execute(&mut *tx).await;
 
-    match res {
+    if let Err(e) = res {
+        println!("Lỗi delete_product: {:?}", e);
+        let _ = tx.rollback().await;
+        return (StatusCode::INTERNAL_SERVER_ERROR, Json("Error DB")).into_response();
+    }
+
+    let audit = record_audit(&mut *tx, AuditEntry {
+        actor_id: &admin.user_id,
+        action: "products.delete",
+        target_type: "product",
+        target_id: &id,
+        before: Some(serde_json::json!(old)),
+        after: Some(serde_json::json!({ "is_deleted": true })),
+        reason: None,
+        ip: client.ip.as_deref(),
+    }).await;
+
+    if audit.is_err() {
+        let _ = tx.rollback().await;
+        return (StatusCode::INTERNAL_SERVER_ERROR, Json("Lỗi ghi audit log")).into_response();
+    }
+
+    match tx.commit().await {
         Ok(_) => (StatusCode::OK, Json("Deleted successfully (Soft delete)")).into_response(),
+        Err(_) => (StatusCode::INTERNAL_SERVER_ERROR, Json("Error DB")).into_response(),
+    }
+}
+
+// --- HANDLERS: USERS ---
+
+#[derive(Debug, Deserialize)]
+pub struct UserFilter {
+    pub search: Option<String>, // Tìm theo email / tên
+    pub level: Option<String>,
+    pub role: Option<String>,
+    pub status: Option<String>,
+    pub page: Option<i64>,
+    pub limit: Option<i64>,
+}
+
+// Tổng số bản ghi trả qua header X-Total-Count, body vẫn là mảng như cũ
+async fn get_all_users(
+    State(state): State<AppState>,
+    _: RequirePermission<perm::UsersRead>,
+    Query(filter): Query<UserFilter>
+) -> impl IntoResponse {
+    let limit = filter.limit.unwrap_or(50).clamp(1, 200);
+    let page = filter.page.unwrap_or(1).max(1);
+
+    fn push_filters<'a>(qb: &mut QueryBuilder<'a, MySql>, filter: &'a UserFilter) {
+        if let Some(search) = &filter.search {
+            let pattern = format!("%{}%", search.trim());
+            qb.push(" AND (email LIKE ").push_bind(pattern.clone())
+                .push(" OR name LIKE ").push_bind(pattern)
+                .push(")");
+        }
+        if let Some(level) = &filter.level {
+            qb.push(" AND level = ").push_bind(level);
+        }
+        if let Some(role) = &filter.role {
+            qb.push(" AND role = ").push_bind(role);
+        }
+        if let Some(status) = &filter.status {
+            qb.push(" AND status = ").push_bind(status);
+        }
+    }
+
+    let mut count_qb: QueryBuilder<MySql> = QueryBuilder::new("SELECT COUNT(*) FROM users WHERE 1 = 1");
+    push_filters(&mut count_qb, &filter);
+    let total: (i64,) = count_qb.build_query_as().fetch_one(&state.db).await.unwrap_or((0,));
+
+    let mut qb: QueryBuilder<MySql> = QueryBuilder::new(
+        "SELECT id, email, name, picture, role, status, points, level, phone, address FROM users WHERE 1 = 1"
+    );
+    push_filters(&mut qb, &filter);
+    qb.push(" ORDER BY created_at DESC LIMIT ")
+        .push_bind(limit)
+        .push(" OFFSET ")
+        .push_bind((page - 1) * limit);
+
+    let users = qb.build_query_as::<UserResponse>().fetch_all(&state.db).await;
+
+    match users {
+        Ok(data) => {
+            let mut headers = HeaderMap::new();
+            headers.insert("x-total-count", total.0.into());
+            (StatusCode::OK, headers, Json(data)).into_response()
+        }
         Err(e) => {
-            println!("Lỗi delete_product: {:?}", e);
-            (StatusCode::INTERNAL_SERVER_ERROR, Json("Error DB")).into_response()
+            println!("Lỗi get_all_users: {:?}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, Json("Error")).into_response()
         }
     }
 }
 
-// --- HANDLERS: USERS ---
+#[derive(Debug, Serialize, FromRow)]
+pub struct UserReviewItem {
+    pub id: String,
+    pub product_id: String,
+    pub product_name: String,
+    pub rating: i32,
+    pub content: Option<String>,
+    pub status: String,
+    pub created_at: Option<chrono::NaiveDateTime>,
+}
 
-async fn get_all_users(State(state): State<AppState>, _: AdminUser) -> impl IntoResponse {
-    let users = sqlx
-        ::query_as::<_, crate::routes::auth::UserResponse>(
-            "SELECT id, email, name, picture, role, status, points, level, phone, address FROM users"
+// Chi tiết 1 user: thông tin + đơn hàng + đánh giá + liên hệ
+async fn get_user_detail(
+    State(state): State<AppState>,
+    _: RequirePermission<perm::UsersRead>,
+    Path(id): Path<String>
+) -> impl IntoResponse {
+    let user = sqlx
+        ::query_as::<_, UserResponse>(
+            "SELECT id, email, name, picture, role, status, points, level, phone, address FROM users WHERE id = ?"
         )
-        .fetch_all(&state.db).await;
+        .bind(&id)
+        .fetch_optional(&state.db).await;
 
-    match users {
-        Ok(data) => (StatusCode::OK, Json(data)).into_response(),
-        Err(_) => (StatusCode::INTERNAL_SERVER_ERROR, Json("Error")).into_response(),
+    let user = match user {
+        Ok(Some(u)) => u,
+        Ok(None) => return (StatusCode::NOT_FOUND, Json("Không tìm thấy user")).into_response(),
+        Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, Json("Error")).into_response(),
+    };
+
+    let orders = sqlx
+        ::query_as::<_, OrderHistory>(
+            "SELECT id, final_amount, status, points_earned, created_at FROM orders WHERE user_id = ? ORDER BY created_at DESC"
+        )
+        .bind(&id)
+        .fetch_all(&state.db).await
+        .unwrap_or(vec![]);
+
+    let reviews = sqlx
+        ::query_as::<_, UserReviewItem>(
+            "SELECT r.id, r.product_id, p.name as product_name, r.rating, r.content, r.status, r.created_at
+             FROM reviews r
+             JOIN products p ON r.product_id = p.id
+             WHERE r.user_id = ?
+             ORDER BY r.created_at DESC"
+        )
+        .bind(&id)
+        .fetch_all(&state.db).await
+        .unwrap_or(vec![]);
+
+    let contacts = sqlx
+        ::query_as::<_, ContactItem>(
+            "SELECT c.id, c.user_id, u.name as user_name, c.email, c.message, c.status, c.created_at
+             FROM contacts c
+             LEFT JOIN users u ON c.user_id = u.id
+             WHERE c.user_id = ?
+             ORDER BY c.created_at DESC"
+        )
+        .bind(&id)
+        .fetch_all(&state.db).await
+        .unwrap_or(vec![]);
+
+    (StatusCode::OK, Json(serde_json::json!({
+        "user": user,
+        "orders": orders,
+        "reviews": reviews,
+        "contacts": contacts,
+    }))).into_response()
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateUserReq {
+    pub status: Option<String>,     // active | locked
+    pub role: Option<String>,       // Tên role trong bảng roles (user, staff, warehouse, support, admin)
+    pub points_delta: Option<i32>,  // Cộng (+) / trừ (-) điểm thủ công
+    pub reason: Option<String>,     // Bắt buộc khi điều chỉnh điểm
+}
+
+// Khóa/mở khóa, đổi quyền, cộng/trừ điểm. Mọi thay đổi đều ghi audit log.
+async fn update_user(
+    State(state): State<AppState>,
+    admin: RequirePermission<perm::UsersWrite>,
+    client: ClientInfo,
+    Path(id): Path<String>,
+    Json(payload): Json<UpdateUserReq>
+) -> impl IntoResponse {
+    // 1. Validate
+    if let Some(status) = &payload.status {
+        if status != "active" && status != "locked" {
+            return (StatusCode::BAD_REQUEST, Json("Trạng thái không hợp lệ")).into_response();
+        }
+    }
+    let actor_perms = match rbac::role_permissions(&state, &admin.role).await {
+        Ok(p) => p,
+        Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, Json("Error")).into_response(),
+    };
+    if let Some(role) = &payload.role {
+        // Đổi role cần thêm quyền users.manage_roles, và không được gán role có quyền vượt quá mình
+        if !rbac::has_permission(&actor_perms, perm::UsersManageRoles::NAME) {
+            return (StatusCode::FORBIDDEN, Json("Không có quyền đổi role")).into_response();
+        }
+        let exists: Option<(String,)> = sqlx
+            ::query_as("SELECT name FROM roles WHERE name = ?")
+            .bind(role)
+            .fetch_optional(&state.db).await
+            .unwrap_or(None);
+        if exists.is_none() {
+            return (StatusCode::BAD_REQUEST, Json("Role không hợp lệ")).into_response();
+        }
+        let target_perms = match rbac::role_permissions(&state, role).await {
+            Ok(p) => p,
+            Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, Json("Error")).into_response(),
+        };
+        if target_perms.iter().any(|p| !rbac::has_permission(&actor_perms, p)) {
+            return (StatusCode::FORBIDDEN, Json("Không thể gán role có quyền cao hơn quyền của bạn")).into_response();
+        }
+    }
+    let reason = payload.reason.as_deref().map(str::trim).filter(|r| !r.is_empty());
+    if payload.points_delta.is_some_and(|d| d != 0) && reason.is_none() {
+        return (StatusCode::BAD_REQUEST, Json("Vui lòng nhập lý do điều chỉnh điểm")).into_response();
+    }
+    // Tránh admin tự khóa / tự đổi role của mình
+    if id == admin.user_id && (payload.status.as_deref() == Some("locked") || payload.role.is_some()) {
+        return (StatusCode::BAD_REQUEST, Json("Không thể tự khóa hoặc hạ quyền chính mình")).into_response();
     }
+
+    let mut tx = match state.db.begin().await {
+        Ok(tx) => tx,
+        Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, Json("Error")).into_response(),
+    };
+
+    // 2. Trạng thái trước khi sửa
+    let before: Option<(String, String, i32, String)> = sqlx
+        ::query_as("SELECT status, role, points, level FROM users WHERE id = ? FOR UPDATE")
+        .bind(&id)
+        .fetch_optional(&mut *tx).await
+        .unwrap_or(None);
+
+    let (old_status, old_role, old_points, old_level) = match before {
+        Some(row) => row,
+        None => return (StatusCode::NOT_FOUND, Json("Không tìm thấy user")).into_response(),
+    };
+
+    // Không được sửa (khóa, hạ role, trừ điểm...) user đang giữ role có quyền vượt quá mình
+    let current_perms = match rbac::role_permissions(&state, &old_role).await {
+        Ok(p) => p,
+        Err(_) => {
+            let _ = tx.rollback().await;
+            return (StatusCode::INTERNAL_SERVER_ERROR, Json("Error")).into_response();
+        }
+    };
+    if current_perms.iter().any(|p| !rbac::has_permission(&actor_perms, p)) {
+        let _ = tx.rollback().await;
+        return (StatusCode::FORBIDDEN, Json("Không thể sửa tài khoản có quyền cao hơn quyền của bạn")).into_response();
+    }
+
+    // 3. Cập nhật
+    let new_status = payload.status.clone().unwrap_or(old_status.clone());
+    let new_role = payload.role.clone().unwrap_or(old_role.clone());
+    let delta = payload.points_delta.unwrap_or(0);
+
+    match old_points.checked_add(delta) {
+        Some(points) if points < 0 => return (StatusCode::BAD_REQUEST, Json("Không đủ điểm để trừ")).into_response(),
+        Some(_) => {}
+        None => return (StatusCode::BAD_REQUEST, Json("Số điểm điều chỉnh không hợp lệ")).into_response(),
+    }
+
+    let res = sqlx
+        ::query("UPDATE users SET status = ?, role = ?, points = points + ? WHERE id = ?")
+        .bind(&new_status)
+        .bind(&new_role)
+        .bind(delta)
+        .bind(&id)
+        .execute(&mut *tx).await;
+
+    if res.is_err() {
+        let _ = tx.rollback().await;
+        return (StatusCode::INTERNAL_SERVER_ERROR, Json("Error DB")).into_response();
+    }
+
+    if delta != 0 {
+        update_user_level(&mut tx, &id).await;
+    }
+
+    let after: (String, String, i32, String) = match sqlx
+        ::query_as("SELECT status, role, points, level FROM users WHERE id = ?")
+        .bind(&id)
+        .fetch_one(&mut *tx).await
+    {
+        Ok(row) => row,
+        Err(_) => {
+            let _ = tx.rollback().await;
+            return (StatusCode::INTERNAL_SERVER_ERROR, Json("Error DB")).into_response();
+        }
+    };
+
+    // 4. Audit log (chung transaction)
+    let audit = record_audit(&mut *tx, AuditEntry {
+        actor_id: &admin.user_id,
+        action: "users.update",
+        target_type: "user",
+        target_id: &id,
+        before: Some(serde_json::json!({
+            "status": old_status, "role": old_role, "points": old_points, "level": old_level
+        })),
+        after: Some(serde_json::json!({
+            "status": after.0, "role": after.1, "points": after.2, "level": after.3,
+            "points_delta": delta
+        })),
+        reason,
+        ip: client.ip.as_deref(),
+    }).await;
+
+    if audit.is_err() {
+        let _ = tx.rollback().await;
+        return (StatusCode::INTERNAL_SERVER_ERROR, Json("Lỗi ghi audit log")).into_response();
+    }
+
+    if tx.commit().await.is_err() {
+        return (StatusCode::INTERNAL_SERVER_ERROR, Json("Error")).into_response();
+    }
+
+    // Khóa / đổi quyền có hiệu lực ngay, không chờ cache AuthUser hết hạn
+    state.auth_cache.invalidate_user(&id);
+
+    (StatusCode::OK, Json(serde_json::json!({
+        "status": after.0, "role": after.1, "points": after.2, "level": after.3
+    }))).into_response()
 }
 
 // --- HANDLERS: SETTINGS ---
@@ -298,7 +814,7 @@ pub struct UpdateSettingReq {
     pub settings: Vec<SettingItem>,
 }
 
-async fn get_settings(State(state): State<AppState>, _: AdminUser) -> impl IntoResponse {
+async fn get_settings(State(state): State<AppState>, _: RequirePermission<perm::SettingsRead>) -> impl IntoResponse {
     let settings = sqlx
         ::query_as::<_, SettingItem>("SELECT * FROM settings")
         .fetch_all(&state.db).await;
@@ -308,23 +824,84 @@ async fn get_settings(State(state): State<AppState>, _: AdminUser) -> impl IntoR
     }
 }
 
+// Ghi 1 setting + audit log. Không đổi gì thì bỏ qua, tránh rác trong audit log
+async fn save_setting(
+    tx: &mut Transaction<'_, MySql>,
+    actor_id: &str,
+    ip: Option<&str>,
+    item: &SettingItem
+) -> Result<(), sqlx::Error> {
+    let old: Option<(Option<String>,)> = sqlx
+        ::query_as("SELECT value FROM settings WHERE id = ? FOR UPDATE")
+        .bind(&item.id)
+        .fetch_optional(&mut **tx).await?;
+
+    if let Some((old_value,)) = &old {
+        if *old_value == item.value {
+            return Ok(());
+        }
+    }
+
+    // Dùng ON DUPLICATE KEY UPDATE để vừa insert vừa update
+    sqlx
+        ::query(
+            "INSERT INTO settings (id, value) VALUES (?, ?) ON DUPLICATE KEY UPDATE value = ?"
+        )
+        .bind(&item.id)
+        .bind(&item.value)
+        .bind(&item.value)
+        .execute(&mut **tx).await?;
+
+    record_audit(&mut **tx, AuditEntry {
+        actor_id,
+        action: "settings.update",
+        target_type: "setting",
+        target_id: &item.id,
+        before: old.map(|(value,)| serde_json::json!({ "value": value })),
+        after: Some(serde_json::json!({ "value": item.value })),
+        reason: None,
+        ip,
+    }).await
+}
+
 async fn update_settings(
     State(state): State<AppState>,
-    _: AdminUser,
+    admin: RequirePermission<perm::SettingsWrite>,
+    client: ClientInfo,
     Json(payload): Json<UpdateSettingReq>
 ) -> impl IntoResponse {
-    let mut tx = state.db.begin().await.unwrap();
+    // Danh sách email nhận thông báo + ngưỡng sắp hết hàng phải hợp lệ, tránh mail rơi vào dead
+    for item in &payload.settings {
+        let value = item.value.as_deref().unwrap_or("");
+        if notify::RECIPIENT_SETTINGS.contains(&item.id.as_str()) {
+            if let Some(bad) = notify::parse_recipients(value)
+                .into_iter()
+                .find(|e| e.parse::<lettre::message::Mailbox>().is_err())
+            {
+                return (StatusCode::BAD_REQUEST, Json(format!("Email không hợp lệ trong {}: {}", item.id, bad))).into_response();
+            }
+        }
+        if item.id == notify::SETTING_LOW_STOCK_THRESHOLD && value.trim().parse::<u32>().is_err() {
+            return (StatusCode::BAD_REQUEST, Json("Ngưỡng sắp hết hàng phải là số nguyên >= 0".to_string())).into_response();
+        }
+        if item.id == invoice::SETTING_VAT_RATE
+            && !value.trim().parse::<Decimal>().is_ok_and(|r| r >= Decimal::ZERO && r <= Decimal::ONE_HUNDRED)
+        {
+            return (StatusCode::BAD_REQUEST, Json("Thuế suất GTGT phải là số từ 0 đến 100".to_string())).into_response();
+        }
+    }
+
+    let mut tx = match state.db.begin().await {
+        Ok(tx) => tx,
+        Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, Json("Error")).into_response(),
+    };
 
     for item in payload.settings {
-        // Dùng ON DUPLICATE KEY UPDATE để vừa insert vừa update
-        let _ = sqlx
-            ::query(
-                "INSERT INTO settings (id, value) VALUES (?, ?) ON DUPLICATE KEY UPDATE value = ?"
-            )
-            .bind(&item.id)
-            .bind(&item.value)
-            .bind(&item.value)
-            .execute(&mut *tx).await;
+        if let Err(e) = save_setting(&mut tx, &admin.user_id, client.ip.as_deref(), &item).await {
+            println!("Lỗi update_settings {}: {:?}", item.id, e);
+            let _ = tx.rollback().await;
+            return (StatusCode::INTERNAL_SERVER_ERROR, Json("Lỗi lưu cài đặt")).into_response();
+        }
     }
 
     if tx.commit().await.is_ok() {
@@ -338,16 +915,14 @@ async fn update_settings(
 
 #[derive(Serialize)]
 struct AnalyticsData {
-    revenue_month: f64,
+    revenue_month: Money,
     new_orders: i64,
     new_users: i64,
     top_product: String,
 }
 
-async fn get_analytics(State(state): State<AppState>, _: AdminUser) -> impl IntoResponse {
-    use rust_decimal::prelude::ToPrimitive;
-
-    let revenue: (Option<Decimal>,) = sqlx
+async fn get_analytics(State(state): State<AppState>, _: RequirePermission<perm::AnalyticsRead>) -> impl IntoResponse {
+    let revenue: (Option<Money>,) = sqlx
         ::query_as("SELECT SUM(final_amount) FROM orders")
         .fetch_one(&state.db).await
         .unwrap_or((None,));
@@ -385,7 +960,7 @@ async fn get_analytics(State(state): State<AppState>, _: AdminUser) -> impl Into
     };
 
     let data = AnalyticsData {
-        revenue_month: revenue.0.unwrap_or(Decimal::ZERO).to_f64().unwrap_or(0.0),
+        revenue_month: revenue.0.unwrap_or(Money::vnd(0)),
         new_orders: orders_count.0,
         new_users: users_count.0,
         top_product: top_prod,
@@ -409,7 +984,7 @@ pub struct ContactItem {
 // --- HANDLERS CONTACT ---
 
 // 1. Lấy danh sách liên hệ (Mới nhất lên đầu)
-async fn get_all_contacts(State(state): State<AppState>, _: AdminUser) -> impl IntoResponse {
+async fn get_all_contacts(State(state): State<AppState>, _: RequirePermission<perm::ContactsRead>) -> impl IntoResponse {
     let sql = "
         SELECT c.id, c.user_id, u.name as user_name, c.email, c.message, c.status, c.created_at 
         FROM contacts c
@@ -430,22 +1005,628 @@ async fn get_all_contacts(State(state): State<AppState>, _: AdminUser) -> impl I
 // 2. Cập nhật trạng thái (Đã xử lý / Chưa xử lý)
 async fn update_contact_status(
     State(state): State<AppState>,
-    _: AdminUser,
+    admin: RequirePermission<perm::ContactsWrite>,
+    client: ClientInfo,
     Path(id): Path<String>,
     Json(payload): Json<UpdateStatusReq> // Tái sử dụng struct UpdateStatusReq cũ
 ) -> impl IntoResponse {
+    let mut tx = match state.db.begin().await {
+        Ok(tx) => tx,
+        Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, Json("Error")).into_response(),
+    };
+
+    let old: Option<(String,)> = sqlx::query_as("SELECT status FROM contacts WHERE id = ? FOR UPDATE")
+        .bind(&id)
+        .fetch_optional(&mut *tx)
+        .await
+        .unwrap_or(None);
+
+    let old_status = match old {
+        Some((status,)) => status,
+        None => return (StatusCode::NOT_FOUND, Json("Không tìm thấy liên hệ")).into_response(),
+    };
+
     let res = sqlx::query("UPDATE contacts SET status = ? WHERE id = ?")
-        .bind(payload.status)
-        .bind(id)
-        .execute(&state.db)
+        .bind(&payload.status)
+        .bind(&id)
+        .execute(&mut *tx)
         .await;
 
-    match res {
+    if res.is_err() {
+        let _ = tx.rollback().await;
+        return (StatusCode::INTERNAL_SERVER_ERROR, Json("Error")).into_response();
+    }
+
+    let audit = record_audit(&mut *tx, AuditEntry {
+        actor_id: &admin.user_id,
+        action: "contacts.update_status",
+        target_type: "contact",
+        target_id: &id,
+        before: Some(serde_json::json!({ "status": old_status })),
+        after: Some(serde_json::json!({ "status": payload.status })),
+        reason: None,
+        ip: client.ip.as_deref(),
+    }).await;
+
+    if audit.is_err() {
+        let _ = tx.rollback().await;
+        return (StatusCode::INTERNAL_SERVER_ERROR, Json("Lỗi ghi audit log")).into_response();
+    }
+
+    match tx.commit().await {
         Ok(_) => (StatusCode::OK, Json("Updated")).into_response(),
         Err(_) => (StatusCode::INTERNAL_SERVER_ERROR, Json("Error")).into_response(),
     }
 }
 
+// --- HANDLERS: REVIEWS (KIỂM DUYỆT) ---
+
+const REVIEW_STATUSES: [&str; 4] = ["pending", "approved", "rejected", "hidden"];
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct AdminReviewItem {
+    pub id: String,
+    pub product_id: String,
+    pub product_name: String,
+    pub user_id: String,
+    pub user_name: Option<String>,
+    pub user_email: String,
+    pub rating: i32,
+    pub content: Option<String>,
+    pub verified_purchase: bool,
+    pub status: String,
+    pub created_at: Option<chrono::NaiveDateTime>,
+    pub reply_content: Option<String>,
+    pub replied_at: Option<chrono::NaiveDateTime>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AdminReviewFilter {
+    pub status: Option<String>,
+    pub product_id: Option<String>,
+    pub rating: Option<i32>,
+    pub max_rating: Option<i32>,    // VD: max_rating=2 -> đánh giá 1-2 sao
+    pub unanswered: Option<bool>,   // true -> chỉ lấy đánh giá chưa trả lời
+    pub search: Option<String>,
+    pub page: Option<i64>,
+    pub limit: Option<i64>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct BulkReviewStatusReq {
+    pub ids: Vec<String>,
+    pub status: String,
+}
+
+// 1. Danh sách đánh giá + lọc theo trạng thái / sản phẩm / số sao / nội dung
+async fn get_all_reviews(
+    State(state): State<AppState>,
+    _: RequirePermission<perm::ReviewsRead>,
+    Query(filter): Query<AdminReviewFilter>
+) -> impl IntoResponse {
+    let limit = filter.limit.unwrap_or(20).clamp(1, 100);
+    let page = filter.page.unwrap_or(1).max(1);
+
+    let mut qb: QueryBuilder<MySql> = QueryBuilder::new(
+        "SELECT r.id, r.product_id, p.name as product_name, r.user_id, u.name as user_name,
+                u.email as user_email, r.rating, r.content, r.verified_purchase, r.status, r.created_at,
+                rr.content as reply_content, rr.created_at as replied_at
+         FROM reviews r
+         JOIN users u ON r.user_id = u.id
+         JOIN products p ON r.product_id = p.id
+         LEFT JOIN review_replies rr ON rr.review_id = r.id
+         WHERE 1 = 1"
+    );
+
+    if let Some(status) = &filter.status {
+        qb.push(" AND r.status = ").push_bind(status.clone());
+    }
+    if let Some(product_id) = &filter.product_id {
+        qb.push(" AND r.product_id = ").push_bind(product_id.clone());
+    }
+    if let Some(rating) = filter.rating {
+        qb.push(" AND r.rating = ").push_bind(rating);
+    }
+    if let Some(max_rating) = filter.max_rating {
+        qb.push(" AND r.rating <= ").push_bind(max_rating);
+    }
+    if filter.unanswered == Some(true) {
+        qb.push(" AND rr.id IS NULL");
+    }
+    if let Some(search) = &filter.search {
+        qb.push(" AND r.content LIKE ").push_bind(format!("%{}%", search));
+    }
+
+    qb.push(" ORDER BY r.created_at DESC LIMIT ")
+        .push_bind(limit)
+        .push(" OFFSET ")
+        .push_bind((page - 1) * limit);
+
+    let reviews = qb.build_query_as::<AdminReviewItem>().fetch_all(&state.db).await;
+
+    match reviews {
+        Ok(data) => (StatusCode::OK, Json(data)).into_response(),
+        Err(e) => {
+            println!("Lỗi get_all_reviews: {:?}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, Json("Error")).into_response()
+        }
+    }
+}
+
+// Đổi trạng thái một loạt đánh giá rồi tính lại rating các sản phẩm liên quan
+async fn set_reviews_status(
+    db: &sqlx::MySqlPool,
+    ids: &[String],
+    status: &str,
+    actor_id: &str,
+    ip: Option<&str>
+) -> Result<u64, sqlx::Error> {
+    let mut tx = db.begin().await?;
+    let mut product_ids: Vec<String> = vec![];
+    let mut updated = 0;
+
+    for id in ids {
+        let row: Option<(String, String)> = sqlx::query_as("SELECT product_id, status FROM reviews WHERE id = ? FOR UPDATE")
+            .bind(id)
+            .fetch_optional(&mut *tx).await?;
+
+        if let Some((product_id, old_status)) = row {
+            updated += sqlx::query("UPDATE reviews SET status = ? WHERE id = ?")
+                .bind(status)
+                .bind(id)
+                .execute(&mut *tx).await?
+                .rows_affected();
+
+            record_audit(&mut *tx, AuditEntry {
+                actor_id,
+                action: "reviews.update_status",
+                target_type: "review",
+                target_id: id,
+                before: Some(serde_json::json!({ "status": old_status })),
+                after: Some(serde_json::json!({ "status": status })),
+                reason: None,
+                ip,
+            }).await?;
+
+            if !product_ids.contains(&product_id) {
+                product_ids.push(product_id);
+            }
+        }
+    }
+
+    for product_id in &product_ids {
+        recompute_product_rating(&mut tx, product_id).await?;
+    }
+
+    tx.commit().await?;
+    Ok(updated)
+}
+
+// 2. Duyệt / từ chối / ẩn một đánh giá
+async fn update_review_status(
+    State(state): State<AppState>,
+    admin: RequirePermission<perm::ReviewsModerate>,
+    client: ClientInfo,
+    Path(id): Path<String>,
+    Json(payload): Json<UpdateStatusReq>
+) -> impl IntoResponse {
+    if !REVIEW_STATUSES.contains(&payload.status.as_str()) {
+        return (StatusCode::BAD_REQUEST, Json("Trạng thái không hợp lệ")).into_response();
+    }
+
+    match set_reviews_status(&state.db, &[id], &payload.status, &admin.user_id, client.ip.as_deref()).await {
+        Ok(0) => (StatusCode::NOT_FOUND, Json("Không tìm thấy đánh giá")).into_response(),
+        Ok(_) => (StatusCode::OK, Json("Updated")).into_response(),
+        Err(e) => {
+            println!("Lỗi update_review_status: {:?}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, Json("Error")).into_response()
+        }
+    }
+}
+
+// 3. Duyệt / từ chối hàng loạt
+async fn bulk_update_review_status(
+    State(state): State<AppState>,
+    admin: RequirePermission<perm::ReviewsModerate>,
+    client: ClientInfo,
+    Json(payload): Json<BulkReviewStatusReq>
+) -> impl IntoResponse {
+    if payload.status != "approved" && payload.status != "rejected" {
+        return (StatusCode::BAD_REQUEST, Json("Chỉ hỗ trợ approved / rejected")).into_response();
+    }
+    if payload.ids.is_empty() {
+        return (StatusCode::BAD_REQUEST, Json("Danh sách trống")).into_response();
+    }
+
+    match set_reviews_status(&state.db, &payload.ids, &payload.status, &admin.user_id, client.ip.as_deref()).await {
+        Ok(updated) => (StatusCode::OK, Json(serde_json::json!({ "updated": updated }))).into_response(),
+        Err(e) => {
+            println!("Lỗi bulk_update_review_status: {:?}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, Json("Error")).into_response()
+        }
+    }
+}
+
+// 4. Trả lời công khai một đánh giá (tạo mới hoặc sửa) + gửi mail cho người đánh giá
+#[derive(Debug, Deserialize)]
+pub struct ReviewReplyReq {
+    pub content: String,
+}
+
+async fn reply_review(
+    State(state): State<AppState>,
+    admin: RequirePermission<perm::ReviewsReply>,
+    client: ClientInfo,
+    Path(id): Path<String>,
+    Json(payload): Json<ReviewReplyReq>
+) -> impl IntoResponse {
+    let content = payload.content.trim();
+    if content.is_empty() {
+        return (StatusCode::BAD_REQUEST, Json("Nội dung trả lời trống")).into_response();
+    }
+
+    let review: Option<(String, String, i32, Option<String>)> = sqlx
+        ::query_as(
+            "SELECT u.email, p.name, r.rating, r.content
+             FROM reviews r
+             JOIN users u ON r.user_id = u.id
+             JOIN products p ON r.product_id = p.id
+             WHERE r.id = ?"
+        )
+        .bind(&id)
+        .fetch_optional(&state.db).await
+        .unwrap_or(None);
+
+    let (email, product_name, rating, review_content) = match review {
+        Some(row) => row,
+        None => return (StatusCode::NOT_FOUND, Json("Không tìm thấy đánh giá")).into_response(),
+    };
+
+    let mut tx = match state.db.begin().await {
+        Ok(tx) => tx,
+        Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, Json("Error")).into_response(),
+    };
+
+    let old_reply: Option<(String,)> = sqlx
+        ::query_as("SELECT content FROM review_replies WHERE review_id = ? FOR UPDATE")
+        .bind(&id)
+        .fetch_optional(&mut *tx).await
+        .unwrap_or(None);
+
+    // Mỗi đánh giá chỉ có 1 câu trả lời -> UNIQUE(review_id) + ON DUPLICATE KEY UPDATE
+    let res = sqlx
+        ::query(
+            "INSERT INTO review_replies (id, review_id, admin_id, content) VALUES (?, ?, ?, ?)
+             ON DUPLICATE KEY UPDATE admin_id = VALUES(admin_id), content = VALUES(content), updated_at = NOW()"
+        )
+        .bind(suid())
+        .bind(&id)
+        .bind(&admin.user_id)
+        .bind(content)
+        .execute(&mut *tx).await;
+
+    let res = match res {
+        Ok(_) => record_audit(&mut *tx, AuditEntry {
+            actor_id: &admin.user_id,
+            action: "reviews.reply",
+            target_type: "review",
+            target_id: &id,
+            before: old_reply.map(|(c,)| serde_json::json!({ "reply": c })),
+            after: Some(serde_json::json!({ "reply": content })),
+            reason: None,
+            ip: client.ip.as_deref(),
+        }).await,
+        Err(e) => Err(e),
+    };
+
+    // Mail báo người đánh giá ghi vào outbox cùng transaction
+    let res = match res {
+        Ok(_) => {
+            let lang = recipient_language(&mut *tx, &email).await;
+            send_review_reply_email(
+                &mut *tx,
+                email,
+                lang,
+                product_name,
+                rating,
+                review_content.unwrap_or_default(),
+                content.to_string(),
+            ).await
+        }
+        Err(e) => Err(e.into()),
+    };
+
+    let res = match res {
+        Ok(_) => tx.commit().await.map_err(EmailError::from),
+        Err(e) => {
+            let _ = tx.rollback().await;
+            Err(e)
+        }
+    };
+
+    match res {
+        Ok(_) => {
+            outbox::wake();
+            (StatusCode::OK, Json("Đã trả lời đánh giá")).into_response()
+        }
+        Err(e) => {
+            println!("Lỗi reply_review: {:?}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, Json("Error DB")).into_response()
+        }
+    }
+}
+
+// 5. Xóa câu trả lời
+async fn delete_review_reply(
+    State(state): State<AppState>,
+    admin: RequirePermission<perm::ReviewsReply>,
+    client: ClientInfo,
+    Path(id): Path<String>
+) -> impl IntoResponse {
+    let mut tx = match state.db.begin().await {
+        Ok(tx) => tx,
+        Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, Json("Error")).into_response(),
+    };
+
+    let old_reply: Option<(String,)> = sqlx
+        ::query_as("SELECT content FROM review_replies WHERE review_id = ? FOR UPDATE")
+        .bind(&id)
+        .fetch_optional(&mut *tx).await
+        .unwrap_or(None);
+
+    let old_content = match old_reply {
+        Some((c,)) => c,
+        None => return (StatusCode::OK, Json("Deleted")).into_response(),
+    };
+
+    let res = sqlx
+        ::query("DELETE FROM review_replies WHERE review_id = ?")
+        .bind(&id)
+        .execute(&mut *tx).await;
+
+    let res = match res {
+        Ok(_) => record_audit(&mut *tx, AuditEntry {
+            actor_id: &admin.user_id,
+            action: "reviews.delete_reply",
+            target_type: "review",
+            target_id: &id,
+            before: Some(serde_json::json!({ "reply": old_content })),
+            after: None,
+            reason: None,
+            ip: client.ip.as_deref(),
+        }).await,
+        Err(e) => Err(e),
+    };
+
+    let res = match res {
+        Ok(_) => tx.commit().await,
+        Err(e) => {
+            let _ = tx.rollback().await;
+            Err(e)
+        }
+    };
+
+    match res {
+        Ok(_) => (StatusCode::OK, Json("Deleted")).into_response(),
+        Err(_) => (StatusCode::INTERNAL_SERVER_ERROR, Json("Error")).into_response(),
+    }
+}
+
+// --- HANDLERS: AUDIT LOG ---
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct AuditLogItem {
+    pub id: String,
+    pub actor_id: String,
+    pub actor_email: Option<String>,
+    pub action: String,
+    pub target_type: String,
+    pub target_id: String,
+    pub before_data: Option<sqlx::types::Json<Value>>,
+    pub after_data: Option<sqlx::types::Json<Value>>,
+    pub reason: Option<String>,
+    pub ip: Option<String>,
+    pub created_at: Option<chrono::NaiveDateTime>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct AuditFilter {
+    pub actor_id: Option<String>,
+    pub action: Option<String>,       // VD: products.update
+    pub target_type: Option<String>,
+    pub target_id: Option<String>,
+    pub from: Option<chrono::NaiveDate>,
+    pub to: Option<chrono::NaiveDate>, // Tính cả ngày `to`
+    pub page: Option<i64>,
+    pub limit: Option<i64>,
+}
+
+// Tra cứu ai đã làm gì. VD: ?target_type=product&target_id=xxx&action=products.update
+async fn get_audit_logs(
+    State(state): State<AppState>,
+    _: RequirePermission<perm::AuditRead>,
+    Query(filter): Query<AuditFilter>
+) -> impl IntoResponse {
+    let limit = filter.limit.unwrap_or(50).clamp(1, 200);
+    let page = filter.page.unwrap_or(1).max(1);
+
+    fn push_filters<'a>(qb: &mut QueryBuilder<'a, MySql>, filter: &'a AuditFilter) {
+        if let Some(actor_id) = &filter.actor_id {
+            qb.push(" AND a.actor_id = ").push_bind(actor_id);
+        }
+        if let Some(action) = &filter.action {
+            qb.push(" AND a.action = ").push_bind(action);
+        }
+        if let Some(target_type) = &filter.target_type {
+            qb.push(" AND a.target_type = ").push_bind(target_type);
+        }
+        if let Some(target_id) = &filter.target_id {
+            qb.push(" AND a.target_id = ").push_bind(target_id);
+        }
+        if let Some(from) = filter.from {
+            qb.push(" AND a.created_at >= ").push_bind(from);
+        }
+        if let Some(to) = filter.to {
+            qb.push(" AND a.created_at < DATE_ADD(").push_bind(to).push(", INTERVAL 1 DAY)");
+        }
+    }
+
+    let mut count_qb: QueryBuilder<MySql> = QueryBuilder::new("SELECT COUNT(*) FROM admin_audit_logs a WHERE 1 = 1");
+    push_filters(&mut count_qb, &filter);
+    let total: (i64,) = count_qb.build_query_as().fetch_one(&state.db).await.unwrap_or((0,));
+
+    let mut qb: QueryBuilder<MySql> = QueryBuilder::new(
+        "SELECT a.id, a.actor_id, u.email as actor_email, a.action, a.target_type, a.target_id,
+                a.before_data, a.after_data, a.reason, a.ip, a.created_at
+         FROM admin_audit_logs a
+         LEFT JOIN users u ON a.actor_id = u.id
+         WHERE 1 = 1"
+    );
+    push_filters(&mut qb, &filter);
+    qb.push(" ORDER BY a.created_at DESC LIMIT ")
+        .push_bind(limit)
+        .push(" OFFSET ")
+        .push_bind((page - 1) * limit);
+
+    match qb.build_query_as::<AuditLogItem>().fetch_all(&state.db).await {
+        Ok(data) => {
+            let mut headers = HeaderMap::new();
+            headers.insert("x-total-count", total.0.into());
+            (StatusCode::OK, headers, Json(data)).into_response()
+        }
+        Err(e) => {
+            println!("Lỗi get_audit_logs: {:?}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, Json("Error")).into_response()
+        }
+    }
+}
+
+// --- HANDLERS: MEDIA ---
+
+#[derive(Debug, Deserialize)]
+pub struct MediaFilter {
+    pub search: Option<String>,   // Tìm theo tên file gốc
+    pub ref_type: Option<String>, // Chỉ media đang được dùng bởi product / review
+    pub unused: Option<bool>,     // true: chỉ media không còn tham chiếu (chờ GC)
+    pub page: Option<i64>,
+    pub limit: Option<i64>,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct MediaItem {
+    pub id: String,
+    pub url: String,
+    pub image: sqlx::types::Json<StoredImage>,
+    pub original_name: Option<String>,
+    pub content_type: String,
+    pub size: i32,
+    pub uploaded_by: Option<String>,
+    pub uploader_email: Option<String>,
+    pub ref_count: i64,
+    pub created_at: Option<chrono::NaiveDateTime>,
+    pub last_used_at: Option<chrono::NaiveDateTime>,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct MediaRef {
+    pub ref_type: String,
+    pub ref_id: String,
+    pub created_at: Option<chrono::NaiveDateTime>,
+}
+
+const MEDIA_SELECT: &str =
+    "SELECT m.id, m.url, m.image, m.original_name, m.content_type, m.size, m.uploaded_by,
+            u.email as uploader_email,
+            (SELECT COUNT(*) FROM media_refs r WHERE r.media_id = m.id) as ref_count,
+            m.created_at, m.last_used_at
+     FROM media m
+     LEFT JOIN users u ON m.uploaded_by = u.id";
+
+// Duyệt thư viện media, tổng số bản ghi qua header X-Total-Count
+async fn get_all_media(
+    State(state): State<AppState>,
+    _: RequirePermission<perm::MediaRead>,
+    Query(filter): Query<MediaFilter>
+) -> impl IntoResponse {
+    let limit = filter.limit.unwrap_or(50).clamp(1, 200);
+    let page = filter.page.unwrap_or(1).max(1);
+
+    fn push_filters<'a>(qb: &mut QueryBuilder<'a, MySql>, filter: &'a MediaFilter) {
+        if let Some(search) = &filter.search {
+            qb.push(" AND m.original_name LIKE ").push_bind(format!("%{}%", search.trim()));
+        }
+        if let Some(ref_type) = &filter.ref_type {
+            qb.push(" AND EXISTS (SELECT 1 FROM media_refs r WHERE r.media_id = m.id AND r.ref_type = ")
+                .push_bind(ref_type)
+                .push(")");
+        }
+        match filter.unused {
+            Some(true) => {
+                qb.push(" AND NOT EXISTS (SELECT 1 FROM media_refs r WHERE r.media_id = m.id)");
+            }
+            Some(false) => {
+                qb.push(" AND EXISTS (SELECT 1 FROM media_refs r WHERE r.media_id = m.id)");
+            }
+            None => {}
+        }
+    }
+
+    let mut count_qb: QueryBuilder<MySql> = QueryBuilder::new("SELECT COUNT(*) FROM media m WHERE 1 = 1");
+    push_filters(&mut count_qb, &filter);
+    let total: (i64,) = count_qb.build_query_as().fetch_one(&state.db).await.unwrap_or((0,));
+
+    let mut qb: QueryBuilder<MySql> = QueryBuilder::new(MEDIA_SELECT);
+    qb.push(" WHERE 1 = 1");
+    push_filters(&mut qb, &filter);
+    qb.push(" ORDER BY m.created_at DESC LIMIT ")
+        .push_bind(limit)
+        .push(" OFFSET ")
+        .push_bind((page - 1) * limit);
+
+    match qb.build_query_as::<MediaItem>().fetch_all(&state.db).await {
+        Ok(data) => {
+            let mut headers = HeaderMap::new();
+            headers.insert("x-total-count", total.0.into());
+            (StatusCode::OK, headers, Json(data)).into_response()
+        }
+        Err(e) => {
+            println!("Lỗi get_all_media: {:?}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, Json("Error")).into_response()
+        }
+    }
+}
+
+// Chi tiết 1 media + danh sách nơi đang dùng
+async fn get_media_detail(
+    State(state): State<AppState>,
+    _: RequirePermission<perm::MediaRead>,
+    Path(id): Path<String>
+) -> impl IntoResponse {
+    let item = sqlx
+        ::query_as::<_, MediaItem>(&format!("{} WHERE m.id = ?", MEDIA_SELECT))
+        .bind(&id)
+        .fetch_optional(&state.db).await;
+
+    let item = match item {
+        Ok(Some(m)) => m,
+        Ok(None) => return (StatusCode::NOT_FOUND, Json("Không tìm thấy media")).into_response(),
+        Err(e) => {
+            println!("Lỗi get_media_detail: {:?}", e);
+            return (StatusCode::INTERNAL_SERVER_ERROR, Json("Error")).into_response();
+        }
+    };
+
+    let refs = sqlx
+        ::query_as::<_, MediaRef>(
+            "SELECT ref_type, ref_id, created_at FROM media_refs WHERE media_id = ? ORDER BY created_at DESC"
+        )
+        .bind(&id)
+        .fetch_all(&state.db).await
+        .unwrap_or(vec![]);
+
+    (StatusCode::OK, Json(serde_json::json!({ "media": item, "refs": refs }))).into_response()
+}
+
 // --- ROUTER ---
 pub fn admin_routes() -> Router<AppState> {
     Router::new()
@@ -453,9 +1634,21 @@ pub fn admin_routes() -> Router<AppState> {
         .route("/orders/:id/status", put(update_order_status))
         .route("/products", post(create_product))
         .route("/products/:id", put(update_product).delete(delete_product)) // Thêm route update
+        .route("/products/:id/stock", put(adjust_stock))
         .route("/users", get(get_all_users))
+        .route("/users/:id", get(get_user_detail).put(update_user))
         .route("/settings", get(get_settings).post(update_settings))
         .route("/analytics", get(get_analytics))
         .route("/contacts", get(get_all_contacts))             // <-- Thêm
         .route("/contacts/:id/status", put(update_contact_status))
+        .route("/reviews", get(get_all_reviews))
+        .route("/reviews/bulk", post(bulk_update_review_status))
+        .route("/reviews/:id/status", put(update_review_status))
+        .route("/reviews/:id/reply", put(reply_review).delete(delete_review_reply))
+        .route("/audit", get(get_audit_logs))
+        .route("/media", get(get_all_media))
+        .route("/media/:id", get(get_media_detail))
+        .merge(rbac::rbac_routes())
+        .merge(email_templates::email_template_routes())
+        .merge(email_outbox::email_outbox_routes())
 }
@@ -1,8 +1,8 @@
 use axum::{
-    extract::{State, Json, FromRequestParts},
+    extract::{State, Json, FromRequestParts, Path, ConnectInfo},
     http::{StatusCode, request::Parts},
     response::{IntoResponse, Response},
-    routing::{post, get},
+    routing::{post, get, put, delete},
     Router,
     async_trait,
 };
@@ -11,10 +11,16 @@ use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
 use jsonwebtoken::{encode, decode, Header, Algorithm, Validation, EncodingKey, DecodingKey};
 use crate::AppState;
-use std::env;
+use crate::config::Config;
 use time::Duration;
-use reqwest::Client;
+use crate::identity::{IdentityError, VerifiedIdentity};
+use rand::Rng;
+use sha2::{Digest, Sha256};
+use std::net::{IpAddr, SocketAddr};
+use crate::utils::outbox;
 use crate::utils::suid;
+use crate::utils::templates::LANGUAGES;
+use crate::utils::{ recipient_language, send_verify_email, send_password_reset_email, send_login_otp_email };
 
 // --- MODELS ---
 
@@ -43,15 +49,61 @@ pub struct UserResponse {
 
 #[derive(Debug, Deserialize)]
 pub struct LoginPayload {
-    pub credential: Option<String>,
+    pub provider: Option<String>,   // "google" (mặc định), "facebook", ...
+    pub credential: Option<String>, // ID token / access token của provider
+    pub email: Option<String>,      // Đăng nhập bằng email + mật khẩu
+    pub password: Option<String>,
 }
 
 #[derive(Debug, Deserialize)]
-struct GoogleTokenInfo {
+pub struct RegisterPayload {
+    pub email: String,
+    pub password: String,
+    pub name: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct TokenPayload {
+    pub token: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct EmailPayload {
+    pub email: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ResetPasswordPayload {
+    pub token: String,
+    pub password: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct OtpVerifyPayload {
+    pub email: String,
+    pub code: String,
+}
+
+#[derive(Debug, FromRow)]
+struct PasswordLoginRow {
+    id: String,
     email: String,
-    aud: String,
-    name: Option<String>,
-    picture: Option<String>,
+    role: String,
+    status: String,
+    password_hash: Option<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct LinkIdentityPayload {
+    pub provider: String,
+    pub credential: String,
+}
+
+#[derive(Debug, FromRow, Serialize)]
+pub struct IdentityItem {
+    pub provider: String,
+    pub email: Option<String>,
+    pub created_at: Option<chrono::NaiveDateTime>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -60,16 +112,175 @@ pub struct Claims {
     pub email: String,
     pub role: String,
     pub exp: usize,
+    #[serde(default)]
+    pub sid: Option<String>, // family_id của phiên (bảng sessions)
 }
 
 pub struct AuthUser {
     pub user_id: String,
+    pub session_id: Option<String>,
+    pub role: String,
+}
+
+// Thông tin thiết bị gửi request, lưu kèm phiên đăng nhập
+#[derive(Debug, Clone, Default)]
+pub struct ClientInfo {
+    pub ip: Option<String>,
+    pub user_agent: Option<String>,
+}
+
+// IP khách của request. Header X-Forwarded-For / X-Real-IP ai cũng gửi được nên chỉ tin khi kết nối
+// đến từ proxy trong `trusted`. XFF đọc từ phải sang trái, bỏ qua các proxy tin cậy: IP đầu tiên
+// không phải proxy là IP khách (các giá trị bên trái nó do client tự điền).
+fn client_ip(
+    peer: Option<IpAddr>,
+    forwarded_for: Option<&str>,
+    real_ip: Option<&str>,
+    trusted: &[IpAddr],
+) -> Option<IpAddr> {
+    let peer = peer?;
+    if !trusted.contains(&peer) {
+        return Some(peer);
+    }
+
+    if let Some(xff) = forwarded_for {
+        let hops: Vec<IpAddr> = xff.split(',').filter_map(|v| v.trim().parse().ok()).collect();
+        if let Some(ip) = hops.iter().rev().find(|ip| !trusted.contains(ip)).or(hops.first()) {
+            return Some(*ip);
+        }
+    }
+
+    real_ip.and_then(|v| v.trim().parse().ok()).or(Some(peer))
+}
+
+#[async_trait]
+impl<S> FromRequestParts<S> for ClientInfo
+where
+    S: Send + Sync + Clone,
+    AppState: From<S>,
+{
+    type Rejection = std::convert::Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S)
+        -> Result<Self, Self::Rejection>
+    {
+        let header = |name: &str| parts.headers.get(name).and_then(|v| v.to_str().ok());
+        let app_state = AppState::from(state.clone());
+
+        let peer = parts.extensions
+            .get::<ConnectInfo<SocketAddr>>()
+            .map(|ci| ci.0.ip());
+        let ip = client_ip(
+            peer,
+            header("x-forwarded-for"),
+            header("x-real-ip"),
+            &app_state.config.server.trusted_proxies,
+        )
+        .map(|ip| ip.to_string());
+
+        let user_agent = header("user-agent").map(|v| v.chars().take(512).collect());
+
+        Ok(ClientInfo { ip, user_agent })
+    }
+}
+
+#[derive(Debug, FromRow, Serialize)]
+pub struct SessionItem {
+    pub id: String, // family_id
+    pub device: Option<String>,
+    pub ip: Option<String>,
+    pub user_agent: Option<String>,
+    pub created_at: Option<chrono::NaiveDateTime>,
+    pub last_used_at: Option<chrono::NaiveDateTime>,
+    pub expires_at: chrono::NaiveDateTime,
+    #[sqlx(skip)]
+    pub current: bool,
+}
+
+// --- CACHE TRẠNG THÁI USER ---
+//
+// AuthUser cần biết user có bị khóa / đổi quyền / phiên bị thu hồi chưa. Để không query DB
+// mỗi request, kết quả được cache AUTH_CACHE_TTL giây theo (user_id, session_id).
+// Khi admin khóa user hoặc đổi quyền thì gọi `invalidate_user` để có hiệu lực ngay.
+
+const AUTH_CACHE_TTL: std::time::Duration = std::time::Duration::from_secs(30);
+const AUTH_CACHE_MAX_ENTRIES: usize = 10_000;
+
+#[derive(Debug, Clone)]
+struct CachedAuth {
+    status: String,
+    role: String,
+    session_active: bool,
+    cached_at: std::time::Instant,
+}
+
+#[derive(Default)]
+pub struct AuthCache {
+    entries: std::sync::RwLock<std::collections::HashMap<(String, Option<String>), CachedAuth>>,
+}
+
+impl AuthCache {
+    fn get(&self, key: &(String, Option<String>)) -> Option<CachedAuth> {
+        let entries = self.entries.read().unwrap_or_else(|e| e.into_inner());
+        entries
+            .get(key)
+            .filter(|c| c.cached_at.elapsed() < AUTH_CACHE_TTL)
+            .cloned()
+    }
+
+    fn insert(&self, key: (String, Option<String>), value: CachedAuth) {
+        let mut entries = self.entries.write().unwrap_or_else(|e| e.into_inner());
+        if entries.len() >= AUTH_CACHE_MAX_ENTRIES {
+            entries.retain(|_, c| c.cached_at.elapsed() < AUTH_CACHE_TTL);
+        }
+        entries.insert(key, value);
+    }
+
+    /// Xóa cache của một user (mọi phiên) sau khi đổi status/role/thu hồi phiên
+    pub fn invalidate_user(&self, user_id: &str) {
+        let mut entries = self.entries.write().unwrap_or_else(|e| e.into_inner());
+        entries.retain(|(uid, _), _| uid != user_id);
+    }
+}
+
+fn auth_error(status: StatusCode, code: &str, message: &str) -> (StatusCode, Json<serde_json::Value>) {
+    (status, Json(serde_json::json!({ "code": code, "message": message })))
+}
+
+async fn load_auth_state(
+    state: &AppState,
+    user_id: &str,
+    session_id: Option<&str>,
+) -> Result<Option<CachedAuth>, sqlx::Error> {
+    // Token cũ (trước khi có bảng sessions) không có sid -> chỉ kiểm tra user
+    let row: Option<(String, String, bool)> = sqlx::query_as(
+        "SELECT u.status, u.role,
+                (? IS NULL OR EXISTS (
+                    SELECT 1 FROM sessions s
+                    WHERE s.family_id = ? AND s.user_id = u.id
+                      AND s.revoked_at IS NULL AND s.expires_at > UTC_TIMESTAMP()
+                ))
+         FROM users u WHERE u.id = ?"
+    )
+    .bind(session_id)
+    .bind(session_id)
+    .bind(user_id)
+    .fetch_optional(&state.db)
+    .await?;
+
+    Ok(row.map(|(status, role, session_active)| CachedAuth {
+        status,
+        role,
+        session_active,
+        cached_at: std::time::Instant::now(),
+    }))
 }
 
 #[async_trait]
 impl<S> FromRequestParts<S> for AuthUser
 where
-    S: Send + Sync,
+    S: Send + Sync + Clone,
+    AppState: From<S>,
 {
     type Rejection = (StatusCode, Json<serde_json::Value>);
 
@@ -77,23 +288,63 @@ where
         -> Result<Self, Self::Rejection>
     {
         let jar = CookieJar::from_request_parts(parts, state).await
-            .map_err(|_| (StatusCode::INTERNAL_SERVER_ERROR,
-                Json(serde_json::json!({"message": "Cookie error"}))))?;
+            .map_err(|_| auth_error(StatusCode::INTERNAL_SERVER_ERROR, "COOKIE_ERROR", "Cookie error"))?;
 
         let token = match jar.get("token") {
             Some(c) => c.value().to_string(),
-            None => return Err((StatusCode::UNAUTHORIZED,
-                Json(serde_json::json!({"message": "Chưa đăng nhập"})))),
+            None => return Err(auth_error(StatusCode::UNAUTHORIZED, "UNAUTHENTICATED", "Chưa đăng nhập")),
         };
 
-        let secret = env::var("SECRET_KEY").unwrap_or("secret".to_string());
-        let decoding_key = DecodingKey::from_secret(secret.as_bytes());
+        let app_state = AppState::from(state.clone());
+        let decoding_key = DecodingKey::from_secret(app_state.config.jwt.secret.as_bytes());
+
+        let claims = decode::<Claims>(&token, &decoding_key, &Validation::new(Algorithm::HS256))
+            .map_err(|_| auth_error(StatusCode::UNAUTHORIZED, "INVALID_TOKEN", "Token không hợp lệ"))?
+            .claims;
 
-        match decode::<Claims>(&token, &decoding_key, &Validation::new(Algorithm::HS256)) {
-            Ok(token_data) => Ok(AuthUser { user_id: token_data.claims.sub }),
-            Err(_) => Err((StatusCode::UNAUTHORIZED,
-                Json(serde_json::json!({"message": "Token không hợp lệ"})))),
+        // Kiểm tra trạng thái hiện tại của user (có cache)
+        let key = (claims.sub.clone(), claims.sid.clone());
+        let cached = match app_state.auth_cache.get(&key) {
+            Some(c) => c,
+            None => {
+                let loaded = load_auth_state(&app_state, &claims.sub, claims.sid.as_deref()).await
+                    .map_err(|_| auth_error(StatusCode::INTERNAL_SERVER_ERROR, "DB_ERROR", "Lỗi DB"))?
+                    .ok_or_else(|| auth_error(StatusCode::UNAUTHORIZED, "USER_NOT_FOUND", "User không tồn tại"))?;
+                app_state.auth_cache.insert(key, loaded.clone());
+                loaded
+            }
+        };
+
+        if cached.status == "locked" {
+            return Err(auth_error(StatusCode::FORBIDDEN, "ACCOUNT_LOCKED", "Tài khoản đã bị khóa"));
+        }
+        if !cached.session_active {
+            return Err(auth_error(StatusCode::UNAUTHORIZED, "SESSION_REVOKED", "Phiên đăng nhập đã bị thu hồi"));
         }
+
+        Ok(AuthUser {
+            user_id: claims.sub,
+            session_id: claims.sid,
+            role: cached.role, // Lấy từ DB, không tin role trong token
+        })
+    }
+}
+
+/// Như AuthUser nhưng không bắt buộc đăng nhập: chưa đăng nhập / token lỗi / bị khóa -> None
+pub struct OptionalAuthUser(pub Option<AuthUser>);
+
+#[async_trait]
+impl<S> FromRequestParts<S> for OptionalAuthUser
+where
+    S: Send + Sync + Clone,
+    AppState: From<S>,
+{
+    type Rejection = std::convert::Infallible;
+
+    async fn from_request_parts(parts: &mut Parts, state: &S)
+        -> Result<Self, Self::Rejection>
+    {
+        Ok(OptionalAuthUser(AuthUser::from_request_parts(parts, state).await.ok()))
     }
 }
 
@@ -102,155 +353,1257 @@ where
 pub async fn login(
     State(state): State<AppState>,
     jar: CookieJar,
+    client: ClientInfo,
     Json(payload): Json<LoginPayload>,
 ) -> impl IntoResponse {
-    if let Some(google_token) = payload.credential {
-        let client = Client::new();
-        let google_res = client
-            .get("https://oauth2.googleapis.com/tokeninfo")
-            .query(&[("id_token", &google_token)])
-            .send()
-            .await;
+    if let Some(credential) = payload.credential {
+        let provider = payload.provider.unwrap_or("google".to_string());
+        return social_login(&state, jar, &client, &provider, &credential).await;
+    }
 
-        let google_info: GoogleTokenInfo = match google_res {
-            Ok(res) if res.status().is_success() => res.json().await.unwrap(),
-            _ => return (StatusCode::UNAUTHORIZED, jar, Json(serde_json::json!({"message": "Token Google lỗi"}))).into_response(),
+    if let (Some(email), Some(password)) = (payload.email, payload.password) {
+        return password_login(&state, jar, &client, email, password).await;
+    }
+
+    (StatusCode::BAD_REQUEST, jar,
+        Json(serde_json::json!({"message": "Thiếu thông tin đăng nhập"}))
+    ).into_response()
+}
+
+// --- ĐĂNG NHẬP MẠNG XÃ HỘI (IdentityProvider) ---
+
+// Xác thực credential qua provider đã đăng ký trong AppState
+async fn verify_identity(
+    state: &AppState,
+    provider: &str,
+    credential: &str,
+) -> Result<VerifiedIdentity, (StatusCode, Json<serde_json::Value>)> {
+    let provider = state.identity.get(provider).ok_or((
+        StatusCode::BAD_REQUEST,
+        Json(serde_json::json!({"message": "Không hỗ trợ đăng nhập bằng provider này"})),
+    ))?;
+
+    provider.verify(credential).await.map_err(|e| {
+        println!("Lỗi xác thực {}: {}", provider.name(), e);
+        let status = match e {
+            IdentityError::Upstream(_) => StatusCode::BAD_GATEWAY,
+            _ => StatusCode::UNAUTHORIZED,
         };
+        (status, Json(serde_json::json!({"message": e.to_string()})))
+    })
+}
+
+// 1. Đã liên kết (provider, subject) -> user đó
+// 2. Chưa liên kết nhưng trùng email đã xác thực -> liên kết vào user có sẵn, chỉ khi email của user
+//    đó cũng đã xác thực. Nếu chưa thì ai cũng có thể đăng ký trước bằng email của nạn nhân rồi chờ
+//    nạn nhân đăng nhập Google vào tài khoản kẻ gian -> bắt đăng nhập mật khẩu rồi tự liên kết.
+//    Provider không xác nhận email (Facebook) thì không bao giờ liên kết theo email.
+// 3. Chưa có -> tạo user mới + liên kết (email provider chưa xác nhận thì gửi mail xác thực)
+async fn social_login(
+    state: &AppState,
+    jar: CookieJar,
+    client: &ClientInfo,
+    provider: &str,
+    credential: &str,
+) -> Response {
+    let identity = match verify_identity(state, provider, credential).await {
+        Ok(i) => i,
+        Err((status, body)) => return (status, jar, body).into_response(),
+    };
+
+    let linked = sqlx::query_as::<_, (String, String, String, String)>(
+        "SELECT u.id, u.email, u.role, u.status
+         FROM user_identities ui
+         JOIN users u ON ui.user_id = u.id
+         WHERE ui.provider = ? AND ui.subject = ?"
+    )
+    .bind(&identity.provider)
+    .bind(&identity.subject)
+    .fetch_optional(&state.db)
+    .await;
+
+    let (user, status) = match linked {
+        Ok(Some((id, email, role, status))) => (UserAuthDetails { id, email, role }, status),
+        Ok(None) => {
+            let email = match &identity.email {
+                Some(e) => normalize_email(e),
+                None => return (StatusCode::UNAUTHORIZED, jar,
+                    Json(serde_json::json!({"message": "Tài khoản chưa có email đã xác thực"}))).into_response(),
+            };
+
+            let existing = sqlx::query_as::<_, (String, String, String, bool)>(
+                "SELECT id, role, status, email_verified FROM users WHERE email = ?"
+            )
+            .bind(&email)
+            .fetch_optional(&state.db)
+            .await;
 
-        let env_client_id = env::var("GOOGLE_CLIENT_ID").expect("Thiếu GOOGLE_CLIENT_ID");
-        if google_info.aud != env_client_id {
-            return (StatusCode::UNAUTHORIZED, jar, Json(serde_json::json!({"message": "Client ID sai"}))).into_response();
+            let (user, status) = match existing {
+                // Provider không đảm bảo email (Facebook): không bao giờ tự liên kết theo email
+                Ok(Some(_)) if !identity.email_verified => return (StatusCode::CONFLICT, jar,
+                    Json(serde_json::json!({
+                        "code": "LINK_REQUIRES_PASSWORD",
+                        "message": "Email này đã có tài khoản. Vui lòng đăng nhập rồi liên kết trong trang cá nhân"
+                    }))).into_response(),
+                Ok(Some((_, _, _, false))) => return (StatusCode::CONFLICT, jar,
+                    Json(serde_json::json!({
+                        "code": "LINK_REQUIRES_PASSWORD",
+                        "message": "Email này đã có tài khoản chưa xác thực. Vui lòng đăng nhập bằng mật khẩu \
+                                    (hoặc đặt lại mật khẩu) rồi liên kết trong trang cá nhân"
+                    }))).into_response(),
+                Ok(Some((id, role, status, true))) => (UserAuthDetails { id, email, role }, status),
+                Ok(None) => {
+                    let uid = suid();
+                    let insert = sqlx::query(
+                        "INSERT INTO users (id, email, name, picture, role, status, email_verified)
+                         VALUES (?, ?, ?, ?, 'user', 'active', ?)"
+                    )
+                    .bind(&uid)
+                    .bind(&email)
+                    .bind(identity.name.clone().unwrap_or("User".to_string()))
+                    .bind(identity.picture.clone())
+                    .bind(identity.email_verified)
+                    .execute(&state.db)
+                    .await;
+
+                    if insert.is_err() {
+                        return (StatusCode::INTERNAL_SERVER_ERROR, jar,
+                            Json(serde_json::json!({"message": "Lỗi tạo user"}))).into_response();
+                    }
+                    if !identity.email_verified {
+                        send_verification(state, &uid, &email).await;
+                    }
+                    (UserAuthDetails { id: uid, email, role: "user".to_string() }, "active".to_string())
+                }
+                Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, jar,
+                    Json(serde_json::json!({"message": "Lỗi DB"}))).into_response(),
+            };
+
+            if link_identity(&state.db, &user.id, &identity).await.is_err() {
+                return (StatusCode::INTERNAL_SERVER_ERROR, jar,
+                    Json(serde_json::json!({"message": "Lỗi liên kết tài khoản"}))).into_response();
+            }
+            (user, status)
         }
+        Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, jar,
+            Json(serde_json::json!({"message": "Lỗi DB"}))).into_response(),
+    };
 
-        let user_result = sqlx::query_as::<_, UserAuthDetails>(
-            "SELECT id, email, role FROM users WHERE email = ?"
-        )
-        .bind(&google_info.email)
+    if status == "locked" {
+        return (StatusCode::FORBIDDEN, jar,
+            Json(serde_json::json!({"message": "Tài khoản đã bị khóa"}))).into_response();
+    }
+
+    generate_jwt_response(user, jar, state, client).await
+}
+
+async fn link_identity(
+    db: &sqlx::MySqlPool,
+    user_id: &str,
+    identity: &VerifiedIdentity,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "INSERT INTO user_identities (id, user_id, provider, subject, email) VALUES (?, ?, ?, ?, ?)"
+    )
+    .bind(suid())
+    .bind(user_id)
+    .bind(&identity.provider)
+    .bind(&identity.subject)
+    .bind(&identity.email)
+    .execute(db)
+    .await?;
+
+    // Provider đã xác thực email này
+    if identity.email_verified {
+        sqlx::query("UPDATE users SET email_verified = 1 WHERE id = ? AND email = ?")
+            .bind(user_id)
+            .bind(identity.email.as_deref().map(normalize_email))
+            .execute(db)
+            .await?;
+    }
+    Ok(())
+}
+
+// GET /api/auth/identities - Các provider đã liên kết với tài khoản
+async fn get_identities(
+    State(state): State<AppState>,
+    auth: AuthUser,
+) -> impl IntoResponse {
+    let items = sqlx::query_as::<_, IdentityItem>(
+        "SELECT provider, email, created_at FROM user_identities WHERE user_id = ? ORDER BY created_at"
+    )
+    .bind(&auth.user_id)
+    .fetch_all(&state.db)
+    .await;
+
+    match items {
+        Ok(data) => (StatusCode::OK, Json(serde_json::json!({
+            "linked": data,
+            "available": state.identity.names(),
+        }))).into_response(),
+        Err(_) => (StatusCode::INTERNAL_SERVER_ERROR,
+            Json(serde_json::json!({"message": "Lỗi DB"}))).into_response(),
+    }
+}
+
+// POST /api/auth/identities { provider, credential } - Liên kết thêm provider cho user đang đăng nhập
+async fn add_identity(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Json(payload): Json<LinkIdentityPayload>,
+) -> impl IntoResponse {
+    let identity = match verify_identity(&state, &payload.provider, &payload.credential).await {
+        Ok(i) => i,
+        Err(err) => return err.into_response(),
+    };
+
+    let owner: Option<(String,)> = sqlx::query_as(
+        "SELECT user_id FROM user_identities WHERE provider = ? AND subject = ?"
+    )
+    .bind(&identity.provider)
+    .bind(&identity.subject)
+    .fetch_optional(&state.db)
+    .await
+    .unwrap_or(None);
+
+    match owner {
+        Some((user_id,)) if user_id == auth.user_id => {
+            return (StatusCode::OK, Json(serde_json::json!({"message": "Đã liên kết từ trước"}))).into_response();
+        }
+        Some(_) => {
+            return (StatusCode::CONFLICT,
+                Json(serde_json::json!({"message": "Tài khoản này đã liên kết với user khác"}))).into_response();
+        }
+        None => {}
+    }
+
+    match link_identity(&state.db, &auth.user_id, &identity).await {
+        Ok(_) => (StatusCode::CREATED, Json(serde_json::json!({"message": "Liên kết thành công"}))).into_response(),
+        Err(_) => (StatusCode::INTERNAL_SERVER_ERROR,
+            Json(serde_json::json!({"message": "Lỗi liên kết tài khoản"}))).into_response(),
+    }
+}
+
+// DELETE /api/auth/identities/:provider - Phải còn ít nhất 1 cách đăng nhập (mật khẩu hoặc provider khác)
+async fn remove_identity(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Path(provider): Path<String>,
+) -> impl IntoResponse {
+    let counts: Option<(i64, bool)> = sqlx::query_as(
+        "SELECT (SELECT COUNT(*) FROM user_identities WHERE user_id = u.id AND provider != ?),
+                u.password_hash IS NOT NULL
+         FROM users u WHERE u.id = ?"
+    )
+    .bind(&provider)
+    .bind(&auth.user_id)
+    .fetch_optional(&state.db)
+    .await
+    .unwrap_or(None);
+
+    match counts {
+        None => return (StatusCode::NOT_FOUND,
+            Json(serde_json::json!({"message": "User not found"}))).into_response(),
+        Some((0, false)) => return (StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({"message": "Cần đặt mật khẩu hoặc liên kết provider khác trước khi gỡ"}))).into_response(),
+        _ => {}
+    }
+
+    let res = sqlx::query("DELETE FROM user_identities WHERE user_id = ? AND provider = ?")
+        .bind(&auth.user_id)
+        .bind(&provider)
+        .execute(&state.db)
+        .await;
+
+    match res {
+        Ok(r) if r.rows_affected() == 0 => (StatusCode::NOT_FOUND,
+            Json(serde_json::json!({"message": "Chưa liên kết provider này"}))).into_response(),
+        Ok(_) => (StatusCode::OK, Json(serde_json::json!({"message": "Đã gỡ liên kết"}))).into_response(),
+        Err(_) => (StatusCode::INTERNAL_SERVER_ERROR,
+            Json(serde_json::json!({"message": "Lỗi DB"}))).into_response(),
+    }
+}
+
+// --- EMAIL / MẬT KHẨU / OTP ---
+
+const MIN_PASSWORD_LEN: usize = 8;
+const VERIFY_EMAIL_TTL_HOURS: i64 = 24;
+const RESET_PASSWORD_TTL_MINUTES: i64 = 30;
+const OTP_TTL_MINUTES: i64 = 10;
+const OTP_MAX_ATTEMPTS: i32 = 5;
+const OTP_RESEND_SECONDS: i64 = 60;
+
+fn normalize_email(email: &str) -> String {
+    email.trim().to_lowercase()
+}
+
+fn sha256_hex(input: &str) -> String {
+    format!("{:x}", Sha256::digest(input.as_bytes()))
+}
+
+// Token ngẫu nhiên 32 bytes (hex) cho link xác thực / đặt lại mật khẩu
+fn random_token() -> String {
+    let bytes: [u8; 32] = rand::thread_rng().gen();
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn random_otp() -> String {
+    format!("{:06}", rand::thread_rng().gen_range(0..1_000_000))
+}
+
+// bcrypt tốn CPU -> chạy trong blocking pool để không chặn runtime
+async fn hash_password(password: String) -> Option<String> {
+    tokio::task::spawn_blocking(move || bcrypt::hash(password, bcrypt::DEFAULT_COST).ok())
+        .await
+        .ok()
+        .flatten()
+}
+
+async fn verify_password(password: String, hash: String) -> bool {
+    tokio::task::spawn_blocking(move || bcrypt::verify(password, &hash).unwrap_or(false))
+        .await
+        .unwrap_or(false)
+}
+
+// Lưu token (đã hash) vào bảng auth_tokens, vô hiệu hóa các token cũ cùng mục đích
+async fn store_auth_token(
+    db: &sqlx::MySqlPool,
+    user_id: Option<&str>,
+    email: &str,
+    purpose: &str,
+    token_hash: &str,
+    ttl: chrono::Duration,
+) -> Result<(), sqlx::Error> {
+    sqlx::query(
+        "UPDATE auth_tokens SET used_at = NOW() WHERE email = ? AND purpose = ? AND used_at IS NULL"
+    )
+    .bind(email)
+    .bind(purpose)
+    .execute(db)
+    .await?;
+
+    let expires_at = chrono::Utc::now().naive_utc() + ttl;
+    sqlx::query(
+        "INSERT INTO auth_tokens (id, user_id, email, purpose, token_hash, expires_at) VALUES (?, ?, ?, ?, ?, ?)"
+    )
+    .bind(suid())
+    .bind(user_id)
+    .bind(email)
+    .bind(purpose)
+    .bind(token_hash)
+    .bind(expires_at)
+    .execute(db)
+    .await?;
+
+    Ok(())
+}
+
+// Đổi token (link) lấy (token_id, user_id, email); token chỉ dùng được 1 lần
+async fn consume_link_token(
+    db: &sqlx::MySqlPool,
+    token: &str,
+    purpose: &str,
+) -> Option<(String, String)> {
+    let row: Option<(String, Option<String>, String)> = sqlx::query_as(
+        "SELECT id, user_id, email FROM auth_tokens
+         WHERE token_hash = ? AND purpose = ? AND used_at IS NULL AND expires_at > UTC_TIMESTAMP()"
+    )
+    .bind(sha256_hex(token))
+    .bind(purpose)
+    .fetch_optional(db)
+    .await
+    .ok()
+    .flatten();
+
+    let (token_id, user_id, email) = row?;
+    let used = sqlx::query("UPDATE auth_tokens SET used_at = NOW() WHERE id = ? AND used_at IS NULL")
+        .bind(&token_id)
+        .execute(db)
+        .await
+        .ok()?;
+
+    if used.rows_affected() == 0 {
+        return None;
+    }
+    Some((user_id?, email))
+}
+
+async fn send_verification(state: &AppState, user_id: &str, email: &str) {
+    let token = random_token();
+    let res = store_auth_token(
+        &state.db, Some(user_id), email, "verify_email", &sha256_hex(&token),
+        chrono::Duration::hours(VERIFY_EMAIL_TTL_HOURS),
+    ).await;
+
+    match res {
+        Ok(_) => {
+            let link = format!("{}/verify-email?token={}", state.config.frontend_url, token);
+            let lang = recipient_language(&state.db, email).await;
+            match send_verify_email(&state.db, email.to_string(), lang, link, VERIFY_EMAIL_TTL_HOURS).await {
+                Ok(_) => outbox::wake(),
+                Err(e) => println!("Lỗi ghi email xác thực: {:?}", e),
+            }
+        }
+        Err(e) => println!("Lỗi tạo token xác thực: {:?}", e),
+    }
+}
+
+async fn password_login(
+    state: &AppState,
+    jar: CookieJar,
+    client: &ClientInfo,
+    email: String,
+    password: String,
+) -> Response {
+    let email = normalize_email(&email);
+    let row = sqlx::query_as::<_, PasswordLoginRow>(
+        "SELECT id, email, role, status, password_hash FROM users WHERE email = ?"
+    )
+    .bind(&email)
+    .fetch_optional(&state.db)
+    .await;
+
+    let row = match row {
+        Ok(Some(r)) => r,
+        Ok(None) => return (StatusCode::UNAUTHORIZED, jar,
+            Json(serde_json::json!({"message": "Sai email hoặc mật khẩu"}))).into_response(),
+        Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, jar,
+            Json(serde_json::json!({"message": "Lỗi DB"}))).into_response(),
+    };
+
+    // Tài khoản tạo qua Google chưa đặt mật khẩu
+    let hash = match row.password_hash {
+        Some(h) => h,
+        None => return (StatusCode::UNAUTHORIZED, jar,
+            Json(serde_json::json!({"message": "Sai email hoặc mật khẩu"}))).into_response(),
+    };
+
+    if !verify_password(password, hash).await {
+        return (StatusCode::UNAUTHORIZED, jar,
+            Json(serde_json::json!({"message": "Sai email hoặc mật khẩu"}))).into_response();
+    }
+
+    if row.status == "locked" {
+        return (StatusCode::FORBIDDEN, jar,
+            Json(serde_json::json!({"message": "Tài khoản đã bị khóa"}))).into_response();
+    }
+
+    let user = UserAuthDetails { id: row.id, email: row.email, role: row.role };
+    generate_jwt_response(user, jar, state, client).await
+}
+
+// POST /api/auth/register
+async fn register(
+    State(state): State<AppState>,
+    jar: CookieJar,
+    client: ClientInfo,
+    Json(payload): Json<RegisterPayload>,
+) -> impl IntoResponse {
+    let email = normalize_email(&payload.email);
+    if email.parse::<lettre::Address>().is_err() {
+        return (StatusCode::BAD_REQUEST, jar,
+            Json(serde_json::json!({"message": "Email không hợp lệ"}))).into_response();
+    }
+    if payload.password.chars().count() < MIN_PASSWORD_LEN {
+        return (StatusCode::BAD_REQUEST, jar,
+            Json(serde_json::json!({"message": "Mật khẩu tối thiểu 8 ký tự"}))).into_response();
+    }
+
+    let existing: Option<(String,)> = sqlx::query_as("SELECT id FROM users WHERE email = ?")
+        .bind(&email)
         .fetch_optional(&state.db)
+        .await
+        .unwrap_or(None);
+
+    if existing.is_some() {
+        return (StatusCode::CONFLICT, jar,
+            Json(serde_json::json!({"message": "Email đã được đăng ký"}))).into_response();
+    }
+
+    let hash = match hash_password(payload.password).await {
+        Some(h) => h,
+        None => return (StatusCode::INTERNAL_SERVER_ERROR, jar,
+            Json(serde_json::json!({"message": "Lỗi mã hóa mật khẩu"}))).into_response(),
+    };
+
+    let uid = suid();
+    let name = payload.name
+        .filter(|n| !n.trim().is_empty())
+        .unwrap_or_else(|| email.split('@').next().unwrap_or("User").to_string());
+
+    let insert = sqlx::query(
+        "INSERT INTO users (id, email, name, password_hash, role, status, email_verified)
+         VALUES (?, ?, ?, ?, 'user', 'active', 0)"
+    )
+    .bind(&uid)
+    .bind(&email)
+    .bind(name)
+    .bind(hash)
+    .execute(&state.db)
+    .await;
+
+    if insert.is_err() {
+        return (StatusCode::INTERNAL_SERVER_ERROR, jar,
+            Json(serde_json::json!({"message": "Lỗi tạo user"}))).into_response();
+    }
+
+    send_verification(&state, &uid, &email).await;
+
+    let user = UserAuthDetails { id: uid, email, role: "user".to_string() };
+    generate_jwt_response(user, jar, &state, &client).await
+}
+
+// POST /api/auth/verify-email  { token }
+async fn verify_email(
+    State(state): State<AppState>,
+    Json(payload): Json<TokenPayload>,
+) -> impl IntoResponse {
+    let (user_id, _email) = match consume_link_token(&state.db, &payload.token, "verify_email").await {
+        Some(v) => v,
+        None => return (StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({"message": "Link xác thực không hợp lệ hoặc đã hết hạn"}))).into_response(),
+    };
+
+    let res = sqlx::query("UPDATE users SET email_verified = 1 WHERE id = ?")
+        .bind(&user_id)
+        .execute(&state.db)
         .await;
 
-        let user = match user_result {
-            Ok(Some(u)) => u,
-            Ok(None) => {
-                let uid = suid().to_string();
-                let insert = sqlx::query(
-                    "INSERT INTO users (id, email, name, picture, role, status)
-                     VALUES (?, ?, ?, ?, 'user', 'active')"
-                )
-                .bind(&uid)
-                .bind(&google_info.email)
-                .bind(google_info.name.unwrap_or("User".to_string()))
-                .bind(google_info.picture)
-                .execute(&state.db)
-                .await;
-
-                match insert {
-                    Ok(_) => UserAuthDetails {
-                        id: uid,
-                        email: google_info.email,
-                        role: "user".to_string(),
-                    },
-                    Err(_) => {
-                        return (StatusCode::INTERNAL_SERVER_ERROR, jar,
-                            Json(serde_json::json!({"message": "Lỗi tạo user"}))
-                        ).into_response()
-                    }
+    match res {
+        Ok(_) => (StatusCode::OK, Json(serde_json::json!({"message": "Xác thực email thành công"}))).into_response(),
+        Err(_) => (StatusCode::INTERNAL_SERVER_ERROR, Json(serde_json::json!({"message": "Lỗi DB"}))).into_response(),
+    }
+}
+
+// POST /api/auth/verify-email/resend (cần đăng nhập)
+async fn resend_verification(
+    State(state): State<AppState>,
+    auth: AuthUser,
+) -> impl IntoResponse {
+    let row: Option<(String, bool)> = sqlx::query_as("SELECT email, email_verified FROM users WHERE id = ?")
+        .bind(&auth.user_id)
+        .fetch_optional(&state.db)
+        .await
+        .unwrap_or(None);
+
+    match row {
+        Some((_, true)) => (StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({"message": "Email đã được xác thực"}))).into_response(),
+        Some((email, false)) => {
+            send_verification(&state, &auth.user_id, &email).await;
+            (StatusCode::OK, Json(serde_json::json!({"message": "Đã gửi lại email xác thực"}))).into_response()
+        }
+        None => (StatusCode::NOT_FOUND, Json(serde_json::json!({"message": "User not found"}))).into_response(),
+    }
+}
+
+// POST /api/auth/password/forgot  { email }
+// Luôn trả 200 để không lộ email nào đã đăng ký
+async fn forgot_password(
+    State(state): State<AppState>,
+    Json(payload): Json<EmailPayload>,
+) -> impl IntoResponse {
+    let email = normalize_email(&payload.email);
+    let user: Option<(String,)> = sqlx::query_as("SELECT id FROM users WHERE email = ? AND status = 'active'")
+        .bind(&email)
+        .fetch_optional(&state.db)
+        .await
+        .unwrap_or(None);
+
+    if let Some((user_id,)) = user {
+        let token = random_token();
+        let res = store_auth_token(
+            &state.db, Some(&user_id), &email, "reset_password", &sha256_hex(&token),
+            chrono::Duration::minutes(RESET_PASSWORD_TTL_MINUTES),
+        ).await;
+
+        match res {
+            Ok(_) => {
+                let link = format!("{}/reset-password?token={}", state.config.frontend_url, token);
+                let lang = recipient_language(&state.db, &email).await;
+                match send_password_reset_email(&state.db, email, lang, link, RESET_PASSWORD_TTL_MINUTES).await {
+                    Ok(_) => outbox::wake(),
+                    Err(e) => println!("Lỗi ghi email reset: {:?}", e),
                 }
-            },
-            Err(_) => {
+            }
+            Err(e) => println!("Lỗi tạo token reset: {:?}", e),
+        }
+    }
+
+    (StatusCode::OK, Json(serde_json::json!({
+        "message": "Nếu email tồn tại, link đặt lại mật khẩu đã được gửi"
+    })))
+}
+
+// Nhận được mail reset nghĩa là email hợp lệ. Mật khẩu cũ có thể đã lộ (hoặc là của kẻ đăng ký
+// trước bằng email này) -> thu hồi mọi phiên, và nếu email chưa xác thực thì bỏ cả liên kết social
+async fn reset_password_tx(state: &AppState, user_id: &str, hash: String) -> Result<(), sqlx::Error> {
+    let mut tx = state.db.begin().await?;
+
+    let unverified: Option<(String,)> = sqlx::query_as(
+        "SELECT id FROM users WHERE id = ? AND email_verified = 0 FOR UPDATE"
+    )
+    .bind(user_id)
+    .fetch_optional(&mut *tx)
+    .await?;
+
+    if unverified.is_some() {
+        sqlx::query("DELETE FROM user_identities WHERE user_id = ?")
+            .bind(user_id)
+            .execute(&mut *tx)
+            .await?;
+    }
+
+    sqlx::query("UPDATE users SET password_hash = ?, email_verified = 1 WHERE id = ?")
+        .bind(hash)
+        .bind(user_id)
+        .execute(&mut *tx)
+        .await?;
+    revoke_user_sessions(&mut *tx, user_id).await?;
+
+    tx.commit().await?;
+    state.auth_cache.invalidate_user(user_id);
+    Ok(())
+}
+
+// POST /api/auth/password/reset  { token, password }
+async fn reset_password(
+    State(state): State<AppState>,
+    jar: CookieJar,
+    client: ClientInfo,
+    Json(payload): Json<ResetPasswordPayload>,
+) -> impl IntoResponse {
+    if payload.password.chars().count() < MIN_PASSWORD_LEN {
+        return (StatusCode::BAD_REQUEST, jar,
+            Json(serde_json::json!({"message": "Mật khẩu tối thiểu 8 ký tự"}))).into_response();
+    }
+
+    let (user_id, _email) = match consume_link_token(&state.db, &payload.token, "reset_password").await {
+        Some(v) => v,
+        None => return (StatusCode::BAD_REQUEST, jar,
+            Json(serde_json::json!({"message": "Link không hợp lệ hoặc đã hết hạn"}))).into_response(),
+    };
+
+    let hash = match hash_password(payload.password).await {
+        Some(h) => h,
+        None => return (StatusCode::INTERNAL_SERVER_ERROR, jar,
+            Json(serde_json::json!({"message": "Lỗi mã hóa mật khẩu"}))).into_response(),
+    };
+
+    if let Err(e) = reset_password_tx(&state, &user_id, hash).await {
+        println!("Lỗi đặt lại mật khẩu: {:?}", e);
+        return (StatusCode::INTERNAL_SERVER_ERROR, jar,
+            Json(serde_json::json!({"message": "Lỗi DB"}))).into_response();
+    }
+
+    match sqlx::query_as::<_, UserAuthDetails>("SELECT id, email, role FROM users WHERE id = ?")
+        .bind(&user_id)
+        .fetch_optional(&state.db)
+        .await
+    {
+        Ok(Some(u)) => generate_jwt_response(u, jar, &state, &client).await,
+        _ => (StatusCode::NOT_FOUND, jar,
+            Json(serde_json::json!({"message": "User not found"}))).into_response(),
+    }
+}
+
+// POST /api/auth/otp/request  { email }
+async fn request_otp(
+    State(state): State<AppState>,
+    Json(payload): Json<EmailPayload>,
+) -> impl IntoResponse {
+    let email = normalize_email(&payload.email);
+    if email.parse::<lettre::Address>().is_err() {
+        return (StatusCode::BAD_REQUEST,
+            Json(serde_json::json!({"message": "Email không hợp lệ"}))).into_response();
+    }
+
+    // Chống spam: 1 mã / OTP_RESEND_SECONDS giây
+    let recent: (i64,) = sqlx::query_as(
+        "SELECT COUNT(*) FROM auth_tokens
+         WHERE email = ? AND purpose = 'login_otp' AND created_at > NOW() - INTERVAL ? SECOND"
+    )
+    .bind(&email)
+    .bind(OTP_RESEND_SECONDS)
+    .fetch_one(&state.db)
+    .await
+    .unwrap_or((0,));
+
+    if recent.0 > 0 {
+        return (StatusCode::TOO_MANY_REQUESTS,
+            Json(serde_json::json!({"message": "Vui lòng chờ trước khi yêu cầu mã mới"}))).into_response();
+    }
+
+    let user: Option<(String, String)> = sqlx::query_as("SELECT id, status FROM users WHERE email = ?")
+        .bind(&email)
+        .fetch_optional(&state.db)
+        .await
+        .unwrap_or(None);
+
+    if let Some((_, status)) = &user {
+        if status == "locked" {
+            return (StatusCode::FORBIDDEN,
+                Json(serde_json::json!({"message": "Tài khoản đã bị khóa"}))).into_response();
+        }
+    }
+
+    let code = random_otp();
+    let res = store_auth_token(
+        &state.db,
+        user.as_ref().map(|(id, _)| id.as_str()),
+        &email,
+        "login_otp",
+        &sha256_hex(&format!("{}:{}", email, code)),
+        chrono::Duration::minutes(OTP_TTL_MINUTES),
+    ).await;
+
+    let res = match res {
+        Ok(_) => {
+            let lang = recipient_language(&state.db, &email).await;
+            send_login_otp_email(&state.db, email, lang, code, OTP_TTL_MINUTES).await
+        }
+        Err(e) => Err(e.into()),
+    };
+
+    match res {
+        Ok(_) => {
+            outbox::wake();
+            (StatusCode::OK, Json(serde_json::json!({"message": "Đã gửi mã đăng nhập"}))).into_response()
+        }
+        Err(e) => {
+            println!("Lỗi tạo OTP: {:?}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, Json(serde_json::json!({"message": "Lỗi DB"}))).into_response()
+        }
+    }
+}
+
+#[derive(Debug, PartialEq)]
+enum OtpLogin {
+    Locked,
+    Login,
+    // Email chưa xác thực: người đăng ký trước chưa chắc là chủ hộp thư
+    ClaimUnverified,
+}
+
+fn otp_login_action(status: &str, email_verified: bool) -> OtpLogin {
+    if status == "locked" {
+        OtpLogin::Locked
+    } else if email_verified {
+        OtpLogin::Login
+    } else {
+        OtpLogin::ClaimUnverified
+    }
+}
+
+// Thu hồi mọi phiên (kèm refresh token) của user
+async fn revoke_user_sessions<'e, E>(executor: E, user_id: &str) -> Result<(), sqlx::Error>
+where
+    E: sqlx::Executor<'e, Database = sqlx::MySql>,
+{
+    sqlx::query("UPDATE sessions SET revoked_at = NOW() WHERE user_id = ? AND revoked_at IS NULL")
+        .bind(user_id)
+        .execute(executor)
+        .await?;
+    Ok(())
+}
+
+// Chủ hộp thư vừa chứng minh sở hữu email của một tài khoản CHƯA xác thực. Người đăng ký
+// trước có thể là kẻ gian dùng email của nạn nhân -> bỏ mật khẩu, liên kết social và mọi phiên
+// của họ trong cùng transaction rồi mới đánh dấu đã xác thực.
+async fn claim_unverified_account(state: &AppState, user_id: &str) -> Result<(), sqlx::Error> {
+    let mut tx = state.db.begin().await?;
+
+    let claimed = sqlx::query(
+        "UPDATE users SET email_verified = 1, password_hash = NULL WHERE id = ? AND email_verified = 0"
+    )
+    .bind(user_id)
+    .execute(&mut *tx)
+    .await?;
+
+    // Request song song đã xử lý trước -> không xóa gì thêm
+    if claimed.rows_affected() == 1 {
+        sqlx::query("DELETE FROM user_identities WHERE user_id = ?")
+            .bind(user_id)
+            .execute(&mut *tx)
+            .await?;
+        revoke_user_sessions(&mut *tx, user_id).await?;
+    }
+
+    tx.commit().await?;
+    state.auth_cache.invalidate_user(user_id);
+    Ok(())
+}
+
+// POST /api/auth/otp/verify  { email, code }
+// Email chưa có tài khoản -> tự tạo (giống đăng nhập Google)
+async fn verify_otp(
+    State(state): State<AppState>,
+    jar: CookieJar,
+    client: ClientInfo,
+    Json(payload): Json<OtpVerifyPayload>,
+) -> impl IntoResponse {
+    let email = normalize_email(&payload.email);
+
+    let row: Option<(String, String)> = sqlx::query_as(
+        "SELECT id, token_hash FROM auth_tokens
+         WHERE email = ? AND purpose = 'login_otp' AND used_at IS NULL AND expires_at > UTC_TIMESTAMP()
+         ORDER BY created_at DESC LIMIT 1"
+    )
+    .bind(&email)
+    .fetch_optional(&state.db)
+    .await
+    .unwrap_or(None);
+
+    let (token_id, token_hash) = match row {
+        Some(r) => r,
+        None => return (StatusCode::BAD_REQUEST, jar,
+            Json(serde_json::json!({"message": "Mã không hợp lệ hoặc đã hết hạn"}))).into_response(),
+    };
+
+    // Trừ lượt thử TRƯỚC khi so mã, trong 1 câu UPDATE: đoán song song cũng không vượt quá giới hạn
+    let attempt = sqlx::query(
+        "UPDATE auth_tokens SET attempts = attempts + 1 WHERE id = ? AND attempts < ? AND used_at IS NULL"
+    )
+    .bind(&token_id)
+    .bind(OTP_MAX_ATTEMPTS)
+    .execute(&state.db)
+    .await;
+
+    match attempt {
+        Ok(r) if r.rows_affected() == 1 => {}
+        Ok(_) => return (StatusCode::TOO_MANY_REQUESTS, jar,
+            Json(serde_json::json!({"message": "Nhập sai quá nhiều lần, vui lòng yêu cầu mã mới"}))).into_response(),
+        Err(e) => {
+            println!("Lỗi cập nhật lượt nhập OTP: {:?}", e);
+            return (StatusCode::INTERNAL_SERVER_ERROR, jar,
+                Json(serde_json::json!({"message": "Lỗi DB"}))).into_response();
+        }
+    }
+
+    if sha256_hex(&format!("{}:{}", email, payload.code.trim())) != token_hash {
+        return (StatusCode::UNAUTHORIZED, jar,
+            Json(serde_json::json!({"message": "Mã không đúng"}))).into_response();
+    }
+
+    let used = sqlx::query("UPDATE auth_tokens SET used_at = NOW() WHERE id = ? AND used_at IS NULL")
+        .bind(&token_id)
+        .execute(&state.db)
+        .await;
+
+    if !matches!(used, Ok(r) if r.rows_affected() == 1) {
+        return (StatusCode::BAD_REQUEST, jar,
+            Json(serde_json::json!({"message": "Mã đã được sử dụng"}))).into_response();
+    }
+
+    let existing = sqlx::query_as::<_, (String, String, String, bool)>(
+        "SELECT id, role, status, email_verified FROM users WHERE email = ?"
+    )
+    .bind(&email)
+    .fetch_optional(&state.db)
+    .await;
+
+    let user = match existing {
+        Ok(Some((id, role, status, email_verified))) => match otp_login_action(&status, email_verified) {
+            OtpLogin::Locked => return (StatusCode::FORBIDDEN, jar,
+                Json(serde_json::json!({"message": "Tài khoản đã bị khóa"}))).into_response(),
+            OtpLogin::Login => UserAuthDetails { id, email, role },
+            OtpLogin::ClaimUnverified => {
+                // Đăng nhập bằng mã gửi qua email cũng là xác thực email
+                if let Err(e) = claim_unverified_account(&state, &id).await {
+                    println!("Lỗi xác thực email qua OTP: {:?}", e);
+                    return (StatusCode::INTERNAL_SERVER_ERROR, jar,
+                        Json(serde_json::json!({"message": "Lỗi DB"}))).into_response();
+                }
+                UserAuthDetails { id, email, role }
+            }
+        },
+        Ok(None) => {
+            let uid = suid();
+            let insert = sqlx::query(
+                "INSERT INTO users (id, email, name, role, status, email_verified)
+                 VALUES (?, ?, ?, 'user', 'active', 1)"
+            )
+            .bind(&uid)
+            .bind(&email)
+            .bind(email.split('@').next().unwrap_or("User"))
+            .execute(&state.db)
+            .await;
+
+            if insert.is_err() {
                 return (StatusCode::INTERNAL_SERVER_ERROR, jar,
-                    Json(serde_json::json!({"message": "Lỗi DB"}))
-                ).into_response()
+                    Json(serde_json::json!({"message": "Lỗi tạo user"}))).into_response();
             }
-        };
+            UserAuthDetails { id: uid, email, role: "user".to_string() }
+        }
+        Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, jar,
+            Json(serde_json::json!({"message": "Lỗi DB"}))).into_response(),
+    };
+
+    generate_jwt_response(user, jar, &state, &client).await
+}
+
+// --- PHIÊN ĐĂNG NHẬP (REFRESH TOKEN XOAY VÒNG) ---
+//
+// Mỗi lần đăng nhập tạo một "family" phiên mới. Mỗi lần /refresh, refresh token cũ bị thu hồi
+// (replaced_by) và cấp token mới cùng family. Nếu một token đã bị thu hồi lại được dùng
+// (bị đánh cắp), toàn bộ family bị thu hồi. Riêng token vừa xoay vòng trong REFRESH_RACE_GRACE_SECONDS
+// giây là 2 request refresh song song (2 tab, request retry): request thua trả 409, không thu hồi gì.
+
+const ACCESS_TOKEN_TTL_HOURS: i64 = 1;
+const REFRESH_TOKEN_TTL_DAYS: i64 = 30;
+const REFRESH_COOKIE: &str = "refresh_token";
+const REFRESH_COOKIE_PATH: &str = "/api/auth";
+const REFRESH_RACE_GRACE_SECONDS: i64 = 10;
+
+// Nhãn thiết bị dễ đọc từ User-Agent, VD "Chrome trên Windows"
+fn device_label(user_agent: Option<&str>) -> Option<String> {
+    let ua = user_agent?;
+    let os = if ua.contains("Android") { "Android" }
+        else if ua.contains("iPhone") || ua.contains("iPad") { "iOS" }
+        else if ua.contains("Windows") { "Windows" }
+        else if ua.contains("Mac OS") { "macOS" }
+        else if ua.contains("Linux") { "Linux" }
+        else { "Không rõ" };
+    let browser = if ua.contains("Edg/") { "Edge" }
+        else if ua.contains("OPR/") { "Opera" }
+        else if ua.contains("Firefox/") { "Firefox" }
+        else if ua.contains("Chrome/") { "Chrome" }
+        else if ua.contains("Safari/") { "Safari" }
+        else { "Trình duyệt" };
+    Some(format!("{} trên {}", browser, os))
+}
+
+// Tạo bản ghi session mới, trả về refresh token (chưa hash) để đặt vào cookie
+async fn create_session<'e, E>(
+    executor: E,
+    session_id: &str,
+    user_id: &str,
+    family_id: &str,
+    client: &ClientInfo,
+) -> Result<String, sqlx::Error>
+where
+    E: sqlx::Executor<'e, Database = sqlx::MySql>,
+{
+    let refresh_token = random_token();
+    let expires_at = chrono::Utc::now().naive_utc() + chrono::Duration::days(REFRESH_TOKEN_TTL_DAYS);
+
+    sqlx::query(
+        "INSERT INTO sessions (id, family_id, user_id, token_hash, device, ip, user_agent, expires_at, last_used_at)
+         VALUES (?, ?, ?, ?, ?, ?, ?, ?, NOW())"
+    )
+    .bind(session_id)
+    .bind(family_id)
+    .bind(user_id)
+    .bind(sha256_hex(&refresh_token))
+    .bind(device_label(client.user_agent.as_deref()))
+    .bind(&client.ip)
+    .bind(&client.user_agent)
+    .bind(expires_at)
+    .execute(executor)
+    .await?;
+
+    Ok(refresh_token)
+}
 
-        return generate_jwt_response(user, jar, &state.db).await;
+async fn revoke_family(state: &AppState, family_id: &str) {
+    let res = sqlx::query(
+        "UPDATE sessions SET revoked_at = NOW() WHERE family_id = ? AND revoked_at IS NULL"
+    )
+    .bind(family_id)
+    .execute(&state.db)
+    .await;
+
+    if let Err(e) = res {
+        println!("Lỗi thu hồi phiên: {:?}", e);
     }
 
-    (StatusCode::BAD_REQUEST, jar,
-        Json(serde_json::json!({"message": "Yêu cầu đăng nhập bằng Google"}))
-    ).into_response()
+    let user: Option<(String,)> = sqlx::query_as("SELECT user_id FROM sessions WHERE family_id = ? LIMIT 1")
+        .bind(family_id)
+        .fetch_optional(&state.db)
+        .await
+        .unwrap_or(None);
+    if let Some((user_id,)) = user {
+        state.auth_cache.invalidate_user(&user_id);
+    }
 }
 
+fn clear_auth_cookies(jar: CookieJar, config: &Config) -> CookieJar {
+    let is_prod = config.is_production();
+
+    let cookie = Cookie::build(("token", ""))
+        .http_only(true)
+        .secure(is_prod)
+        .path("/")
+        .same_site(SameSite::Lax)
+        .max_age(Duration::seconds(0));
+
+    let refresh_cookie = Cookie::build((REFRESH_COOKIE, ""))
+        .http_only(true)
+        .secure(is_prod)
+        .path(REFRESH_COOKIE_PATH)
+        .same_site(SameSite::Strict)
+        .max_age(Duration::seconds(0));
+
+    jar.add(cookie).add(refresh_cookie)
+}
+
+// POST /api/auth/refresh - Chỉ cần refresh token (access token có thể đã hết hạn)
 pub async fn refresh(
     State(state): State<AppState>,
-    jar: CookieJar
+    jar: CookieJar,
+    client: ClientInfo,
 ) -> impl IntoResponse {
-    let token = match jar.get("token") {
-        Some(c) => c.value(),
+    let token = match jar.get(REFRESH_COOKIE) {
+        Some(c) => c.value().to_string(),
         None => return (StatusCode::UNAUTHORIZED,
             Json(serde_json::json!({"message": "Chưa đăng nhập"}))).into_response(),
     };
 
-    let secret = env::var("SECRET_KEY").unwrap_or("secret".to_string());
-    let decoding_key = DecodingKey::from_secret(secret.as_bytes());
+    let session: Option<(String, String, String, bool, bool, bool)> = sqlx::query_as(
+        "SELECT id, family_id, user_id, revoked_at IS NOT NULL, expires_at <= UTC_TIMESTAMP(),
+                replaced_by IS NOT NULL AND revoked_at > NOW() - INTERVAL ? SECOND
+         FROM sessions WHERE token_hash = ?"
+    )
+    .bind(REFRESH_RACE_GRACE_SECONDS)
+    .bind(sha256_hex(&token))
+    .fetch_optional(&state.db)
+    .await
+    .unwrap_or(None);
 
-    let claims = match decode::<Claims>(token, &decoding_key, &Validation::new(Algorithm::HS256)) {
-        Ok(data) => data.claims,
-        Err(_) => return (StatusCode::UNAUTHORIZED,
-            Json(serde_json::json!({"message": "Token lỗi"}))).into_response(),
+    let (session_id, family_id, user_id) = match session {
+        Some((id, family_id, user_id, false, false, _)) => (id, family_id, user_id),
+        Some((_, _, _, true, _, true)) => return refresh_race_response(),
+        Some((_, family_id, user_id, true, _, false)) => {
+            // Token đã xoay vòng mà vẫn bị dùng lại -> nghi bị đánh cắp, thu hồi cả family
+            println!("⚠️  Phát hiện dùng lại refresh token của user {}, thu hồi phiên {}", user_id, family_id);
+            revoke_family(&state, &family_id).await;
+            return (StatusCode::UNAUTHORIZED, clear_auth_cookies(jar, &state.config),
+                Json(serde_json::json!({"message": "Phiên đăng nhập không hợp lệ"}))).into_response();
+        }
+        _ => return (StatusCode::UNAUTHORIZED, clear_auth_cookies(jar, &state.config),
+            Json(serde_json::json!({"message": "Phiên đăng nhập đã hết hạn"}))).into_response(),
     };
 
-    let user = sqlx::query_as::<_, UserAuthDetails>(
-        "SELECT id, email, role FROM users WHERE id = ?"
+    let user = sqlx::query_as::<_, (String, String, String)>(
+        "SELECT email, role, status FROM users WHERE id = ?"
     )
-    .bind(&claims.sub)
+    .bind(&user_id)
     .fetch_optional(&state.db)
     .await;
 
-    match user {
-        Ok(Some(u)) => generate_jwt_response(u, jar, &state.db).await,
-        _ => (StatusCode::UNAUTHORIZED,
+    let user = match user {
+        Ok(Some((_, _, status))) if status == "locked" => {
+            revoke_family(&state, &family_id).await;
+            return (StatusCode::FORBIDDEN, clear_auth_cookies(jar, &state.config),
+                Json(serde_json::json!({"message": "Tài khoản đã bị khóa"}))).into_response();
+        }
+        Ok(Some((email, role, _))) => UserAuthDetails { id: user_id, email, role },
+        _ => return (StatusCode::UNAUTHORIZED, clear_auth_cookies(jar, &state.config),
             Json(serde_json::json!({"message": "User không tồn tại"}))).into_response(),
+    };
+
+    // Xoay vòng: đánh dấu token cũ đã bị thay thế rồi mới tạo token mới, cùng 1 transaction.
+    // Điều kiện revoked_at IS NULL chỉ cho 1 trong các request song song thắng; request thua
+    // không tạo phiên nào.
+    let mut tx = match state.db.begin().await {
+        Ok(tx) => tx,
+        Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR,
+            Json(serde_json::json!({"message": "Lỗi DB"}))).into_response(),
+    };
+
+    let new_id = suid();
+    let rotated = sqlx::query(
+        "UPDATE sessions SET revoked_at = NOW(), replaced_by = ? WHERE id = ? AND revoked_at IS NULL"
+    )
+    .bind(&new_id)
+    .bind(&session_id)
+    .execute(&mut *tx)
+    .await;
+
+    match rotated {
+        Ok(r) if r.rows_affected() == 1 => {}
+        Ok(_) => {
+            let _ = tx.rollback().await;
+            return refresh_race_response();
+        }
+        Err(e) => {
+            println!("Lỗi xoay vòng phiên: {:?}", e);
+            let _ = tx.rollback().await;
+            return (StatusCode::INTERNAL_SERVER_ERROR,
+                Json(serde_json::json!({"message": "Lỗi DB"}))).into_response();
+        }
     }
+
+    let new_token = match create_session(&mut *tx, &new_id, &user.id, &family_id, &client).await {
+        Ok(token) => token,
+        Err(e) => {
+            println!("Lỗi tạo phiên: {:?}", e);
+            let _ = tx.rollback().await;
+            return (StatusCode::INTERNAL_SERVER_ERROR,
+                Json(serde_json::json!({"message": "Lỗi DB"}))).into_response();
+        }
+    };
+
+    if tx.commit().await.is_err() {
+        return (StatusCode::INTERNAL_SERVER_ERROR,
+            Json(serde_json::json!({"message": "Lỗi DB"}))).into_response();
+    }
+
+    build_auth_response(user, jar, &state, &family_id, new_token).await
 }
 
+// Request refresh khác vừa dùng cùng token và đã nhận cookie mới: giữ nguyên cookie, client thử lại sau
+fn refresh_race_response() -> Response {
+    (StatusCode::CONFLICT, Json(serde_json::json!({
+        "code": "REFRESH_IN_PROGRESS",
+        "message": "Phiên đang được làm mới, vui lòng thử lại"
+    }))).into_response()
+}
+
+// Đăng nhập mới: tạo family phiên mới rồi cấp access + refresh token
 async fn generate_jwt_response(
     user: UserAuthDetails,
     jar: CookieJar,
-    db: &sqlx::MySqlPool
+    state: &AppState,
+    client: &ClientInfo,
 ) -> Response {
-    let exp = chrono::Utc::now()
-        .checked_add_signed(chrono::Duration::hours(1))
-        .unwrap()
-        .timestamp() as usize;
+    let family_id = suid();
+    match create_session(&state.db, &suid(), &user.id, &family_id, client).await {
+        Ok(refresh_token) => build_auth_response(user, jar, state, &family_id, refresh_token).await,
+        Err(e) => {
+            println!("Lỗi tạo phiên: {:?}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, jar,
+                Json(serde_json::json!({"message": "Lỗi tạo phiên đăng nhập"}))).into_response()
+        }
+    }
+}
+
+async fn build_auth_response(
+    user: UserAuthDetails,
+    jar: CookieJar,
+    state: &AppState,
+    family_id: &str,
+    refresh_token: String,
+) -> Response {
+    let exp = match chrono::Utc::now().checked_add_signed(chrono::Duration::hours(ACCESS_TOKEN_TTL_HOURS)) {
+        Some(t) => t.timestamp() as usize,
+        None => return (StatusCode::INTERNAL_SERVER_ERROR, jar,
+            Json(serde_json::json!({"message": "Lỗi tạo token"}))).into_response(),
+    };
 
     let claims = Claims {
         sub: user.id.clone(),
         email: user.email.clone(),
         role: user.role.clone(),
         exp,
+        sid: Some(family_id.to_string()),
     };
 
-    let secret = env::var("SECRET_KEY").unwrap_or("secret".into());
-    let token = encode(&Header::default(), &claims, &EncodingKey::from_secret(secret.as_bytes()))
-        .unwrap();
+    let token = match encode(&Header::default(), &claims, &EncodingKey::from_secret(state.config.jwt.secret.as_bytes())) {
+        Ok(t) => t,
+        Err(e) => {
+            println!("Lỗi tạo JWT: {:?}", e);
+            return (StatusCode::INTERNAL_SERVER_ERROR, jar,
+                Json(serde_json::json!({"message": "Lỗi tạo token"}))).into_response();
+        }
+    };
 
-    let is_prod = env::var("NODE_ENV").unwrap_or_default() == "production";
+    let is_prod = state.config.is_production();
 
     let cookie = Cookie::build(("token", token))
         .http_only(true)
         .secure(is_prod)
         .path("/")
         .same_site(SameSite::Lax)
-        .max_age(Duration::hours(1));
+        .max_age(Duration::hours(ACCESS_TOKEN_TTL_HOURS));
+
+    // Refresh token chỉ gửi kèm các request tới /api/auth
+    let refresh_cookie = Cookie::build((REFRESH_COOKIE, refresh_token))
+        .http_only(true)
+        .secure(is_prod)
+        .path(REFRESH_COOKIE_PATH)
+        .same_site(SameSite::Strict)
+        .max_age(Duration::days(REFRESH_TOKEN_TTL_DAYS));
 
     let full_info = sqlx::query_as::<_, UserResponse>(
         "SELECT id, email, name, picture, role, status, points, level, phone, address
          FROM users WHERE id = ?"
     )
     .bind(user.id)
-    .fetch_one(db)
-    .await
-    .unwrap();
+    .fetch_one(&state.db)
+    .await;
+
+    let full_info = match full_info {
+        Ok(u) => u,
+        Err(e) => {
+            println!("Lỗi đọc thông tin user: {:?}", e);
+            return (StatusCode::INTERNAL_SERVER_ERROR, jar,
+                Json(serde_json::json!({"message": "Lỗi DB"}))).into_response();
+        }
+    };
 
-    (StatusCode::OK, jar.add(cookie), Json(serde_json::json!({
+    (StatusCode::OK, jar.add(cookie).add(refresh_cookie), Json(serde_json::json!({
         "user": full_info,
         "message": "Success"
     })))
     .into_response()
 }
 
+// GET /api/auth/sessions - Các thiết bị đang đăng nhập
+async fn get_sessions(
+    State(state): State<AppState>,
+    auth: AuthUser,
+) -> impl IntoResponse {
+    let sessions = sqlx::query_as::<_, SessionItem>(
+        "SELECT family_id as id, device, ip, user_agent,
+                (SELECT MIN(s2.created_at) FROM sessions s2 WHERE s2.family_id = s.family_id) as created_at,
+                last_used_at, expires_at
+         FROM sessions s
+         WHERE user_id = ? AND revoked_at IS NULL AND expires_at > UTC_TIMESTAMP()
+         ORDER BY last_used_at DESC"
+    )
+    .bind(&auth.user_id)
+    .fetch_all(&state.db)
+    .await;
+
+    match sessions {
+        Ok(mut data) => {
+            for item in data.iter_mut() {
+                item.current = auth.session_id.as_deref() == Some(item.id.as_str());
+            }
+            (StatusCode::OK, Json(data)).into_response()
+        }
+        Err(_) => (StatusCode::INTERNAL_SERVER_ERROR,
+            Json(serde_json::json!({"message": "Lỗi DB"}))).into_response(),
+    }
+}
+
+// DELETE /api/auth/sessions/:id - Đăng xuất một thiết bị
+async fn delete_session(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Path(id): Path<String>,
+) -> impl IntoResponse {
+    let res = sqlx::query(
+        "UPDATE sessions SET revoked_at = NOW() WHERE family_id = ? AND user_id = ? AND revoked_at IS NULL"
+    )
+    .bind(&id)
+    .bind(&auth.user_id)
+    .execute(&state.db)
+    .await;
+
+    match res {
+        Ok(r) if r.rows_affected() == 0 => (StatusCode::NOT_FOUND,
+            Json(serde_json::json!({"message": "Không tìm thấy phiên"}))).into_response(),
+        Ok(_) => {
+            state.auth_cache.invalidate_user(&auth.user_id);
+            (StatusCode::OK, Json(serde_json::json!({"message": "Đã đăng xuất thiết bị"}))).into_response()
+        }
+        Err(_) => (StatusCode::INTERNAL_SERVER_ERROR,
+            Json(serde_json::json!({"message": "Lỗi DB"}))).into_response(),
+    }
+}
+
 async fn get_me(
     State(state): State<AppState>,
     auth: AuthUser
@@ -270,23 +1623,118 @@ async fn get_me(
     }
 }
 
-async fn logout(jar: CookieJar) -> impl IntoResponse {
-    let is_prod = env::var("NODE_ENV").unwrap_or_default() == "production";
+#[derive(Debug, Deserialize)]
+pub struct LanguagePayload {
+    pub language: String, // vi | en
+}
 
-    let cookie = Cookie::build(("token", ""))
-        .http_only(true)
-        .secure(is_prod)
-        .path("/")
-        .same_site(SameSite::Lax)
-        .max_age(Duration::seconds(0));
+// Đổi ngôn ngữ nhận email
+async fn update_language(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Json(payload): Json<LanguagePayload>
+) -> impl IntoResponse {
+    if !LANGUAGES.contains(&payload.language.as_str()) {
+        return (StatusCode::BAD_REQUEST, Json(serde_json::json!({"message": "Ngôn ngữ không hỗ trợ"}))).into_response();
+    }
+
+    let res = sqlx::query("UPDATE users SET language = ? WHERE id = ?")
+        .bind(&payload.language)
+        .bind(&auth.user_id)
+        .execute(&state.db)
+        .await;
+
+    match res {
+        Ok(_) => (StatusCode::OK, Json(serde_json::json!({"language": payload.language}))).into_response(),
+        Err(e) => {
+            println!("Lỗi update_language: {:?}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, Json(serde_json::json!({"message": "Lỗi DB"}))).into_response()
+        }
+    }
+}
+
+async fn logout(State(state): State<AppState>, jar: CookieJar) -> impl IntoResponse {
+    // Thu hồi phiên phía server để refresh token không dùng lại được
+    if let Some(c) = jar.get(REFRESH_COOKIE) {
+        let family: Option<(String,)> = sqlx::query_as("SELECT family_id FROM sessions WHERE token_hash = ?")
+            .bind(sha256_hex(c.value()))
+            .fetch_optional(&state.db)
+            .await
+            .unwrap_or(None);
 
-    (StatusCode::OK, jar.add(cookie), Json(serde_json::json!({"message": "Logout"})))
+        if let Some((family_id,)) = family {
+            revoke_family(&state, &family_id).await;
+        }
+    }
+
+    (StatusCode::OK, clear_auth_cookies(jar, &state.config), Json(serde_json::json!({"message": "Logout"})))
 }
 
 pub fn auth_routes() -> Router<AppState> {
     Router::new()
         .route("/login", post(login))
         .route("/me", get(get_me))
+        .route("/me/language", put(update_language))
         .route("/refresh", post(refresh))
         .route("/logout", post(logout))
+        .route("/register", post(register))
+        .route("/verify-email", post(verify_email))
+        .route("/verify-email/resend", post(resend_verification))
+        .route("/password/forgot", post(forgot_password))
+        .route("/password/reset", post(reset_password))
+        .route("/otp/request", post(request_otp))
+        .route("/otp/verify", post(verify_otp))
+        .route("/identities", get(get_identities).post(add_identity))
+        .route("/identities/:provider", delete(remove_identity))
+        .route("/sessions", get(get_sessions))
+        .route("/sessions/:id", delete(delete_session))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ip(s: &str) -> IpAddr {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn client_ip_ignores_headers_without_trusted_proxy() {
+        let peer = Some(ip("203.0.113.7"));
+        assert_eq!(client_ip(peer, Some("1.2.3.4"), Some("5.6.7.8"), &[]), peer);
+        // Proxy có cấu hình nhưng request không đi qua nó
+        assert_eq!(client_ip(peer, Some("1.2.3.4"), None, &[ip("10.0.0.1")]), peer);
+        assert_eq!(client_ip(None, Some("1.2.3.4"), None, &[ip("10.0.0.1")]), None);
+    }
+
+    #[test]
+    fn client_ip_reads_forwarded_for_behind_trusted_proxy() {
+        let trusted = [ip("10.0.0.1"), ip("10.0.0.2")];
+        let peer = Some(ip("10.0.0.1"));
+        assert_eq!(client_ip(peer, Some("198.51.100.9"), None, &trusted), Some(ip("198.51.100.9")));
+        // Client tự thêm IP giả vào đầu XFF: lấy IP cuối cùng không phải proxy
+        assert_eq!(
+            client_ip(peer, Some("1.1.1.1, 198.51.100.9, 10.0.0.2"), None, &trusted),
+            Some(ip("198.51.100.9"))
+        );
+        assert_eq!(client_ip(peer, Some("10.0.0.2"), None, &trusted), Some(ip("10.0.0.2")));
+    }
+
+    #[test]
+    fn otp_login_claims_unverified_accounts() {
+        // Tài khoản chưa xác thực -> xóa mật khẩu/liên kết/phiên của người đăng ký trước
+        assert_eq!(otp_login_action("active", false), OtpLogin::ClaimUnverified);
+        assert_eq!(otp_login_action("active", true), OtpLogin::Login);
+        assert_eq!(otp_login_action("locked", false), OtpLogin::Locked);
+        assert_eq!(otp_login_action("locked", true), OtpLogin::Locked);
+    }
+
+    #[test]
+    fn client_ip_falls_back_to_real_ip_then_peer() {
+        let trusted = [ip("127.0.0.1")];
+        let peer = Some(ip("127.0.0.1"));
+        assert_eq!(client_ip(peer, None, Some(" 198.51.100.9 "), &trusted), Some(ip("198.51.100.9")));
+        assert_eq!(client_ip(peer, Some("garbage"), None, &trusted), peer);
+        assert_eq!(client_ip(peer, None, None, &trusted), peer);
+    }
 }
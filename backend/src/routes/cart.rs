@@ -1,6 +1,6 @@
 use axum::{
     extract::{State, Path, Json},
-    Router, routing::{get, post, delete},
+    Router, routing::{get, delete},
     http::StatusCode,
     response::IntoResponse,
 };
@@ -8,14 +8,14 @@ use crate::AppState;
 use crate::routes::auth::AuthUser;
 use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
-use rust_decimal::Decimal;
+use crate::utils::Money;
 
 // Struct trả về cho Frontend (giống cấu trúc Redux store)
 #[derive(Debug, Serialize, FromRow)]
 pub struct CartItemResponse {
     pub id: String,          // product_id
     pub name: String,
-    pub price: Decimal,
+    pub price: Money,
     pub image: Option<String>, // Lấy ảnh đầu tiên hoặc ảnh đại diện
     pub quantity: i32,
 }
@@ -32,9 +32,13 @@ async fn get_cart(State(state): State<AppState>, auth: AuthUser) -> impl IntoRes
     // Join bảng cart_items với products để lấy thông tin chi tiết
     let sql = "
         SELECT p.id, p.name, p.price, 
-               -- Xử lý lấy ảnh: nếu là JSON array thì lấy phần tử đầu, nếu string thì lấy nguyên
+               -- Xử lý lấy ảnh: ưu tiên bản thumb của ảnh đầu, dữ liệu cũ là mảng URL, hoặc string thì lấy nguyên
                CASE 
-                 WHEN JSON_VALID(p.images) THEN JSON_UNQUOTE(JSON_EXTRACT(p.images, '$[0]'))
+                 WHEN JSON_VALID(p.images) THEN COALESCE(
+                   JSON_UNQUOTE(JSON_EXTRACT(p.images, '$[0].variants.thumb.url')),
+                   JSON_UNQUOTE(JSON_EXTRACT(p.images, '$[0].url')),
+                   JSON_UNQUOTE(JSON_EXTRACT(p.images, '$[0]'))
+                 )
                  ELSE p.images 
                END as image,
                c.quantity
@@ -4,13 +4,13 @@ use axum::{
     http::StatusCode,
     response::IntoResponse,
 };
-use axum_extra::extract::cookie::CookieJar; // Cần thêm cái này để đọc cookie
-use jsonwebtoken::{decode, DecodingKey, Validation, Algorithm}; // Để giải mã token
 use crate::AppState;
 use crate::utils::suid;
-use crate::routes::auth::Claims; // Import Claims từ auth để đọc token
+use crate::utils::notify;
+use crate::utils::outbox;
+use crate::utils::EmailError;
+use crate::routes::auth::OptionalAuthUser; // Đăng nhập không bắt buộc
 use serde::Deserialize;
-use std::env;
 
 #[derive(Deserialize)]
 pub struct ContactRequest {
@@ -20,7 +20,7 @@ pub struct ContactRequest {
 
 async fn submit_contact(
     State(state): State<AppState>,
-    jar: CookieJar, // Thêm CookieJar để lấy token tùy chọn
+    OptionalAuthUser(auth): OptionalAuthUser,
     Json(payload): Json<ContactRequest>,
 ) -> impl IntoResponse {
     // 1. Validate cơ bản
@@ -28,33 +28,45 @@ async fn submit_contact(
         return (StatusCode::BAD_REQUEST, Json("Vui lòng điền đầy đủ thông tin")).into_response();
     }
 
-    // 2. Kiểm tra xem người dùng có đăng nhập không (Optional Auth)
-    let mut user_id: Option<String> = None;
-
-    if let Some(token_cookie) = jar.get("token") {
-        let token = token_cookie.value();
-        let secret = env::var("SECRET_KEY").unwrap_or("secret".to_string());
-        let decoding_key = DecodingKey::from_secret(secret.as_bytes());
-        
-        // Nếu giải mã token thành công -> Lấy user_id
-        if let Ok(token_data) = decode::<Claims>(token, &decoding_key, &Validation::new(Algorithm::HS256)) {
-            user_id = Some(token_data.claims.sub);
-        }
-    }
+    // 2. Nếu người dùng đã đăng nhập thì gắn user_id
+    let user_id: Option<String> = auth.map(|a| a.user_id);
+    let registered = user_id.is_some();
 
     let id = suid();
 
+    let mut tx = match state.db.begin().await {
+        Ok(tx) => tx,
+        Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, Json("Lỗi server")).into_response(),
+    };
+
     // 3. Lưu vào Database (Có thêm cột user_id)
     let res = sqlx::query("INSERT INTO contacts (id, user_id, email, message) VALUES (?, ?, ?, ?)")
         .bind(&id)
         .bind(user_id) // Nếu không đăng nhập, giá trị này là None (NULL trong DB)
         .bind(&payload.email)
         .bind(&payload.message)
-        .execute(&state.db)
+        .execute(&mut *tx)
         .await;
 
+    // 4. Báo nhân viên chăm sóc khách hàng (outbox, cùng transaction)
+    let res = match res {
+        Ok(_) => notify::new_contact(&mut tx, &payload.email, &payload.message, registered).await,
+        Err(e) => Err(e.into()),
+    };
+
+    let res = match res {
+        Ok(_) => tx.commit().await.map_err(EmailError::from),
+        Err(e) => {
+            let _ = tx.rollback().await;
+            Err(e)
+        }
+    };
+
     match res {
-        Ok(_) => (StatusCode::OK, Json("Đã gửi tin nhắn thành công")).into_response(),
+        Ok(_) => {
+            outbox::wake();
+            (StatusCode::OK, Json("Đã gửi tin nhắn thành công")).into_response()
+        }
         Err(e) => {
             println!("Lỗi contact: {:?}", e);
             (StatusCode::INTERNAL_SERVER_ERROR, Json("Lỗi server")).into_response()
@@ -0,0 +1,203 @@
+// src/routes/email_outbox.rs
+// Theo dõi hàng đợi email cho admin: xem email đang chờ / lỗi / đã gửi và gửi lại email dead
+use axum::{
+    extract::{ State, Path, Json, Query },
+    http::{ HeaderMap, StatusCode },
+    response::IntoResponse,
+    Router,
+    routing::{ get, post },
+};
+use crate::AppState;
+use crate::routes::auth::ClientInfo;
+use crate::routes::rbac::{ perm, RequirePermission };
+use crate::utils::audit::{ record_audit, AuditEntry };
+use crate::utils::outbox::{ self, OutboxAttachment, STATUSES, STATUS_DEAD };
+use serde::{ Deserialize, Serialize };
+use sqlx::{ FromRow, MySql, QueryBuilder };
+
+#[derive(Debug, Deserialize)]
+pub struct OutboxFilter {
+    pub status: Option<String>,   // pending | sending | sent | dead
+    pub template: Option<String>,
+    pub search: Option<String>,   // Tìm theo email người nhận
+    pub page: Option<i64>,
+    pub limit: Option<i64>,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct OutboxItem {
+    pub id: String,
+    pub to_email: String,
+    pub template: String,
+    pub lang: String,
+    pub subject: String,
+    pub status: String,
+    pub attempts: i32,
+    pub last_error: Option<String>,
+    pub next_attempt_at: Option<chrono::NaiveDateTime>,
+    pub created_at: Option<chrono::NaiveDateTime>,
+    pub sent_at: Option<chrono::NaiveDateTime>,
+}
+
+#[derive(Debug, Serialize, FromRow)]
+pub struct OutboxDetail {
+    #[sqlx(flatten)]
+    #[serde(flatten)]
+    pub item: OutboxItem,
+    pub html_body: String,
+    pub text_body: String,
+    pub attachments: Option<sqlx::types::Json<Vec<OutboxAttachment>>>,
+}
+
+const OUTBOX_SELECT: &str =
+    "SELECT id, to_email, template, lang, subject, status, attempts, last_error, next_attempt_at, created_at, sent_at
+     FROM email_outbox";
+
+// Danh sách email (mới nhất trước), tổng số bản ghi qua header X-Total-Count
+async fn get_outbox(
+    State(state): State<AppState>,
+    _: RequirePermission<perm::EmailsRead>,
+    Query(filter): Query<OutboxFilter>
+) -> impl IntoResponse {
+    if let Some(status) = &filter.status {
+        if !STATUSES.contains(&status.as_str()) {
+            return (StatusCode::BAD_REQUEST, Json("Trạng thái không hợp lệ")).into_response();
+        }
+    }
+    let limit = filter.limit.unwrap_or(50).clamp(1, 200);
+    let page = filter.page.unwrap_or(1).max(1);
+
+    fn push_filters<'a>(qb: &mut QueryBuilder<'a, MySql>, filter: &'a OutboxFilter) {
+        if let Some(status) = &filter.status {
+            qb.push(" AND status = ").push_bind(status);
+        }
+        if let Some(template) = &filter.template {
+            qb.push(" AND template = ").push_bind(template);
+        }
+        if let Some(search) = &filter.search {
+            qb.push(" AND to_email LIKE ").push_bind(format!("%{}%", search.trim()));
+        }
+    }
+
+    let mut count_qb: QueryBuilder<MySql> = QueryBuilder::new("SELECT COUNT(*) FROM email_outbox WHERE 1 = 1");
+    push_filters(&mut count_qb, &filter);
+    let total: (i64,) = count_qb.build_query_as().fetch_one(&state.db).await.unwrap_or((0,));
+
+    let mut qb: QueryBuilder<MySql> = QueryBuilder::new(OUTBOX_SELECT);
+    qb.push(" WHERE 1 = 1");
+    push_filters(&mut qb, &filter);
+    qb.push(" ORDER BY created_at DESC LIMIT ")
+        .push_bind(limit)
+        .push(" OFFSET ")
+        .push_bind((page - 1) * limit);
+
+    match qb.build_query_as::<OutboxItem>().fetch_all(&state.db).await {
+        Ok(data) => {
+            let mut headers = HeaderMap::new();
+            headers.insert("x-total-count", total.0.into());
+            (StatusCode::OK, headers, Json(data)).into_response()
+        }
+        Err(e) => {
+            println!("Lỗi get_outbox: {:?}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, Json("Error")).into_response()
+        }
+    }
+}
+
+// Chi tiết 1 email, kèm nội dung đã render và danh sách file đính kèm
+async fn get_outbox_detail(
+    State(state): State<AppState>,
+    _: RequirePermission<perm::EmailsRead>,
+    Path(id): Path<String>
+) -> impl IntoResponse {
+    let item = sqlx
+        ::query_as::<_, OutboxDetail>(
+            "SELECT id, to_email, template, lang, subject, status, attempts, last_error, next_attempt_at,
+                    created_at, sent_at, html_body, text_body, attachments
+             FROM email_outbox WHERE id = ?"
+        )
+        .bind(&id)
+        .fetch_optional(&state.db).await;
+
+    match item {
+        Ok(Some(item)) => (StatusCode::OK, Json(item)).into_response(),
+        Ok(None) => (StatusCode::NOT_FOUND, Json("Không tìm thấy email")).into_response(),
+        Err(e) => {
+            println!("Lỗi get_outbox_detail: {:?}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, Json("Error")).into_response()
+        }
+    }
+}
+
+// Gửi lại email đã bị bỏ (dead): đưa về hàng đợi, bộ đếm lần thử tính lại từ đầu
+async fn resend_email(
+    State(state): State<AppState>,
+    admin: RequirePermission<perm::EmailsResend>,
+    client: ClientInfo,
+    Path(id): Path<String>
+) -> impl IntoResponse {
+    let mut tx = match state.db.begin().await {
+        Ok(tx) => tx,
+        Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, Json("Error")).into_response(),
+    };
+
+    let current: Option<(String, String, i32, Option<String>)> = sqlx
+        ::query_as("SELECT to_email, status, attempts, last_error FROM email_outbox WHERE id = ? FOR UPDATE")
+        .bind(&id)
+        .fetch_optional(&mut *tx).await
+        .unwrap_or(None);
+
+    let (to_email, status, attempts, last_error) = match current {
+        Some(row) => row,
+        None => return (StatusCode::NOT_FOUND, Json("Không tìm thấy email")).into_response(),
+    };
+    if status != STATUS_DEAD {
+        return (StatusCode::CONFLICT, Json("Chỉ gửi lại được email đã lỗi (dead)")).into_response();
+    }
+
+    let res = outbox::requeue(&mut *tx, &id).await;
+    let res = match res {
+        Ok(_) => record_audit(&mut *tx, AuditEntry {
+            actor_id: &admin.user_id,
+            action: "emails.resend",
+            target_type: "email_outbox",
+            target_id: &id,
+            before: Some(serde_json::json!({
+                "to_email": to_email,
+                "status": status,
+                "attempts": attempts,
+                "last_error": last_error,
+            })),
+            after: Some(serde_json::json!({ "status": outbox::STATUS_PENDING })),
+            reason: None,
+            ip: client.ip.as_deref(),
+        }).await,
+        Err(e) => Err(e),
+    };
+
+    let res = match res {
+        Ok(_) => tx.commit().await,
+        Err(e) => {
+            let _ = tx.rollback().await;
+            Err(e)
+        }
+    };
+
+    match res {
+        Ok(_) => {
+            outbox::wake();
+            (StatusCode::OK, Json("Đã đưa email vào hàng đợi gửi lại")).into_response()
+        }
+        Err(e) => {
+            println!("Lỗi resend_email: {:?}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, Json("Error DB")).into_response()
+        }
+    }
+}
+
+pub fn email_outbox_routes() -> Router<AppState> {
+    Router::new()
+        .route("/email-outbox", get(get_outbox))
+        .route("/email-outbox/:id", get(get_outbox_detail))
+        .route("/email-outbox/:id/resend", post(resend_email))
+}
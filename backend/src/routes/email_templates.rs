@@ -0,0 +1,243 @@
+// src/routes/email_templates.rs
+// Quản lý template email cho admin: xem, sửa (lưu DB, ghi đè bản mặc định), khôi phục mặc định và preview với dữ liệu mẫu
+use axum::{
+    extract::{ State, Path, Json },
+    http::StatusCode,
+    response::IntoResponse,
+    Router,
+    routing::{ get, post },
+};
+use crate::AppState;
+use crate::routes::auth::ClientInfo;
+use crate::routes::rbac::{ perm, RequirePermission };
+use crate::utils::audit::{ record_audit, AuditEntry };
+use crate::utils::templates::{ self, TemplateSource, LANGUAGES, TEMPLATES };
+use serde::Deserialize;
+use serde_json::Value;
+
+// Kiểm tra name + lang có trong danh sách
+fn check_template(name: &str, lang: &str) -> Result<(), (StatusCode, Json<&'static str>)> {
+    if templates::find_template(name).is_none() {
+        return Err((StatusCode::NOT_FOUND, Json("Không tìm thấy template")));
+    }
+    if !LANGUAGES.contains(&lang) {
+        return Err((StatusCode::BAD_REQUEST, Json("Ngôn ngữ không hỗ trợ")));
+    }
+    Ok(())
+}
+
+// Danh sách template + ngôn ngữ nào đã được admin sửa
+async fn list_templates(_: RequirePermission<perm::SettingsRead>) -> impl IntoResponse {
+    let data: Vec<Value> = TEMPLATES
+        .iter()
+        .map(|t| {
+            let languages: Vec<Value> = LANGUAGES
+                .iter()
+                .map(|lang| serde_json::json!({
+                    "lang": lang,
+                    "customized": templates::override_source(t.name, lang).is_some(),
+                }))
+                .collect();
+            serde_json::json!({ "name": t.name, "description": t.description, "languages": languages })
+        })
+        .collect();
+
+    (StatusCode::OK, Json(data)).into_response()
+}
+
+// Nội dung đang dùng + bản mặc định (để so sánh / khôi phục)
+async fn get_template(
+    _: RequirePermission<perm::SettingsRead>,
+    Path((name, lang)): Path<(String, String)>
+) -> impl IntoResponse {
+    if let Err(e) = check_template(&name, &lang) {
+        return e.into_response();
+    }
+
+    (StatusCode::OK, Json(serde_json::json!({
+        "name": name,
+        "lang": lang,
+        "customized": templates::override_source(&name, &lang).is_some(),
+        "current": templates::current_source(&name, &lang),
+        "default": templates::builtin_source(&name, &lang),
+    }))).into_response()
+}
+
+async fn update_template(
+    State(state): State<AppState>,
+    admin: RequirePermission<perm::SettingsWrite>,
+    client: ClientInfo,
+    Path((name, lang)): Path<(String, String)>,
+    Json(payload): Json<TemplateSource>
+) -> impl IntoResponse {
+    if let Err(e) = check_template(&name, &lang) {
+        return e.into_response();
+    }
+
+    // Render thử với dữ liệu mẫu, lỗi cú pháp thì không lưu
+    let sample = (templates::find_template(&name).unwrap().sample)();
+    if let Err(e) = templates::render_source(&payload, &lang, &sample) {
+        return (StatusCode::BAD_REQUEST, Json(format!("Template lỗi: {}", e))).into_response();
+    }
+
+    let mut tx = match state.db.begin().await {
+        Ok(tx) => tx,
+        Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, Json("Error")).into_response(),
+    };
+
+    let before = templates::current_source(&name, &lang);
+    let res = sqlx
+        ::query(
+            "INSERT INTO email_templates (name, lang, subject, html_body, text_body, updated_by) VALUES (?, ?, ?, ?, ?, ?)
+             ON DUPLICATE KEY UPDATE subject = VALUES(subject), html_body = VALUES(html_body),
+                 text_body = VALUES(text_body), updated_by = VALUES(updated_by)"
+        )
+        .bind(&name)
+        .bind(&lang)
+        .bind(&payload.subject)
+        .bind(&payload.html_body)
+        .bind(&payload.text_body)
+        .bind(&admin.user_id)
+        .execute(&mut *tx).await;
+
+    let target_id = format!("{}:{}", name, lang);
+    let res = match res {
+        Ok(_) => record_audit(&mut *tx, AuditEntry {
+            actor_id: &admin.user_id,
+            action: "email_templates.update",
+            target_type: "email_template",
+            target_id: &target_id,
+            before: Some(serde_json::json!(before)),
+            after: Some(serde_json::json!(payload)),
+            reason: None,
+            ip: client.ip.as_deref(),
+        }).await,
+        Err(e) => Err(e),
+    };
+
+    let res = match res {
+        Ok(_) => tx.commit().await,
+        Err(e) => {
+            let _ = tx.rollback().await;
+            Err(e)
+        }
+    };
+
+    match res {
+        Ok(_) => {
+            templates::set_override(&name, &lang, payload);
+            (StatusCode::OK, Json("Đã lưu template")).into_response()
+        }
+        Err(e) => {
+            println!("Lỗi update_template: {:?}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, Json("Error DB")).into_response()
+        }
+    }
+}
+
+// Xóa bản sửa -> dùng lại bản mặc định
+async fn reset_template(
+    State(state): State<AppState>,
+    admin: RequirePermission<perm::SettingsWrite>,
+    client: ClientInfo,
+    Path((name, lang)): Path<(String, String)>
+) -> impl IntoResponse {
+    if let Err(e) = check_template(&name, &lang) {
+        return e.into_response();
+    }
+    let before = match templates::override_source(&name, &lang) {
+        Some(s) => s,
+        None => return (StatusCode::OK, Json("Template đang là bản mặc định")).into_response(),
+    };
+
+    let mut tx = match state.db.begin().await {
+        Ok(tx) => tx,
+        Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, Json("Error")).into_response(),
+    };
+
+    let res = sqlx
+        ::query("DELETE FROM email_templates WHERE name = ? AND lang = ?")
+        .bind(&name)
+        .bind(&lang)
+        .execute(&mut *tx).await;
+
+    let target_id = format!("{}:{}", name, lang);
+    let res = match res {
+        Ok(_) => record_audit(&mut *tx, AuditEntry {
+            actor_id: &admin.user_id,
+            action: "email_templates.reset",
+            target_type: "email_template",
+            target_id: &target_id,
+            before: Some(serde_json::json!(before)),
+            after: None,
+            reason: None,
+            ip: client.ip.as_deref(),
+        }).await,
+        Err(e) => Err(e),
+    };
+
+    let res = match res {
+        Ok(_) => tx.commit().await,
+        Err(e) => {
+            let _ = tx.rollback().await;
+            Err(e)
+        }
+    };
+
+    match res {
+        Ok(_) => {
+            templates::remove_override(&name, &lang);
+            (StatusCode::OK, Json("Đã khôi phục template mặc định")).into_response()
+        }
+        Err(e) => {
+            println!("Lỗi reset_template: {:?}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, Json("Error DB")).into_response()
+        }
+    }
+}
+
+#[derive(Debug, Default, Deserialize)]
+#[serde(default)]
+struct PreviewReq {
+    // Bản nháp chưa lưu, trống thì dùng bản đang dùng
+    subject: Option<String>,
+    html_body: Option<String>,
+    text_body: Option<String>,
+    // Dữ liệu render, trống thì dùng dữ liệu mẫu
+    data: Option<Value>,
+}
+
+async fn preview_template(
+    _: RequirePermission<perm::SettingsRead>,
+    Path((name, lang)): Path<(String, String)>,
+    payload: Option<Json<PreviewReq>>
+) -> impl IntoResponse {
+    if let Err(e) = check_template(&name, &lang) {
+        return e.into_response();
+    }
+    let payload = payload.map(|Json(p)| p).unwrap_or_default();
+
+    let current = templates::current_source(&name, &lang).unwrap_or_else(|| TemplateSource {
+        subject: String::new(),
+        html_body: String::new(),
+        text_body: String::new(),
+    });
+    let source = TemplateSource {
+        subject: payload.subject.unwrap_or(current.subject),
+        html_body: payload.html_body.unwrap_or(current.html_body),
+        text_body: payload.text_body.unwrap_or(current.text_body),
+    };
+    let data = payload.data.unwrap_or_else(|| (templates::find_template(&name).unwrap().sample)());
+
+    match templates::render_source(&source, &lang, &data) {
+        Ok(rendered) => (StatusCode::OK, Json(rendered)).into_response(),
+        Err(e) => (StatusCode::BAD_REQUEST, Json(format!("Template lỗi: {}", e))).into_response(),
+    }
+}
+
+pub fn email_template_routes() -> Router<AppState> {
+    Router::new()
+        .route("/email-templates", get(list_templates))
+        .route("/email-templates/:name/:lang", get(get_template).put(update_template).delete(reset_template))
+        .route("/email-templates/:name/:lang/preview", post(preview_template))
+}
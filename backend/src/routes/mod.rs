@@ -6,6 +6,7 @@ pub mod reviews;
 pub mod orders;
 pub mod cart;
 pub mod contact;
-pub mod admin; // Module dành riêng cho admin
-// Re-export để dễ dùng nếu cần
-pub use auth::AuthUser;
\ No newline at end of file
+pub mod rbac;
+pub mod email_templates;
+pub mod email_outbox;
+pub mod admin; // Module dành riêng cho admin
\ No newline at end of file
@@ -2,24 +2,26 @@ use axum::{
     extract::{ State, Json, Path },
     Router,
     routing::{ post, get, put },
-    http::StatusCode,
+    http::{ header, StatusCode },
     response::IntoResponse,
 };
 use crate::AppState;
 use crate::routes::auth::AuthUser;
+use crate::routes::rbac::{ self, perm, Permission };
+use crate::utils::invoice;
 use crate::utils::suid;
 use serde::{ Deserialize, Serialize };
-use sqlx::FromRow;
-use rust_decimal::Decimal;
-use rust_decimal::prelude::ToPrimitive;
-use crate::utils::email::send_order_thank_you_email;
+use sqlx::{ FromRow, MySql, MySqlConnection, Transaction };
+use crate::utils::{ recipient_language, send_order_confirmation_email, send_order_thank_you_email, EmailError, Money, OrderEmailItem, OrderEmailShipping, OrderPlacedEmail };
+use crate::utils::notify;
+use crate::utils::outbox;
 
 // --- STRUCTS ---
 #[derive(Deserialize)]
 pub struct OrderItemRequest {
     pub product_id: String,
     pub quantity: i32,
-    pub price: Decimal,
+    pub price: Money,
 }
 
 #[derive(Deserialize)]
@@ -39,12 +41,110 @@ pub struct CreateOrderRequest {
 #[derive(Serialize, FromRow)]
 pub struct OrderHistory {
     pub id: String,
-    pub final_amount: Decimal,
+    pub final_amount: Money,
     pub status: String,
     pub points_earned: i32,
     pub created_at: Option<chrono::NaiveDateTime>,
 }
 
+// Mỗi 1.000 ₫ thanh toán được 1 điểm
+pub(crate) const POINT_UNIT_VND: i64 = 1000;
+
+// --- HELPERS ---
+
+// Cập nhật level user theo điểm hiện tại và các mốc trong settings
+// Logic: Diamond(10000), Gold(5000), Silver(1000), Bronze(<1000)
+pub(crate) async fn update_user_level(tx: &mut Transaction<'_, MySql>, user_id: &str) {
+    // 1. Lấy các mốc điểm từ bảng settings
+    // Chúng ta lấy 3 dòng cấu hình cùng lúc
+    let settings_rows: Vec<(String, Option<String>)> = sqlx::query_as(
+        "SELECT id, value FROM settings WHERE id IN ('level_silver', 'level_gold', 'level_diamond')"
+    )
+    .fetch_all(&mut **tx)
+    .await
+    .unwrap_or(vec![]);
+
+    // 2. Đặt giá trị mặc định (đề phòng chưa config trong DB)
+    let mut s_silver = 1000;
+    let mut s_gold = 5000;
+    let mut s_diamond = 10000;
+
+    // 3. Parse dữ liệu từ DB vào biến
+    for (id, value_opt) in settings_rows {
+        if let Some(val_str) = value_opt {
+            if let Ok(val_int) = val_str.parse::<i32>() {
+                match id.as_str() {
+                    "level_silver" => s_silver = val_int,
+                    "level_gold" => s_gold = val_int,
+                    "level_diamond" => s_diamond = val_int,
+                    _ => {}
+                }
+            }
+        }
+    }
+
+    // 4. Cập nhật Level User với các biến động
+    // Lưu ý thứ tự bind: Diamond -> Gold -> Silver -> UserID
+    let _ = sqlx::query(r#"
+        UPDATE users 
+        SET level = CASE 
+            WHEN points >= ? THEN 'DIAMOND'
+            WHEN points >= ? THEN 'GOLD'
+            WHEN points >= ? THEN 'SILVER'
+            ELSE 'BRONZE'
+        END
+        WHERE id = ?
+    "#)
+    .bind(s_diamond)  // Bind vào dấu ? thứ 1
+    .bind(s_gold)     // Bind vào dấu ? thứ 2
+    .bind(s_silver)   // Bind vào dấu ? thứ 3
+    .bind(user_id) // Bind vào dấu ? thứ 4 (WHERE id)
+    .execute(&mut **tx).await;
+}
+
+// Các dòng sản phẩm của đơn để đưa vào email (template tự escape tên sản phẩm)
+pub(crate) async fn order_email_items(conn: &mut MySqlConnection, order_id: &str) -> Result<Vec<OrderEmailItem>, sqlx::Error> {
+    let items: Vec<(String, i32, Money)> = sqlx::query_as(
+        "SELECT p.name, oi.quantity, oi.price 
+         FROM order_items oi 
+         JOIN products p ON oi.product_id = p.id 
+         WHERE oi.order_id = ?"
+    )
+    .bind(order_id)
+    .fetch_all(conn).await?;
+
+    Ok(items.into_iter().map(|(name, quantity, price)| OrderEmailItem { name, quantity, price }).collect())
+}
+
+// Email xác nhận cho khách + báo nhân viên, ghi vào outbox trong transaction tạo đơn
+async fn enqueue_order_placed_emails(
+    tx: &mut Transaction<'_, MySql>,
+    user_id: &str,
+    order_id: &str,
+    shipping: &ShippingInfo,
+    final_amount: Money
+) -> Result<(), EmailError> {
+    let (email,): (String,) = sqlx::query_as("SELECT email FROM users WHERE id = ?")
+        .bind(user_id)
+        .fetch_one(&mut **tx).await?;
+    let order = OrderPlacedEmail {
+        order_id: order_id.to_string(),
+        items: order_email_items(&mut *tx, order_id).await?,
+        shipping: OrderEmailShipping {
+            name: shipping.name.clone(),
+            phone: shipping.phone.clone(),
+            address: shipping.address.clone(),
+            note: shipping.note.clone().filter(|n| !n.trim().is_empty()),
+        },
+        total: final_amount,
+    };
+
+    notify::new_order(&mut *tx, &email, &order).await?;
+
+    let lang = recipient_language(&mut **tx, &email).await;
+    send_order_confirmation_email(&mut **tx, email, lang, &order).await
+}
+
 // --- HANDLERS ---
 
 async fn create_order(
@@ -52,15 +152,36 @@ async fn create_order(
     auth: AuthUser,
     Json(payload): Json<CreateOrderRequest>
 ) -> impl IntoResponse {
-    // Tính tổng tiền
-    let mut total_amount = Decimal::new(0, 0);
-    for item in &payload.items {
-        total_amount += item.price * Decimal::from(item.quantity);
+    if payload.items.is_empty() {
+        return (StatusCode::BAD_REQUEST, Json("Đơn hàng chưa có sản phẩm")).into_response();
+    }
+    // Số lượng <= 0 sẽ ra tổng tiền/điểm âm và cộng ngược tồn kho
+    if payload.items.iter().any(|item| item.quantity <= 0) {
+        return (StatusCode::BAD_REQUEST, Json("Số lượng sản phẩm không hợp lệ")).into_response();
+    }
+    // Giá âm hoặc lẻ hơn 1 đồng là dữ liệu sai, không làm tròn hộ
+    if payload.items.iter().any(|item| item.price.is_negative() || item.price.has_sub_minor_units()) {
+        return (StatusCode::BAD_REQUEST, Json("Giá sản phẩm không hợp lệ")).into_response();
     }
 
-    let discount_amount = Decimal::new(0, 0);
-    let final_amount = total_amount - discount_amount;
-    let points_earned = (final_amount.to_f64().unwrap_or(0.0) / 1000.0) as i32;
+    // Tính tổng tiền. Giá / số lượng từ request nên dùng checked_*: số quá lớn -> 400 thay vì panic
+    let discount_amount = Money::vnd(0);
+    let amounts = payload.items
+        .iter()
+        .try_fold(Money::vnd(0), |total, item| total.checked_add(item.price.checked_mul(item.quantity)?))
+        .and_then(|total| {
+            let final_amount = total.checked_sub(discount_amount)?;
+            let points = final_amount.div_floor(Money::vnd(POINT_UNIT_VND))?;
+            Ok((total, final_amount, i32::try_from(points).unwrap_or(i32::MAX)))
+        });
+
+    let (total_amount, final_amount, points_earned) = match amounts {
+        Ok(v) => v,
+        Err(e) => {
+            println!("Lỗi tính tiền đơn hàng: {}", e);
+            return (StatusCode::BAD_REQUEST, Json("Giá sản phẩm không hợp lệ")).into_response();
+        }
+    };
 
     let mut tx = match state.db.begin().await {
         Ok(tx) => tx,
@@ -137,6 +258,21 @@ async fn create_order(
                 return (StatusCode::INTERNAL_SERVER_ERROR, Json("Lỗi trừ tồn kho")).into_response();
             }
         }
+
+        // 2c. Vừa chạm ngưỡng sắp hết hàng -> báo nhân viên kho
+        let low_stock = match sqlx
+            ::query_as::<_, (i32,)>("SELECT stock FROM products WHERE id = ?")
+            .bind(&item.product_id)
+            .fetch_one(&mut *tx).await
+        {
+            Ok((new_stock,)) => notify::stock_changed(&mut tx, &item.product_id, new_stock + item.quantity, new_stock).await,
+            Err(e) => Err(e.into()),
+        };
+        if let Err(e) = low_stock {
+            println!("Lỗi báo sắp hết hàng: {:?}", e);
+            let _ = tx.rollback().await;
+            return (StatusCode::INTERNAL_SERVER_ERROR, Json("Lỗi trừ tồn kho")).into_response();
+        }
     }
 
     // 3. Lưu thông tin liên hệ mới nhất (CHƯA CỘNG ĐIỂM)
@@ -152,9 +288,17 @@ async fn create_order(
         return (StatusCode::INTERNAL_SERVER_ERROR, Json("Lỗi cập nhật user")).into_response();
     }
 
-    // 4. Commit
+    // 4. Email xác nhận đơn + báo nhân viên (outbox, cùng transaction)
+    if let Err(e) = enqueue_order_placed_emails(&mut tx, &auth.user_id, &order_id, &payload.shipping_info, final_amount).await {
+        println!("Lỗi ghi email đơn hàng: {:?}", e);
+        let _ = tx.rollback().await;
+        return (StatusCode::INTERNAL_SERVER_ERROR, Json("Lỗi tạo order")).into_response();
+    }
+
+    // 5. Commit
     match tx.commit().await {
-        Ok(_) =>
+        Ok(_) => {
+            outbox::wake();
             (
                 StatusCode::CREATED,
                 Json(
@@ -165,7 +309,8 @@ async fn create_order(
             "final_amount": final_amount
         })
                 ),
-            ).into_response(),
+            ).into_response()
+        }
         Err(_) => (StatusCode::INTERNAL_SERVER_ERROR, Json("Lỗi commit")).into_response(),
     }
 }
@@ -198,7 +343,7 @@ async fn confirm_receipt(
     };
 
     // 1. Kiểm tra đơn hàng
-    let order_info: Option<(String, Decimal, i32, String)> = sqlx
+    let order_info: Option<(String, Money, i32, String)> = sqlx
         ::query_as(
             "SELECT o.status, o.final_amount, o.points_earned, u.email 
          FROM orders o 
@@ -225,59 +370,21 @@ async fn confirm_receipt(
                 .bind(&auth.user_id)
                 .execute(&mut *tx).await;
 
-            // 3.1 --- THÊM ĐOẠN NÀY: CẬP NHẬT LEVEL TỰ ĐỘNG ---
-            // Logic: Diamond(10000), Gold(5000), Silver(1000), Bronze(<1000)
-           // 1. Lấy các mốc điểm từ bảng settings
-            // Chúng ta lấy 3 dòng cấu hình cùng lúc
-            let settings_rows: Vec<(String, Option<String>)> = sqlx::query_as(
-                "SELECT id, value FROM settings WHERE id IN ('level_silver', 'level_gold', 'level_diamond')"
-            )
-            .fetch_all(&mut *tx)
-            .await
-            .unwrap_or(vec![]);
-
-            // 2. Đặt giá trị mặc định (đề phòng chưa config trong DB)
-            let mut s_silver = 1000;
-            let mut s_gold = 5000;
-            let mut s_diamond = 10000;
-
-            // 3. Parse dữ liệu từ DB vào biến
-            for (id, value_opt) in settings_rows {
-                if let Some(val_str) = value_opt {
-                    if let Ok(val_int) = val_str.parse::<i32>() {
-                        match id.as_str() {
-                            "level_silver" => s_silver = val_int,
-                            "level_gold" => s_gold = val_int,
-                            "level_diamond" => s_diamond = val_int,
-                            _ => {}
-                        }
-                    }
-                }
-            }
-
-            // 4. Cập nhật Level User với các biến động
-            // Lưu ý thứ tự bind: Diamond -> Gold -> Silver -> UserID
-            let _ = sqlx::query(r#"
-                UPDATE users 
-                SET level = CASE 
-                    WHEN points >= ? THEN 'DIAMOND'
-                    WHEN points >= ? THEN 'GOLD'
-                    WHEN points >= ? THEN 'SILVER'
-                    ELSE 'BRONZE'
-                END
-                WHERE id = ?
-            "#)
-            .bind(s_diamond)  // Bind vào dấu ? thứ 1
-            .bind(s_gold)     // Bind vào dấu ? thứ 2
-            .bind(s_silver)   // Bind vào dấu ? thứ 3
-            .bind(&auth.user_id) // Bind vào dấu ? thứ 4 (WHERE id)
-            .execute(&mut *tx).await;
+            // 3.1 Cập nhật level theo điểm mới
+            update_user_level(&mut tx, &auth.user_id).await;
 
             // ------------------------------------------------
 
-            // 4. Commit & Gửi Mail Cảm Ơn
+            // 4. Mail cảm ơn vào outbox cùng transaction, rồi Commit
+            let lang = recipient_language(&mut *tx, &email).await;
+            if let Err(e) = send_order_thank_you_email(&mut *tx, email, lang, id, points).await {
+                println!("Lỗi ghi email cảm ơn: {:?}", e);
+                let _ = tx.rollback().await;
+                return (StatusCode::INTERNAL_SERVER_ERROR, Json("Transaction Error")).into_response();
+            }
+
             if tx.commit().await.is_ok() {
-                send_order_thank_you_email(email, id, points);
+                outbox::wake();
                 return (StatusCode::OK, Json("Đã xác nhận nhận hàng")).into_response();
             }
         } else {
@@ -291,9 +398,64 @@ async fn confirm_receipt(
     (StatusCode::NOT_FOUND, Json("Không tìm thấy đơn hàng")).into_response()
 }
 
+// --- HANDLER: Hóa đơn PDF (chủ đơn hoặc nhân viên có quyền orders.read) ---
+async fn get_invoice_pdf(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Path(id): Path<String>
+) -> impl IntoResponse {
+    let mut conn = match state.db.acquire().await {
+        Ok(conn) => conn,
+        Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, Json("Error DB")).into_response(),
+    };
+    let data = match invoice::load_invoice(&mut conn, &id).await {
+        Ok(Some(data)) => data,
+        Ok(None) => return (StatusCode::NOT_FOUND, Json("Không tìm thấy đơn hàng")).into_response(),
+        Err(e) => {
+            println!("Lỗi load_invoice: {:?}", e);
+            return (StatusCode::INTERNAL_SERVER_ERROR, Json("Error DB")).into_response();
+        }
+    };
+    drop(conn);
+
+    if data.user_id != auth.user_id {
+        let allowed = match rbac::role_permissions(&state, &auth.role).await {
+            Ok(perms) => rbac::has_permission(&perms, perm::OrdersRead::NAME),
+            Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, Json("Error DB")).into_response(),
+        };
+        // Không lộ việc đơn của người khác có tồn tại
+        if !allowed {
+            return (StatusCode::NOT_FOUND, Json("Không tìm thấy đơn hàng")).into_response();
+        }
+    }
+
+    let filename = data.filename();
+    let pdf = tokio::task::spawn_blocking(move || invoice::render_pdf(&data)).await;
+    match pdf {
+        Ok(Ok(bytes)) => (
+            StatusCode::OK,
+            [
+                (header::CONTENT_TYPE, "application/pdf".to_string()),
+                (header::CONTENT_DISPOSITION, format!("inline; filename=\"{}\"", filename)),
+                (header::CACHE_CONTROL, "private, no-store".to_string()),
+            ],
+            bytes,
+        ).into_response(),
+        Ok(Err(e)) => {
+            println!("Lỗi render hóa đơn {}: {}", id, e);
+            (StatusCode::INTERNAL_SERVER_ERROR, Json("Lỗi tạo hóa đơn")).into_response()
+        }
+        Err(e) => {
+            println!("Lỗi render hóa đơn {}: {:?}", id, e);
+            (StatusCode::INTERNAL_SERVER_ERROR, Json("Lỗi tạo hóa đơn")).into_response()
+        }
+    }
+}
+
 pub fn order_routes() -> Router<AppState> {
     Router::new()
         .route("/", post(create_order))
         .route("/my", get(get_my_orders))
         .route("/:id/receive", put(confirm_receipt))
+        .route("/:id/invoice.pdf", get(get_invoice_pdf))
 }
@@ -1,10 +1,15 @@
 use axum::{
     extract::{Path, State, Query},
-    Json, Router, routing::get,
+    Json, Router, routing::{get, post},
     http::StatusCode,
     response::IntoResponse,
 };
 use crate::AppState;
+use crate::routes::auth::OptionalAuthUser;
+use crate::utils::outbox;
+use crate::utils::suid;
+use crate::utils::image::StoredImage;
+use crate::utils::{ recipient_language, send_back_in_stock_email, send_price_drop_email, EmailError, Money };
 use serde::{Deserialize, Serialize};
 use sqlx::FromRow;
 use rust_decimal::Decimal; 
@@ -12,20 +17,14 @@ use serde_json::Value; // Import Value để xử lý JSON
 
 // --- STRUCTS ---
 
-#[derive(Debug, Serialize, FromRow)]
-pub struct Category {
-    pub id: String,
-    pub name: String,
-}
-
 #[derive(Debug, Serialize, FromRow)]
 pub struct Product {
     pub id: String,
     pub category_id: String,
     pub name: String,
-    pub price: Decimal,
+    pub price: Money,
     pub stock: i32,
-    pub images: Option<sqlx::types::Json<Vec<String>>>,
+    pub images: Option<sqlx::types::Json<Vec<StoredImage>>>, // url + width/height + variants (thumb/medium/large)
     pub description: Option<String>,
     
     // --- CÁC TRƯỜNG MỚI THÊM ---
@@ -38,6 +37,13 @@ pub struct Product {
     pub created_at: Option<chrono::NaiveDateTime>,
 }
 
+// Đăng ký nhận thông báo: "back_in_stock" hoặc "price_drop"
+#[derive(Debug, Deserialize)]
+pub struct WatchProductReq {
+    pub kind: String,
+    pub email: Option<String>, // Bắt buộc nếu chưa đăng nhập
+}
+
 #[derive(Debug, Deserialize)]
 pub struct ProductFilter {
     pub category_id: Option<String>,
@@ -95,9 +101,192 @@ async fn get_product_detail(
     }
 }
 
+// --- HANDLERS: WATCH (Báo có hàng / Báo giảm giá) ---
+
+async fn watch_product(
+    State(state): State<AppState>,
+    OptionalAuthUser(auth): OptionalAuthUser,
+    Path(id): Path<String>,
+    Json(payload): Json<WatchProductReq>
+) -> impl IntoResponse {
+    if payload.kind != "back_in_stock" && payload.kind != "price_drop" {
+        return (StatusCode::BAD_REQUEST, Json("Loại đăng ký không hợp lệ")).into_response();
+    }
+
+    // 1. Lấy user_id nếu đã đăng nhập (không bắt buộc)
+    let user_id: Option<String> = auth.map(|a| a.user_id);
+
+    // 2. Xác định email nhận thông báo: ưu tiên email trong body, nếu không có thì lấy email tài khoản
+    let email = match (payload.email, &user_id) {
+        (Some(e), _) if !e.trim().is_empty() => e.trim().to_string(),
+        (_, Some(uid)) => {
+            let row: Option<(String,)> = sqlx::query_as("SELECT email FROM users WHERE id = ?")
+                .bind(uid)
+                .fetch_optional(&state.db).await
+                .unwrap_or(None);
+            match row {
+                Some((e,)) => e,
+                None => return (StatusCode::UNAUTHORIZED, Json("User không tồn tại")).into_response(),
+            }
+        }
+        _ => return (StatusCode::BAD_REQUEST, Json("Vui lòng nhập email")).into_response(),
+    };
+
+    if email.parse::<lettre::Address>().is_err() {
+        return (StatusCode::BAD_REQUEST, Json("Email không hợp lệ")).into_response();
+    }
+
+    // 3. Kiểm tra sản phẩm tồn tại
+    let product: Option<(i32,)> = sqlx::query_as(
+        "SELECT stock FROM products WHERE id = ? AND (is_deleted = FALSE OR is_deleted IS NULL)"
+    )
+    .bind(&id)
+    .fetch_optional(&state.db).await
+    .unwrap_or(None);
+
+    match product {
+        None => return (StatusCode::NOT_FOUND, Json("Không tìm thấy sản phẩm")).into_response(),
+        Some((stock,)) if payload.kind == "back_in_stock" && stock > 0 => {
+            return (StatusCode::BAD_REQUEST, Json("Sản phẩm vẫn còn hàng")).into_response();
+        }
+        _ => {}
+    }
+
+    // 4. Tránh đăng ký trùng (cùng email, cùng loại, còn hiệu lực)
+    let existing: Option<(String,)> = sqlx::query_as(
+        "SELECT id FROM product_watches WHERE product_id = ? AND email = ? AND kind = ? AND status = 'active'"
+    )
+    .bind(&id)
+    .bind(&email)
+    .bind(&payload.kind)
+    .fetch_optional(&state.db).await
+    .unwrap_or(None);
+
+    if existing.is_some() {
+        return (StatusCode::OK, Json("Bạn đã đăng ký nhận thông báo")).into_response();
+    }
+
+    let res = sqlx::query(
+        "INSERT INTO product_watches (id, product_id, user_id, email, kind) VALUES (?, ?, ?, ?, ?)"
+    )
+    .bind(suid())
+    .bind(&id)
+    .bind(user_id)
+    .bind(&email)
+    .bind(&payload.kind)
+    .execute(&state.db).await;
+
+    match res {
+        Ok(_) => (StatusCode::CREATED, Json("Đăng ký nhận thông báo thành công")).into_response(),
+        Err(e) => {
+            println!("Lỗi watch_product: {:?}", e);
+            (StatusCode::INTERNAL_SERVER_ERROR, Json("Lỗi DB")).into_response()
+        }
+    }
+}
+
+/// Gửi mail cho người đăng ký khi sản phẩm có hàng trở lại hoặc giảm giá,
+/// mỗi đăng ký chỉ được thông báo 1 lần (chuyển sang fulfilled).
+///
+/// Gọi sau khi đã lưu thay đổi `stock`/`price` của sản phẩm.
+pub async fn notify_product_watchers(
+    db: &sqlx::MySqlPool,
+    product_id: &str,
+    old_stock: i32,
+    new_stock: i32,
+    old_price: Money,
+    new_price: Money,
+) {
+    let mut kinds = Vec::new();
+    if old_stock <= 0 && new_stock > 0 {
+        kinds.push("back_in_stock");
+    }
+    if new_price < old_price {
+        kinds.push("price_drop");
+    }
+    if kinds.is_empty() {
+        return;
+    }
+
+    let name: String = match sqlx::query_as::<_, (String,)>("SELECT name FROM products WHERE id = ?")
+        .bind(product_id)
+        .fetch_optional(db).await
+    {
+        Ok(Some((n,))) => n,
+        _ => return,
+    };
+
+    let change = WatchedChange { product_id, name: &name, old_price, new_price };
+    for kind in kinds {
+        let watchers: Vec<(String, String)> = sqlx::query_as(
+            "SELECT id, email FROM product_watches WHERE product_id = ? AND kind = ? AND status = 'active'"
+        )
+        .bind(product_id)
+        .bind(kind)
+        .fetch_all(db).await
+        .unwrap_or(vec![]);
+
+        for (watch_id, email) in watchers {
+            if let Err(e) = notify_watcher(db, &watch_id, kind, email, &change).await {
+                // Rollback -> đăng ký vẫn active, lần thay đổi sau thử lại
+                println!("Lỗi gửi thông báo theo dõi sản phẩm {}: {:?}", watch_id, e);
+            }
+        }
+    }
+    outbox::wake();
+}
+
+struct WatchedChange<'a> {
+    product_id: &'a str,
+    name: &'a str,
+    old_price: Money,
+    new_price: Money,
+}
+
+// Nhận đăng ký (active -> fulfilled) rồi mới ghi mail, cùng 1 transaction: 2 lần cập nhật
+// sản phẩm song song chỉ 1 bên nhận được, và ghi mail lỗi thì đăng ký quay lại active
+async fn notify_watcher(
+    db: &sqlx::MySqlPool,
+    watch_id: &str,
+    kind: &str,
+    email: String,
+    change: &WatchedChange<'_>,
+) -> Result<(), EmailError> {
+    let mut tx = db.begin().await?;
+
+    let claimed = sqlx::query(
+        "UPDATE product_watches SET status = 'fulfilled', fulfilled_at = NOW() WHERE id = ? AND status = 'active'"
+    )
+    .bind(watch_id)
+    .execute(&mut *tx).await?;
+    if claimed.rows_affected() != 1 {
+        tx.rollback().await?;
+        return Ok(());
+    }
+
+    let lang = recipient_language(&mut *tx, &email).await;
+    if kind == "back_in_stock" {
+        send_back_in_stock_email(&mut *tx, email, lang, change.product_id.to_string(), change.name.to_string()).await?;
+    } else {
+        send_price_drop_email(
+            &mut *tx,
+            email,
+            lang,
+            change.product_id.to_string(),
+            change.name.to_string(),
+            change.old_price,
+            change.new_price,
+        ).await?;
+    }
+
+    tx.commit().await?;
+    Ok(())
+}
+
 // --- ROUTER ---
 pub fn product_routes() -> Router<AppState> {
     Router::new()
         .route("/", get(get_products))
         .route("/:id", get(get_product_detail))
+        .route("/:id/watch", post(watch_product))
 }
\ No newline at end of file
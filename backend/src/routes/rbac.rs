@@ -0,0 +1,333 @@
+// src/routes/rbac.rs
+// Phân quyền chi tiết cho trang quản trị: mỗi role có một tập quyền lưu trong DB (bảng role_permissions)
+use axum::{
+    extract::{ State, FromRequestParts, Path, Json },
+    http::{ StatusCode, request::Parts },
+    response::IntoResponse,
+    Router,
+    routing::{ get, put },
+    async_trait,
+};
+use crate::AppState;
+use crate::routes::auth::{ AuthUser, ClientInfo };
+use crate::utils::audit::{ record_audit, AuditEntry };
+use serde::{ Deserialize, Serialize };
+use std::collections::{ HashMap, HashSet };
+use std::marker::PhantomData;
+use std::sync::RwLock;
+use std::time::{ Duration, Instant };
+use self::perm::ALL_PERMISSIONS;
+
+// Quyền đặc biệt: có toàn bộ quyền (role admin)
+pub const WILDCARD: &str = "*";
+
+const PERMISSION_CACHE_TTL: Duration = Duration::from_secs(30);
+
+// --- DANH SÁCH QUYỀN ---
+
+pub trait Permission {
+    const NAME: &'static str;
+}
+
+macro_rules! permissions {
+    ($($ty:ident => $name:literal),* $(,)?) => {
+        $(
+            pub struct $ty;
+            impl Permission for $ty {
+                const NAME: &'static str = $name;
+            }
+        )*
+
+        /// Toàn bộ quyền hệ thống biết (dùng để validate khi sửa quyền của role)
+        pub const ALL_PERMISSIONS: &[&str] = &[$($name),*];
+    };
+}
+
+pub mod perm {
+    use super::Permission;
+
+    permissions! {
+        OrdersRead => "orders.read",
+        OrdersUpdateStatus => "orders.update_status",
+        ProductsWrite => "products.write",
+        InventoryWrite => "inventory.write",
+        UsersRead => "users.read",
+        UsersWrite => "users.write",
+        UsersManageRoles => "users.manage_roles",
+        SettingsRead => "settings.read",
+        SettingsWrite => "settings.write",
+        AnalyticsRead => "analytics.read",
+        ContactsRead => "contacts.read",
+        ContactsWrite => "contacts.write",
+        ReviewsRead => "reviews.read",
+        ReviewsModerate => "reviews.moderate",
+        ReviewsReply => "reviews.reply",
+        RolesWrite => "roles.write",
+        AuditRead => "audit.read",
+        MediaRead => "media.read",
+        EmailsRead => "emails.read",
+        EmailsResend => "emails.resend",
+    }
+}
+
+// --- CACHE QUYỀN THEO ROLE ---
+
+struct CachedPermissions {
+    permissions: HashSet<String>,
+    cached_at: Instant,
+}
+
+#[derive(Default)]
+pub struct PermissionCache {
+    entries: RwLock<HashMap<String, CachedPermissions>>,
+}
+
+impl PermissionCache {
+    fn get(&self, role: &str) -> Option<HashSet<String>> {
+        let entries = self.entries.read().unwrap_or_else(|e| e.into_inner());
+        entries
+            .get(role)
+            .filter(|c| c.cached_at.elapsed() < PERMISSION_CACHE_TTL)
+            .map(|c| c.permissions.clone())
+    }
+
+    fn insert(&self, role: &str, permissions: HashSet<String>) {
+        let mut entries = self.entries.write().unwrap_or_else(|e| e.into_inner());
+        entries.insert(role.to_string(), CachedPermissions { permissions, cached_at: Instant::now() });
+    }
+
+    /// Xóa cache sau khi sửa quyền của một role
+    pub fn invalidate_role(&self, role: &str) {
+        let mut entries = self.entries.write().unwrap_or_else(|e| e.into_inner());
+        entries.remove(role);
+    }
+}
+
+pub async fn role_permissions(state: &AppState, role: &str) -> Result<HashSet<String>, sqlx::Error> {
+    if let Some(cached) = state.permissions.get(role) {
+        return Ok(cached);
+    }
+
+    let rows: Vec<(String,)> = sqlx
+        ::query_as("SELECT permission FROM role_permissions WHERE role = ?")
+        .bind(role)
+        .fetch_all(&state.db).await?;
+
+    let permissions: HashSet<String> = rows.into_iter().map(|(p,)| p).collect();
+    state.permissions.insert(role, permissions.clone());
+    Ok(permissions)
+}
+
+pub fn has_permission(permissions: &HashSet<String>, name: &str) -> bool {
+    permissions.contains(WILDCARD) || permissions.contains(name)
+}
+
+// --- EXTRACTOR ---
+
+/// Yêu cầu user đăng nhập có quyền `P`. VD: `admin: RequirePermission<perm::ProductsWrite>`
+pub struct RequirePermission<P: Permission> {
+    pub user_id: String,
+    pub role: String,
+    _perm: PhantomData<P>,
+}
+
+#[async_trait]
+impl<S, P> FromRequestParts<S> for RequirePermission<P>
+where
+    S: Send + Sync + Clone,
+    AppState: From<S>,
+    P: Permission + Send,
+{
+    type Rejection = (StatusCode, Json<serde_json::Value>);
+
+    async fn from_request_parts(parts: &mut Parts, state: &S) -> Result<Self, Self::Rejection> {
+        // AuthUser đã kiểm tra trạng thái khóa và lấy role mới nhất từ DB
+        let auth_user = AuthUser::from_request_parts(parts, state).await?;
+        let app_state = AppState::from(state.clone());
+
+        let permissions = role_permissions(&app_state, &auth_user.role).await.map_err(|e| {
+            println!("Lỗi tải quyền role {}: {:?}", auth_user.role, e);
+            (
+                StatusCode::INTERNAL_SERVER_ERROR,
+                Json(serde_json::json!({"code": "DB_ERROR", "message": "Lỗi DB"})),
+            )
+        })?;
+
+        if has_permission(&permissions, P::NAME) {
+            Ok(RequirePermission { user_id: auth_user.user_id, role: auth_user.role, _perm: PhantomData })
+        } else {
+            Err((
+                StatusCode::FORBIDDEN,
+                Json(serde_json::json!({
+                    "code": "FORBIDDEN",
+                    "message": "Không có quyền thực hiện thao tác này",
+                    "permission": P::NAME,
+                })),
+            ))
+        }
+    }
+}
+
+// --- HANDLERS: ROLES ---
+
+#[derive(Debug, Serialize)]
+pub struct RoleItem {
+    pub name: String,
+    pub description: Option<String>,
+    pub permissions: Vec<String>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateRolePermissionsReq {
+    pub permissions: Vec<String>,
+}
+
+// Quyền của user hiện tại (frontend dùng để ẩn/hiện tab quản trị)
+async fn my_permissions(State(state): State<AppState>, auth: AuthUser) -> impl IntoResponse {
+    match role_permissions(&state, &auth.role).await {
+        Ok(permissions) => {
+            let mut list: Vec<String> = if permissions.contains(WILDCARD) {
+                ALL_PERMISSIONS.iter().map(|p| p.to_string()).collect()
+            } else {
+                permissions.into_iter().collect()
+            };
+            list.sort();
+            (StatusCode::OK, Json(serde_json::json!({ "role": auth.role, "permissions": list }))).into_response()
+        }
+        Err(_) => (StatusCode::INTERNAL_SERVER_ERROR, Json("Error")).into_response(),
+    }
+}
+
+async fn get_roles(
+    State(state): State<AppState>,
+    _: RequirePermission<perm::UsersRead>
+) -> impl IntoResponse {
+    let roles = sqlx
+        ::query_as::<_, (String, Option<String>)>("SELECT name, description FROM roles ORDER BY name")
+        .fetch_all(&state.db).await;
+    let perms = sqlx
+        ::query_as::<_, (String, String)>("SELECT role, permission FROM role_permissions ORDER BY permission")
+        .fetch_all(&state.db).await;
+
+    match (roles, perms) {
+        (Ok(roles), Ok(perms)) => {
+            let data: Vec<RoleItem> = roles
+                .into_iter()
+                .map(|(name, description)| {
+                    let permissions = perms
+                        .iter()
+                        .filter(|(role, _)| *role == name)
+                        .map(|(_, p)| p.clone())
+                        .collect();
+                    RoleItem { name, description, permissions }
+                })
+                .collect();
+            (StatusCode::OK, Json(serde_json::json!({ "roles": data, "available": ALL_PERMISSIONS }))).into_response()
+        }
+        _ => (StatusCode::INTERNAL_SERVER_ERROR, Json("Error")).into_response(),
+    }
+}
+
+// Thay toàn bộ tập quyền của một role
+async fn update_role_permissions(
+    State(state): State<AppState>,
+    admin: RequirePermission<perm::RolesWrite>,
+    client: ClientInfo,
+    Path(name): Path<String>,
+    Json(payload): Json<UpdateRolePermissionsReq>
+) -> impl IntoResponse {
+    // Role admin luôn giữ toàn quyền để không tự khóa mình khỏi hệ thống
+    if name == "admin" {
+        return (StatusCode::BAD_REQUEST, Json("Không thể sửa quyền của role admin")).into_response();
+    }
+    let mut permissions: Vec<String> = payload.permissions;
+    permissions.sort();
+    permissions.dedup();
+    if let Some(unknown) = permissions.iter().find(|p| !ALL_PERMISSIONS.contains(&p.as_str())) {
+        return (StatusCode::BAD_REQUEST, Json(format!("Quyền không hợp lệ: {}", unknown))).into_response();
+    }
+    // Tự nâng quyền cho role của mình (admin đã bị chặn ở trên)
+    if name == admin.role {
+        return (StatusCode::FORBIDDEN, Json("Không thể sửa quyền của role bạn đang giữ")).into_response();
+    }
+    // Chỉ được cấp quyền mình đang có, và không sửa role có quyền vượt quá mình
+    let actor_perms = match role_permissions(&state, &admin.role).await {
+        Ok(p) => p,
+        Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, Json("Error")).into_response(),
+    };
+    if let Some(p) = permissions.iter().find(|p| !has_permission(&actor_perms, p)) {
+        return (StatusCode::FORBIDDEN, Json(format!("Không thể cấp quyền bạn không có: {}", p))).into_response();
+    }
+    let current_perms = match role_permissions(&state, &name).await {
+        Ok(p) => p,
+        Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, Json("Error")).into_response(),
+    };
+    if current_perms.iter().any(|p| !has_permission(&actor_perms, p)) {
+        return (StatusCode::FORBIDDEN, Json("Không thể sửa role có quyền cao hơn quyền của bạn")).into_response();
+    }
+
+    let mut tx = match state.db.begin().await {
+        Ok(tx) => tx,
+        Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, Json("Error")).into_response(),
+    };
+
+    let exists: Option<(String,)> = sqlx
+        ::query_as("SELECT name FROM roles WHERE name = ?")
+        .bind(&name)
+        .fetch_optional(&mut *tx).await
+        .unwrap_or(None);
+    if exists.is_none() {
+        return (StatusCode::NOT_FOUND, Json("Không tìm thấy role")).into_response();
+    }
+
+    let before: Vec<(String,)> = sqlx
+        ::query_as("SELECT permission FROM role_permissions WHERE role = ? ORDER BY permission")
+        .bind(&name)
+        .fetch_all(&mut *tx).await
+        .unwrap_or_default();
+
+    if sqlx::query("DELETE FROM role_permissions WHERE role = ?").bind(&name).execute(&mut *tx).await.is_err() {
+        let _ = tx.rollback().await;
+        return (StatusCode::INTERNAL_SERVER_ERROR, Json("Error DB")).into_response();
+    }
+    for permission in &permissions {
+        let res = sqlx
+            ::query("INSERT INTO role_permissions (role, permission) VALUES (?, ?)")
+            .bind(&name)
+            .bind(permission)
+            .execute(&mut *tx).await;
+        if res.is_err() {
+            let _ = tx.rollback().await;
+            return (StatusCode::INTERNAL_SERVER_ERROR, Json("Error DB")).into_response();
+        }
+    }
+
+    let audit = record_audit(&mut *tx, AuditEntry {
+        actor_id: &admin.user_id,
+        action: "roles.update_permissions",
+        target_type: "role",
+        target_id: &name,
+        before: Some(serde_json::json!(before.into_iter().map(|(p,)| p).collect::<Vec<_>>())),
+        after: Some(serde_json::json!(permissions)),
+        reason: None,
+        ip: client.ip.as_deref(),
+    }).await;
+    if audit.is_err() {
+        let _ = tx.rollback().await;
+        return (StatusCode::INTERNAL_SERVER_ERROR, Json("Lỗi ghi audit log")).into_response();
+    }
+
+    if tx.commit().await.is_err() {
+        return (StatusCode::INTERNAL_SERVER_ERROR, Json("Error")).into_response();
+    }
+    state.permissions.invalidate_role(&name);
+
+    (StatusCode::OK, Json(serde_json::json!({ "name": name, "permissions": permissions }))).into_response()
+}
+
+pub fn rbac_routes() -> Router<AppState> {
+    Router::new()
+        .route("/permissions/me", get(my_permissions))
+        .route("/roles", get(get_roles))
+        .route("/roles/:name/permissions", put(update_role_permissions))
+}
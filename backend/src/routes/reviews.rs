@@ -1,24 +1,35 @@
 use axum::{
-    extract::{Path, State, Json},
+    extract::{Path, State, Json, Query},
     Router, routing::{get, post},
     http::StatusCode,
     response::IntoResponse,
 };
 use crate::AppState;
 use crate::routes::auth::AuthUser; // Cần đăng nhập mới được review
+use crate::routes::upload::is_uploaded_file;
+use crate::storage::BlobStore;
+use crate::utils::media::{ self, REF_REVIEW };
 use crate::utils::suid; // Hàm sinh ID
 use serde::{Deserialize, Serialize};
-use sqlx::FromRow;
+use sqlx::{FromRow, MySql, Transaction};
 
 // --- MODELS ---
 
 #[derive(Debug, Serialize, FromRow)]
 pub struct ReviewItem {
     pub id: String,
+    pub user_id: String,
     pub user_name: String, // Join bảng users để lấy tên
     pub rating: i32,
     pub content: Option<String>,
+    pub verified_purchase: bool, // Đã mua (đơn completed) sản phẩm này
+    pub status: String,          // pending | approved | rejected | hidden
+    pub images: Option<sqlx::types::Json<Vec<String>>>, // Ảnh đính kèm (URL public do storage sinh ra)
+    pub helpful_count: i32,
+    pub reply_content: Option<String>, // Phản hồi công khai của shop
+    pub replied_at: Option<chrono::NaiveDateTime>,
     pub created_at: chrono::NaiveDateTime,
+    pub updated_at: Option<chrono::NaiveDateTime>,
 }
 
 #[derive(Debug, Deserialize)]
@@ -26,25 +37,171 @@ pub struct CreateReviewReq {
     pub product_id: String,
     pub rating: i32,
     pub content: String,
+    pub images: Option<Vec<String>>, // Upload qua /api/upload trước, gửi lên URL trả về
+}
+
+#[derive(Debug, Deserialize)]
+pub struct UpdateReviewReq {
+    pub rating: i32,
+    pub content: String,
+    pub images: Option<Vec<String>>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ReviewQuery {
+    pub page: Option<i64>,
+    pub limit: Option<i64>,
+    pub sort: Option<String>, // newest | highest | lowest | helpful
+}
+
+#[derive(Debug, Serialize)]
+pub struct RatingHistogram {
+    pub average: f64,
+    pub total: i64,
+    pub counts: [i64; 5], // counts[0] = số đánh giá 1 sao, ..., counts[4] = 5 sao
+}
+
+const MAX_REVIEW_IMAGES: usize = 5;
+
+// --- HELPERS ---
+
+// Ảnh đánh giá phải là file đã upload qua hệ thống upload, tối đa MAX_REVIEW_IMAGES ảnh
+async fn validate_images(store: &dyn BlobStore, images: &Option<Vec<String>>) -> Result<Vec<String>, &'static str> {
+    let images = images.clone().unwrap_or_default();
+    if images.len() > MAX_REVIEW_IMAGES {
+        return Err("Tối đa 5 ảnh cho mỗi đánh giá");
+    }
+    for url in &images {
+        if !is_uploaded_file(store, url).await {
+            return Err("Ảnh không hợp lệ");
+        }
+    }
+    Ok(images)
+}
+
+// Kiểm tra user đã mua sản phẩm trong một đơn đã hoàn thành chưa
+async fn has_purchased(
+    tx: &mut Transaction<'_, MySql>,
+    user_id: &str,
+    product_id: &str
+) -> bool {
+    let count: (i64,) = sqlx::query_as(
+        "SELECT COUNT(*)
+         FROM order_items oi
+         JOIN orders o ON oi.order_id = o.id
+         WHERE o.user_id = ? AND oi.product_id = ? AND o.status = 'completed'"
+    )
+    .bind(user_id)
+    .bind(product_id)
+    .fetch_one(&mut **tx).await
+    .unwrap_or((0,));
+
+    count.0 > 0
+}
+
+// Trạng thái kiểm duyệt cho đánh giá mới/vừa sửa:
+// - Chứa từ cấm (settings.review_banned_words, phân tách bằng dấu phẩy) -> giữ lại chờ duyệt
+// - settings.review_require_approval = "true" -> mọi đánh giá đều chờ duyệt
+// - Lỗi đọc cấu hình -> chờ duyệt (không tự duyệt khi không kiểm tra được)
+// - Còn lại -> duyệt tự động
+async fn moderation_status(
+    tx: &mut Transaction<'_, MySql>,
+    content: &str
+) -> &'static str {
+    let settings_rows: Vec<(String, Option<String>)> = match sqlx::query_as(
+        "SELECT id, value FROM settings WHERE id IN ('review_banned_words', 'review_require_approval')"
+    )
+    .fetch_all(&mut **tx).await {
+        Ok(rows) => rows,
+        Err(e) => {
+            println!("Lỗi đọc cấu hình kiểm duyệt: {:?}", e);
+            return "pending";
+        }
+    };
+
+    let mut banned_words: Vec<String> = vec![];
+    let mut require_approval = false;
+
+    for (id, value_opt) in settings_rows {
+        let value = value_opt.unwrap_or_default();
+        match id.as_str() {
+            "review_banned_words" => {
+                banned_words = value
+                    .split(',')
+                    .map(|w| w.trim().to_lowercase())
+                    .filter(|w| !w.is_empty())
+                    .collect();
+            }
+            "review_require_approval" => require_approval = value.trim() == "true",
+            _ => {}
+        }
+    }
+
+    let content_lower = content.to_lowercase();
+    if require_approval || banned_words.iter().any(|w| content_lower.contains(w.as_str())) {
+        "pending"
+    } else {
+        "approved"
+    }
+}
+
+// Tính lại Rating trung bình và Count cho Product (chỉ tính đánh giá đã duyệt)
+// Dùng COALESCE để khi xóa hết đánh giá thì rating về 0
+pub(crate) async fn recompute_product_rating(
+    tx: &mut Transaction<'_, MySql>,
+    product_id: &str
+) -> Result<(), sqlx::Error> {
+    let (avg_rating, count): (Option<sqlx::types::Decimal>, i64) = sqlx::query_as(
+        "SELECT COALESCE(AVG(rating), 0), COUNT(*) FROM reviews WHERE product_id = ? AND status = 'approved'"
+    )
+    .bind(product_id)
+    .fetch_one(&mut **tx).await?;
+
+    sqlx::query("UPDATE products SET rating = ?, review_count = ? WHERE id = ?")
+        .bind(avg_rating)
+        .bind(count)
+        .bind(product_id)
+        .execute(&mut **tx).await?;
+
+    Ok(())
 }
 
 // --- HANDLERS ---
 
-// 1. Lấy danh sách đánh giá của 1 sản phẩm
+// 1. Lấy danh sách đánh giá đã duyệt của 1 sản phẩm (phân trang + sắp xếp)
+// GET /api/reviews/:product_id?page=1&limit=10&sort=newest|highest|lowest|helpful
 async fn get_reviews(
     State(state): State<AppState>,
-    Path(product_id): Path<String>
+    Path(product_id): Path<String>,
+    Query(query): Query<ReviewQuery>
 ) -> impl IntoResponse {
-    let sql = "
-        SELECT r.id, u.name as user_name, r.rating, r.content, r.created_at
+    let limit = query.limit.unwrap_or(10).clamp(1, 50);
+    let page = query.page.unwrap_or(1).max(1);
+
+    // Chỉ nhận các giá trị cố định nên ghép chuỗi an toàn
+    let order_by = match query.sort.as_deref() {
+        Some("highest") => "r.rating DESC, r.created_at DESC",
+        Some("lowest") => "r.rating ASC, r.created_at DESC",
+        Some("helpful") => "r.helpful_count DESC, r.created_at DESC",
+        _ => "r.created_at DESC",
+    };
+
+    let sql = format!("
+        SELECT r.id, r.user_id, u.name as user_name, r.rating, r.content,
+               r.verified_purchase, r.status, r.images, r.helpful_count, r.created_at, r.updated_at,
+               rr.content as reply_content, rr.created_at as replied_at
         FROM reviews r
         JOIN users u ON r.user_id = u.id
-        WHERE r.product_id = ?
-        ORDER BY r.created_at DESC
-    ";
+        LEFT JOIN review_replies rr ON rr.review_id = r.id
+        WHERE r.product_id = ? AND r.status = 'approved'
+        ORDER BY {}
+        LIMIT ? OFFSET ?
+    ", order_by);
 
-    let reviews = sqlx::query_as::<_, ReviewItem>(sql)
+    let reviews = sqlx::query_as::<_, ReviewItem>(&sql)
         .bind(product_id)
+        .bind(limit)
+        .bind((page - 1) * limit)
         .fetch_all(&state.db)
         .await;
 
@@ -54,7 +211,7 @@ async fn get_reviews(
     }
 }
 
-// 2. Gửi đánh giá mới
+// 2. Gửi đánh giá mới (mỗi user chỉ được đánh giá 1 lần / sản phẩm)
 async fn create_review(
     State(state): State<AppState>,
     auth: AuthUser, // Bắt buộc đăng nhập
@@ -64,63 +221,293 @@ async fn create_review(
     if payload.rating < 1 || payload.rating > 5 {
         return (StatusCode::BAD_REQUEST, Json("Điểm đánh giá từ 1-5")).into_response();
     }
+    let images = match validate_images(state.storage.as_ref(), &payload.images).await {
+        Ok(images) => images,
+        Err(msg) => return (StatusCode::BAD_REQUEST, Json(msg)).into_response(),
+    };
 
     let mut tx = match state.db.begin().await {
         Ok(tx) => tx,
         Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, Json("Lỗi transaction")).into_response(),
     };
 
-    // 2.2 Insert Review
+    // 2.2 Insert Review. Đã đánh giá rồi thì phải sửa chứ không tạo mới:
+    // dựa vào UNIQUE (user_id, product_id) thay vì SELECT trước để 2 request song song không lọt
+    let verified = has_purchased(&mut tx, &auth.user_id, &payload.product_id).await;
+    let status = moderation_status(&mut tx, &payload.content).await;
     let review_id = suid();
     let insert_res = sqlx::query(
-        "INSERT INTO reviews (id, user_id, product_id, rating, content) VALUES (?, ?, ?, ?, ?)"
+        "INSERT INTO reviews (id, user_id, product_id, rating, content, verified_purchase, status, images) VALUES (?, ?, ?, ?, ?, ?, ?, ?)"
     )
-    .bind(review_id)
-    .bind(auth.user_id)
+    .bind(&review_id)
+    .bind(&auth.user_id)
     .bind(&payload.product_id)
     .bind(payload.rating)
     .bind(&payload.content)
+    .bind(verified)
+    .bind(status)
+    .bind(sqlx::types::Json(&images))
     .execute(&mut *tx).await;
 
-    if insert_res.is_err() {
+    if let Err(sqlx::Error::Database(e)) = &insert_res {
+        // MySQL 1062 (ER_DUP_ENTRY)
+        if e.is_unique_violation() {
+            let _ = tx.rollback().await;
+            return (StatusCode::CONFLICT, Json("Bạn đã đánh giá sản phẩm này")).into_response();
+        }
+    }
+    if insert_res.is_err() || media::sync_refs(&mut tx, REF_REVIEW, &review_id, &images).await.is_err() {
         let _ = tx.rollback().await;
         return (StatusCode::INTERNAL_SERVER_ERROR, Json("Lỗi lưu đánh giá")).into_response();
     }
 
-    // 2.3 Tính toán lại Rating trung bình và Count cho Product
-    // Logic: Tính AVG và COUNT từ bảng reviews cho product_id này
-    let stats: Option<(sqlx::types::Decimal, i64)> = sqlx::query_as(
-        "SELECT AVG(rating), COUNT(*) FROM reviews WHERE product_id = ?"
+    // 2.3 Cập nhật bảng Products
+    if recompute_product_rating(&mut tx, &payload.product_id).await.is_err() {
+        let _ = tx.rollback().await;
+        return (StatusCode::INTERNAL_SERVER_ERROR, Json("Lỗi cập nhật sản phẩm")).into_response();
+    }
+
+    // 2.4 Commit
+    if tx.commit().await.is_err() {
+        return (StatusCode::INTERNAL_SERVER_ERROR, Json("Lỗi commit")).into_response();
+    }
+
+    let message = if status == "approved" { "Đánh giá thành công" } else { "Đánh giá đang chờ duyệt" };
+    (StatusCode::CREATED, Json(serde_json::json!({
+        "message": message,
+        "id": review_id,
+        "verified_purchase": verified,
+        "status": status
+    }))).into_response()
+}
+
+// 3. Sửa đánh giá (chỉ tác giả)
+async fn update_review(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Path(id): Path<String>,
+    Json(payload): Json<UpdateReviewReq>
+) -> impl IntoResponse {
+    if payload.rating < 1 || payload.rating > 5 {
+        return (StatusCode::BAD_REQUEST, Json("Điểm đánh giá từ 1-5")).into_response();
+    }
+    let images = match validate_images(state.storage.as_ref(), &payload.images).await {
+        Ok(images) => images,
+        Err(msg) => return (StatusCode::BAD_REQUEST, Json(msg)).into_response(),
+    };
+
+    let mut tx = match state.db.begin().await {
+        Ok(tx) => tx,
+        Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, Json("Lỗi transaction")).into_response(),
+    };
+
+    let review: Option<(String, String, String)> = sqlx::query_as(
+        "SELECT user_id, product_id, status FROM reviews WHERE id = ?"
     )
-    .bind(&payload.product_id)
+    .bind(&id)
     .fetch_optional(&mut *tx).await.unwrap_or(None);
 
-    if let Some((avg_rating, count)) = stats {
-        // 2.4 Update bảng Products
-        let update_res = sqlx::query(
-            "UPDATE products SET rating = ?, review_count = ? WHERE id = ?"
-        )
-        .bind(avg_rating)
-        .bind(count)
-        .bind(&payload.product_id)
-        .execute(&mut *tx).await;
+    let (product_id, old_status) = match review {
+        Some((user_id, product_id, status)) if user_id == auth.user_id => (product_id, status),
+        Some(_) => return (StatusCode::FORBIDDEN, Json("Không có quyền sửa đánh giá này")).into_response(),
+        None => return (StatusCode::NOT_FOUND, Json("Không tìm thấy đánh giá")).into_response(),
+    };
 
-        if update_res.is_err() {
-            let _ = tx.rollback().await;
-            return (StatusCode::INTERNAL_SERVER_ERROR, Json("Lỗi cập nhật sản phẩm")).into_response();
-        }
+    // Đánh giá đã bị từ chối/ẩn thì giữ nguyên trạng thái, còn lại kiểm duyệt lại nội dung mới
+    let status = match old_status.as_str() {
+        "rejected" | "hidden" => old_status.as_str(),
+        _ => moderation_status(&mut tx, &payload.content).await,
+    };
+
+    // Tính lại cờ đã mua (có thể user mua sau khi đánh giá)
+    let verified = has_purchased(&mut tx, &auth.user_id, &product_id).await;
+    let update_res = sqlx::query(
+        "UPDATE reviews SET rating = ?, content = ?, verified_purchase = ?, status = ?, images = ?, updated_at = NOW() WHERE id = ?"
+    )
+    .bind(payload.rating)
+    .bind(&payload.content)
+    .bind(verified)
+    .bind(status)
+    .bind(sqlx::types::Json(&images))
+    .bind(&id)
+    .execute(&mut *tx).await;
+
+    if update_res.is_err() || media::sync_refs(&mut tx, REF_REVIEW, &id, &images).await.is_err() {
+        let _ = tx.rollback().await;
+        return (StatusCode::INTERNAL_SERVER_ERROR, Json("Lỗi lưu đánh giá")).into_response();
+    }
+
+    if recompute_product_rating(&mut tx, &product_id).await.is_err() {
+        let _ = tx.rollback().await;
+        return (StatusCode::INTERNAL_SERVER_ERROR, Json("Lỗi cập nhật sản phẩm")).into_response();
     }
 
-    // 2.5 Commit
     if tx.commit().await.is_err() {
         return (StatusCode::INTERNAL_SERVER_ERROR, Json("Lỗi commit")).into_response();
     }
 
-    (StatusCode::CREATED, Json("Đánh giá thành công")).into_response()
+    (StatusCode::OK, Json(serde_json::json!({
+        "message": "Đã cập nhật đánh giá",
+        "status": status
+    }))).into_response()
+}
+
+// 4. Xóa đánh giá (chỉ tác giả)
+async fn delete_review(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Path(id): Path<String>
+) -> impl IntoResponse {
+    let mut tx = match state.db.begin().await {
+        Ok(tx) => tx,
+        Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, Json("Lỗi transaction")).into_response(),
+    };
+
+    let review: Option<(String, String)> = sqlx::query_as(
+        "SELECT user_id, product_id FROM reviews WHERE id = ?"
+    )
+    .bind(&id)
+    .fetch_optional(&mut *tx).await.unwrap_or(None);
+
+    let product_id = match review {
+        Some((user_id, product_id)) if user_id == auth.user_id => product_id,
+        Some(_) => return (StatusCode::FORBIDDEN, Json("Không có quyền xóa đánh giá này")).into_response(),
+        None => return (StatusCode::NOT_FOUND, Json("Không tìm thấy đánh giá")).into_response(),
+    };
+
+    if sqlx::query("DELETE FROM review_votes WHERE review_id = ?").bind(&id).execute(&mut *tx).await.is_err()
+        || sqlx::query("DELETE FROM review_replies WHERE review_id = ?").bind(&id).execute(&mut *tx).await.is_err()
+        || sqlx::query("DELETE FROM reviews WHERE id = ?").bind(&id).execute(&mut *tx).await.is_err()
+        || media::sync_refs(&mut tx, REF_REVIEW, &id, &[]).await.is_err()
+    {
+        let _ = tx.rollback().await;
+        return (StatusCode::INTERNAL_SERVER_ERROR, Json("Lỗi xóa đánh giá")).into_response();
+    }
+
+    if recompute_product_rating(&mut tx, &product_id).await.is_err() {
+        let _ = tx.rollback().await;
+        return (StatusCode::INTERNAL_SERVER_ERROR, Json("Lỗi cập nhật sản phẩm")).into_response();
+    }
+
+    if tx.commit().await.is_err() {
+        return (StatusCode::INTERNAL_SERVER_ERROR, Json("Lỗi commit")).into_response();
+    }
+
+    (StatusCode::OK, Json("Đã xóa đánh giá")).into_response()
+}
+
+// 5. Bình chọn "hữu ích" (mỗi user 1 phiếu / đánh giá)
+async fn vote_helpful(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Path(id): Path<String>
+) -> impl IntoResponse {
+    let review: Option<(String,)> = sqlx::query_as(
+        "SELECT user_id FROM reviews WHERE id = ? AND status = 'approved'"
+    )
+    .bind(&id)
+    .fetch_optional(&state.db).await.unwrap_or(None);
+
+    match review {
+        None => return (StatusCode::NOT_FOUND, Json("Không tìm thấy đánh giá")).into_response(),
+        Some((user_id,)) if user_id == auth.user_id => {
+            return (StatusCode::BAD_REQUEST, Json("Không thể bình chọn đánh giá của chính mình")).into_response();
+        }
+        _ => {}
+    }
+
+    set_helpful_vote(&state.db, &id, &auth.user_id, true).await
+}
+
+// 6. Bỏ bình chọn "hữu ích"
+async fn unvote_helpful(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    Path(id): Path<String>
+) -> impl IntoResponse {
+    set_helpful_vote(&state.db, &id, &auth.user_id, false).await
+}
+
+async fn set_helpful_vote(
+    db: &sqlx::MySqlPool,
+    review_id: &str,
+    user_id: &str,
+    helpful: bool
+) -> axum::response::Response {
+    let mut tx = match db.begin().await {
+        Ok(tx) => tx,
+        Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, Json("Lỗi transaction")).into_response(),
+    };
+
+    let res = if helpful {
+        sqlx::query("INSERT IGNORE INTO review_votes (review_id, user_id) VALUES (?, ?)")
+    } else {
+        sqlx::query("DELETE FROM review_votes WHERE review_id = ? AND user_id = ?")
+    }
+    .bind(review_id)
+    .bind(user_id)
+    .execute(&mut *tx).await;
+
+    // Chỉ cập nhật bộ đếm khi phiếu thực sự thay đổi
+    if let Ok(r) = &res {
+        if r.rows_affected() > 0 {
+            let _ = sqlx::query(
+                "UPDATE reviews SET helpful_count = (SELECT COUNT(*) FROM review_votes WHERE review_id = ?) WHERE id = ?"
+            )
+            .bind(review_id)
+            .bind(review_id)
+            .execute(&mut *tx).await;
+        }
+    }
+
+    if res.is_err() || tx.commit().await.is_err() {
+        return (StatusCode::INTERNAL_SERVER_ERROR, Json("Lỗi bình chọn")).into_response();
+    }
+
+    let count: (i32,) = sqlx::query_as("SELECT helpful_count FROM reviews WHERE id = ?")
+        .bind(review_id)
+        .fetch_one(db).await
+        .unwrap_or((0,));
+
+    (StatusCode::OK, Json(serde_json::json!({ "helpful_count": count.0, "voted": helpful }))).into_response()
+}
+
+// 7. Thống kê số sao (1-5) cho trang sản phẩm
+async fn get_rating_histogram(
+    State(state): State<AppState>,
+    Path(product_id): Path<String>
+) -> impl IntoResponse {
+    let rows: Result<Vec<(i32, i64)>, _> = sqlx::query_as(
+        "SELECT rating, COUNT(*) FROM reviews WHERE product_id = ? AND status = 'approved' GROUP BY rating"
+    )
+    .bind(product_id)
+    .fetch_all(&state.db).await;
+
+    let rows = match rows {
+        Ok(rows) => rows,
+        Err(_) => return (StatusCode::INTERNAL_SERVER_ERROR, Json("Lỗi lấy thống kê")).into_response(),
+    };
+
+    let mut counts = [0i64; 5];
+    for (rating, count) in rows {
+        if (1..=5).contains(&rating) {
+            counts[(rating - 1) as usize] = count;
+        }
+    }
+
+    let total: i64 = counts.iter().sum();
+    let sum: i64 = counts.iter().enumerate().map(|(i, c)| (i as i64 + 1) * c).sum();
+    let average = if total > 0 { sum as f64 / total as f64 } else { 0.0 };
+
+    (StatusCode::OK, Json(RatingHistogram { average, total, counts })).into_response()
 }
 
 pub fn review_routes() -> Router<AppState> {
     Router::new()
-        .route("/:product_id", get(get_reviews)) // GET /api/reviews/:product_id
+        // GET /api/reviews/:product_id, PUT|DELETE /api/reviews/:review_id
+        // (axum không cho 2 route cùng vị trí mà khác tên tham số)
+        .route("/:id", get(get_reviews).put(update_review).delete(delete_review))
+        .route("/:id/histogram", get(get_rating_histogram))         // :product_id
+        .route("/:id/helpful", post(vote_helpful).delete(unvote_helpful)) // :review_id
         .route("/", post(create_review))         // POST /api/reviews
-}
\ No newline at end of file
+}
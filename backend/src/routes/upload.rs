@@ -1,5 +1,5 @@
 use axum::{
-    extract::Multipart,
+    extract::{ DefaultBodyLimit, Multipart, State },
     routing::post,
     Router,
     Json,
@@ -7,39 +7,312 @@ use axum::{
     response::IntoResponse,
 };
 use crate::AppState;
-use std::path::Path;
-use tokio::fs; // Dùng tokio fs cho async
+use crate::routes::auth::AuthUser;
+use serde::Serialize;
+use crate::storage::BlobStore;
 use crate::utils::suid;
+use crate::utils::image::{ process_image, ImageError, ImageVariant, ImageVariants, StoredImage };
+use crate::utils::media::{ self, NewMedia };
 
-async fn upload_images(mut multipart: Multipart) -> impl IntoResponse {
-    let mut uploaded_files = Vec::new();
+const MB: usize = 1024 * 1024;
 
-    while let Some(field) = multipart.next_field().await.unwrap() {
-        let name = field.name().unwrap().to_string();
-        
-        if name == "files" {
-            let file_name = field.file_name().unwrap().to_string();
-            let data = field.bytes().await.unwrap();
+// Giới hạn theo role: khách (ảnh đánh giá) ít hơn nhân viên (ảnh sản phẩm)
+struct UploadLimits {
+    max_files: usize,
+    max_file_size: usize,
+}
+
+const CUSTOMER_LIMITS: UploadLimits = UploadLimits { max_files: 5, max_file_size: 5 * MB };
+const STAFF_LIMITS: UploadLimits = UploadLimits { max_files: 10, max_file_size: 8 * MB };
+
+// Chặn body quá lớn ngay từ tầng HTTP (lớn nhất trong các role + phần header multipart)
+const MAX_BODY_SIZE: usize = STAFF_LIMITS.max_files * STAFF_LIMITS.max_file_size + MB;
+
+fn limits_for(role: &str) -> &'static UploadLimits {
+    match role {
+        "user" => &CUSTOMER_LIMITS,
+        _ => &STAFF_LIMITS,
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct UploadedFile {
+    pub file_name: String, // Tên gốc phía client
+    pub media_id: String,
+    #[serde(flatten)]
+    pub image: StoredImage, // url (bản large), width, height, variants
+    pub content_type: &'static str, // Định dạng gốc (đã sniff)
+}
+
+#[derive(Debug, Serialize)]
+pub struct UploadError {
+    pub file_name: String,
+    pub code: &'static str, // TOO_MANY_FILES | FILE_TOO_LARGE | UNSUPPORTED_TYPE | EMPTY_FILE | INVALID_IMAGE | READ_ERROR | WRITE_ERROR
+    pub message: String,
+}
+
+/// Nhận dạng định dạng ảnh qua magic bytes (không tin Content-Type / đuôi file của client)
+fn sniff_image(data: &[u8]) -> Option<(&'static str, &'static str)> {
+    if data.starts_with(&[0xFF, 0xD8, 0xFF]) {
+        Some(("image/jpeg", "jpg"))
+    } else if data.starts_with(&[0x89, b'P', b'N', b'G', 0x0D, 0x0A, 0x1A, 0x0A]) {
+        Some(("image/png", "png"))
+    } else if data.starts_with(b"GIF87a") || data.starts_with(b"GIF89a") {
+        Some(("image/gif", "gif"))
+    } else if data.len() >= 12 && &data[0..4] == b"RIFF" && &data[8..12] == b"WEBP" {
+        Some(("image/webp", "webp"))
+    } else {
+        None
+    }
+}
+
+/// Chỉ giữ chữ/số ASCII, '-' và '_' của tên gốc (bỏ đuôi, đường dẫn), tối đa 40 ký tự
+fn sanitize_file_stem(file_name: &str) -> String {
+    let base = file_name.rsplit(['/', '\\']).next().unwrap_or("");
+    let stem = base.rsplit_once('.').map(|(s, _)| s).unwrap_or(base);
+    let cleaned: String = stem
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c.to_ascii_lowercase() } else { '-' })
+        .collect();
+    let trimmed = cleaned.trim_matches('-');
+    trimmed.chars().take(40).collect()
+}
+
+async fn upload_images(
+    State(state): State<AppState>,
+    auth: AuthUser,
+    mut multipart: Multipart
+) -> impl IntoResponse {
+    let limits = limits_for(&auth.role);
+    let mut uploaded_files: Vec<UploadedFile> = Vec::new();
+    let mut errors: Vec<UploadError> = Vec::new();
+    let mut file_count = 0;
+
+    loop {
+        let mut field = match multipart.next_field().await {
+            Ok(Some(field)) => field,
+            Ok(None) => break,
+            Err(e) => {
+                // Body hỏng / vượt giới hạn -> dừng đọc, trả về những gì đã xử lý
+                errors.push(UploadError {
+                    file_name: String::new(),
+                    code: "READ_ERROR",
+                    message: format!("Dữ liệu upload không hợp lệ: {}", e.body_text()),
+                });
+                break;
+            }
+        };
+
+        if field.name() != Some("files") {
+            continue;
+        }
+        let file_name = field.file_name().unwrap_or("").to_string();
+
+        file_count += 1;
+        if file_count > limits.max_files {
+            errors.push(UploadError {
+                file_name,
+                code: "TOO_MANY_FILES",
+                message: format!("Tối đa {} file mỗi lần upload", limits.max_files),
+            });
+            continue;
+        }
+
+        // Đọc từng chunk để dừng sớm khi vượt dung lượng, không đọc hết file lớn vào RAM
+        let mut data: Vec<u8> = Vec::new();
+        let mut failed: Option<UploadError> = None;
+        loop {
+            match field.chunk().await {
+                Ok(Some(chunk)) => {
+                    if data.len() + chunk.len() > limits.max_file_size {
+                        failed = Some(UploadError {
+                            file_name: file_name.clone(),
+                            code: "FILE_TOO_LARGE",
+                            message: format!("File vượt quá {} MB", limits.max_file_size / MB),
+                        });
+                        break;
+                    }
+                    data.extend_from_slice(&chunk);
+                }
+                Ok(None) => break,
+                Err(e) => {
+                    failed = Some(UploadError {
+                        file_name: file_name.clone(),
+                        code: "READ_ERROR",
+                        message: format!("Lỗi đọc file: {}", e.body_text()),
+                    });
+                    break;
+                }
+            }
+        }
+        if let Some(err) = failed {
+            errors.push(err);
+            continue;
+        }
+
+        if data.is_empty() {
+            errors.push(UploadError { file_name, code: "EMPTY_FILE", message: "File rỗng".to_string() });
+            continue;
+        }
 
-            // Tạo tên file unique
-            let new_name = format!("{}_{}", suid(), file_name);
-            let path = Path::new("storages").join(&new_name);
+        let content_type = match sniff_image(&data) {
+            Some((content_type, _)) => content_type,
+            None => {
+                errors.push(UploadError {
+                    file_name,
+                    code: "UNSUPPORTED_TYPE",
+                    message: "Chỉ chấp nhận ảnh JPEG, PNG, WebP, GIF".to_string(),
+                });
+                continue;
+            }
+        };
+
+        // File trùng nội dung với file đã có -> dùng lại, không xử lý / lưu thêm
+        let hash = media::content_hash(&data);
+        match media::find_by_hash(&state.db, &hash).await {
+            Ok(Some((media_id, image))) => {
+                uploaded_files.push(UploadedFile { file_name, media_id, image, content_type });
+                continue;
+            }
+            Ok(None) => {}
+            Err(e) => {
+                println!("Lỗi tra media: {:?}", e);
+                errors.push(UploadError { file_name, code: "WRITE_ERROR", message: "Không lưu được file".to_string() });
+                continue;
+            }
+        }
+        let size = data.len();
 
-            // Lưu file
-            if let Err(e) = fs::write(&path, data).await {
-                println!("Lỗi lưu file: {:?}", e);
+        // Resize + encode WebP (tốn CPU -> blocking pool), đồng thời bỏ EXIF/GPS
+        let processed = tokio::task::spawn_blocking(move || process_image(&data)).await;
+        let variants = match processed {
+            Ok(Ok(v)) => v,
+            Ok(Err(e)) => {
+                let code = if matches!(e, ImageError::TooLarge) { "FILE_TOO_LARGE" } else { "INVALID_IMAGE" };
+                errors.push(UploadError { file_name, code, message: e.to_string() });
+                continue;
+            }
+            Err(e) => {
+                println!("Lỗi xử lý ảnh: {:?}", e);
+                errors.push(UploadError { file_name, code: "INVALID_IMAGE", message: "Không xử lý được ảnh".to_string() });
                 continue;
             }
+        };
+
+        // Tạo tên file unique: <suid>_<tên gốc>_<thumb|medium|large>.webp
+        let stem = sanitize_file_stem(&file_name);
+        let base = if stem.is_empty() { suid() } else { format!("{}_{}", suid(), stem) };
+
+        let mut saved: Vec<(String, ImageVariant)> = Vec::new();
+        let mut write_failed = false;
+        for variant in variants {
+            let key = format!("{}_{}.webp", base, variant.name);
+            // Lưu file (local hoặc S3 tùy cấu hình)
+            if let Err(e) = state.storage.put(&key, variant.data, "image/webp").await {
+                println!("Lỗi lưu file: {}", e);
+                write_failed = true;
+                break;
+            }
+            saved.push((key.clone(), ImageVariant {
+                url: state.storage.public_url(&key), // Đường dẫn truy cập (Public URL)
+                width: variant.width,
+                height: variant.height,
+            }));
+        }
+
+        if write_failed {
+            // Không để lại file dở dang
+            for (key, _) in &saved {
+                let _ = state.storage.delete(key).await;
+            }
+            errors.push(UploadError {
+                file_name,
+                code: "WRITE_ERROR",
+                message: "Không lưu được file".to_string(),
+            });
+            continue;
+        }
+
+        let (blob_keys, saved): (Vec<String>, Vec<ImageVariant>) = saved.into_iter().unzip();
+        let mut saved = saved.into_iter();
+        let (thumb, medium, large) = match (saved.next(), saved.next(), saved.next()) {
+            (Some(t), Some(m), Some(l)) => (t, m, l),
+            _ => continue, // VARIANTS luôn có đủ 3 bản
+        };
+        let image = StoredImage {
+            url: large.url.clone(),
+            width: Some(large.width),
+            height: Some(large.height),
+            variants: Some(ImageVariants { thumb, medium, large }),
+        };
+
+        let inserted = media::insert_media(&state.db, NewMedia {
+            content_hash: &hash,
+            image: &image,
+            blob_keys: &blob_keys,
+            original_name: &file_name,
+            content_type,
+            size,
+            uploaded_by: &auth.user_id,
+        }).await;
+
+        let result = match inserted {
+            Ok(Some(media_id)) => Ok((media_id, image)),
+            // Upload song song cùng nội dung đã ghi trước -> bỏ bản vừa lưu, dùng bản kia
+            Ok(None) => match media::find_by_hash(&state.db, &hash).await {
+                Ok(Some(existing)) => Err(Some(existing)),
+                _ => Err(None),
+            },
+            Err(e) => {
+                println!("Lỗi ghi media: {:?}", e);
+                Err(None)
+            }
+        };
 
-            // Trả về đường dẫn truy cập (Public URL)
-            let public_url = format!("/storages/{}", new_name);
-            uploaded_files.push(public_url);
+        match result {
+            Ok((media_id, image)) => {
+                uploaded_files.push(UploadedFile { file_name, media_id, image, content_type });
+            }
+            Err(existing) => {
+                for key in &blob_keys {
+                    let _ = state.storage.delete(key).await;
+                }
+                match existing {
+                    Some((media_id, image)) => {
+                        uploaded_files.push(UploadedFile { file_name, media_id, image, content_type });
+                    }
+                    None => errors.push(UploadError {
+                        file_name,
+                        code: "WRITE_ERROR",
+                        message: "Không lưu được file".to_string(),
+                    }),
+                }
+            }
         }
     }
 
-    (StatusCode::OK, Json(uploaded_files)).into_response()
+    let status = if uploaded_files.is_empty() && !errors.is_empty() {
+        StatusCode::BAD_REQUEST
+    } else {
+        StatusCode::OK
+    };
+
+    (status, Json(serde_json::json!({ "files": uploaded_files, "errors": errors }))).into_response()
+}
+
+/// Kiểm tra URL có phải file đã upload qua `/api/upload` (URL do store sinh ra và file còn tồn tại) không
+pub(crate) async fn is_uploaded_file(store: &dyn BlobStore, url: &str) -> bool {
+    match store.key_from_url(url) {
+        Some(key) => store.exists(&key).await.unwrap_or_else(|e| {
+            println!("Lỗi kiểm tra file upload: {}", e);
+            false
+        }),
+        None => false,
+    }
 }
 
 pub fn upload_routes() -> Router<AppState> {
-    Router::new().route("/", post(upload_images))
-}
\ No newline at end of file
+    Router::new()
+        .route("/", post(upload_images))
+        .layer(DefaultBodyLimit::max(MAX_BODY_SIZE))
+}
@@ -0,0 +1,423 @@
+// src/seed.rs
+// Sinh dữ liệu demo: `my_rust_module seed --products 500 [--users 30] [--orders 200] [--seed 42]`
+// Cùng --seed thì nội dung (tên, giá, thông số, đánh giá...) giống nhau, chỉ khác ID (suid)
+use crate::routes::orders::{ update_user_level, POINT_UNIT_VND };
+use crate::routes::reviews::recompute_product_rating;
+use crate::utils::image::{ ImageVariant, ImageVariants, StoredImage, VARIANTS };
+use crate::utils::{ suid, Money };
+use rand::rngs::StdRng;
+use rand::seq::SliceRandom;
+use rand::{ Rng, SeedableRng };
+use serde_json::{ json, Value };
+use sqlx::{ MySql, MySqlPool, Transaction };
+use std::collections::HashSet;
+
+// Tất cả tài khoản demo dùng chung mật khẩu này
+pub const DEMO_PASSWORD: &str = "demo1234";
+const DEMO_EMAIL_DOMAIN: &str = "demo.local";
+
+#[derive(Debug)]
+pub struct SeedOptions {
+    pub products: usize,
+    pub users: usize,
+    pub orders: usize,
+    pub seed: u64,
+}
+
+impl SeedOptions {
+    pub fn parse(args: &[String]) -> Result<SeedOptions, String> {
+        let mut opts = SeedOptions { products: 50, users: 20, orders: 100, seed: 42 };
+        let mut iter = args.iter();
+
+        while let Some(flag) = iter.next() {
+            let value = iter.next().ok_or(format!("Thiếu giá trị cho {}", flag))?;
+            let parse = |v: &str| v.parse::<usize>().map_err(|_| format!("{} không hợp lệ: {}", flag, v));
+            match flag.as_str() {
+                "--products" => opts.products = parse(value)?,
+                "--users" => opts.users = parse(value)?.max(1),
+                "--orders" => opts.orders = parse(value)?,
+                "--seed" => opts.seed = value.parse().map_err(|_| format!("--seed không hợp lệ: {}", value))?,
+                _ => return Err(format!("Tham số không hợp lệ: {}", flag)),
+            }
+        }
+
+        if opts.products == 0 && opts.orders > 0 {
+            return Err("Cần ít nhất 1 sản phẩm để tạo đơn hàng".to_string());
+        }
+        Ok(opts)
+    }
+}
+
+// --- DỮ LIỆU MẪU ---
+
+struct CategoryTemplate {
+    name: &'static str,
+    brands: &'static [&'static str],
+    lines: &'static [&'static str],
+    price_range: (i64, i64), // Nghìn đồng
+}
+
+const CATEGORIES: &[CategoryTemplate] = &[
+    CategoryTemplate {
+        name: "Laptop",
+        brands: &["Dell", "HP", "Lenovo", "Asus", "Acer", "MacBook"],
+        lines: &["Inspiron 14", "Pavilion 15", "ThinkPad E14", "Vivobook 15", "Aspire 7", "Air M2", "Pro 14"],
+        price_range: (12_000, 45_000),
+    },
+    CategoryTemplate {
+        name: "Điện thoại",
+        brands: &["Samsung", "iPhone", "Xiaomi", "OPPO", "vivo", "realme"],
+        lines: &["Galaxy A55", "15 Pro", "Redmi Note 13", "Reno11", "V30", "12 Pro+"],
+        price_range: (3_000, 35_000),
+    },
+    CategoryTemplate {
+        name: "Máy tính bảng",
+        brands: &["iPad", "Samsung", "Xiaomi", "Lenovo"],
+        lines: &["Air 5", "Galaxy Tab S9", "Pad 6", "Tab P12"],
+        price_range: (5_000, 25_000),
+    },
+    CategoryTemplate {
+        name: "Tai nghe",
+        brands: &["Sony", "JBL", "AirPods", "Samsung", "Soundcore"],
+        lines: &["WH-1000XM5", "Tune 520BT", "Pro 2", "Buds2 Pro", "Liberty 4"],
+        price_range: (500, 8_000),
+    },
+    CategoryTemplate {
+        name: "Màn hình",
+        brands: &["LG", "Dell", "Samsung", "ViewSonic", "AOC"],
+        lines: &["UltraGear 27", "UltraSharp U2723", "Odyssey G5", "VX2479", "24G2"],
+        price_range: (2_500, 15_000),
+    },
+    CategoryTemplate {
+        name: "Đồng hồ thông minh",
+        brands: &["Apple Watch", "Galaxy Watch", "Garmin", "Amazfit"],
+        lines: &["Series 9", "6 Classic", "Forerunner 265", "GTR 4"],
+        price_range: (2_000, 15_000),
+    },
+    CategoryTemplate {
+        name: "Phụ kiện",
+        brands: &["Anker", "Logitech", "Baseus", "Ugreen"],
+        lines: &["Sạc nhanh 65W", "Chuột MX Master 3S", "Bàn phím K380", "Hub USB-C 7in1", "Pin dự phòng 20000mAh"],
+        price_range: (200, 3_000),
+    },
+];
+
+const FIRST_NAMES: &[&str] = &["An", "Bình", "Chi", "Dũng", "Giang", "Hà", "Hùng", "Lan", "Linh", "Minh", "Nam", "Ngọc", "Phong", "Quân", "Thảo", "Trang", "Tuấn", "Vy"];
+const LAST_NAMES: &[&str] = &["Nguyễn", "Trần", "Lê", "Phạm", "Hoàng", "Vũ", "Đặng", "Bùi", "Đỗ", "Ngô"];
+const CITIES: &[&str] = &["Hà Nội", "TP. Hồ Chí Minh", "Đà Nẵng", "Hải Phòng", "Cần Thơ", "Huế", "Nha Trang"];
+const REVIEW_TEXTS: &[(i32, &str)] = &[
+    (5, "Sản phẩm rất tốt, giao hàng nhanh, đóng gói cẩn thận."),
+    (5, "Dùng mượt, pin trâu, rất đáng tiền."),
+    (4, "Chất lượng ổn so với giá, sẽ ủng hộ tiếp."),
+    (4, "Hàng chính hãng, tư vấn nhiệt tình. Giao hơi chậm một chút."),
+    (3, "Tạm được, không quá nổi bật."),
+    (2, "Máy hơi nóng khi dùng lâu."),
+];
+
+/// Ảnh demo từ picsum, có đủ các bản thumb/medium/large như ảnh upload thật
+fn demo_image(seed: &str) -> StoredImage {
+    let variant = |i: usize| {
+        let side = VARIANTS[i].1;
+        ImageVariant { url: format!("https://picsum.photos/seed/{}/{}/{}", seed, side, side), width: side, height: side }
+    };
+    let large = variant(2);
+    StoredImage {
+        url: large.url.clone(),
+        width: Some(large.width),
+        height: Some(large.height),
+        variants: Some(ImageVariants { thumb: variant(0), medium: variant(1), large }),
+    }
+}
+
+fn specs_for(category: &str, rng: &mut StdRng) -> Value {
+    let pick = |rng: &mut StdRng, items: &[&str]| items.choose(rng).unwrap().to_string();
+    match category {
+        "Laptop" => json!({
+            "CPU": pick(rng, &["Intel Core i5-1335U", "Intel Core i7-13700H", "AMD Ryzen 5 7530U", "AMD Ryzen 7 7840HS", "Apple M2"]),
+            "RAM": pick(rng, &["8GB", "16GB", "32GB"]),
+            "Ổ cứng": pick(rng, &["256GB SSD", "512GB SSD", "1TB SSD"]),
+            "Màn hình": pick(rng, &["14\" FHD IPS", "15.6\" FHD 144Hz", "13.6\" Liquid Retina", "16\" 2.5K 165Hz"]),
+            "Trọng lượng": format!("{:.2} kg", rng.gen_range(1.2..2.4)),
+        }),
+        "Điện thoại" | "Máy tính bảng" => json!({
+            "Chip": pick(rng, &["Snapdragon 8 Gen 2", "Dimensity 8200", "Apple A17 Pro", "Exynos 1480", "Helio G99"]),
+            "RAM": pick(rng, &["6GB", "8GB", "12GB"]),
+            "Bộ nhớ": pick(rng, &["128GB", "256GB", "512GB"]),
+            "Màn hình": pick(rng, &["6.1\" OLED", "6.7\" AMOLED 120Hz", "11\" IPS", "12.4\" AMOLED"]),
+            "Pin": format!("{} mAh", rng.gen_range(40..100) * 100),
+        }),
+        "Tai nghe" => json!({
+            "Kiểu": pick(rng, &["Over-ear", "In-ear", "True Wireless"]),
+            "Chống ồn": pick(rng, &["ANC", "Không"]),
+            "Thời lượng pin": format!("{} giờ", rng.gen_range(6..60)),
+            "Kết nối": pick(rng, &["Bluetooth 5.3", "Bluetooth 5.2"]),
+        }),
+        "Màn hình" => json!({
+            "Kích thước": pick(rng, &["24\"", "27\"", "32\""]),
+            "Độ phân giải": pick(rng, &["FHD", "2K QHD", "4K UHD"]),
+            "Tần số quét": pick(rng, &["75Hz", "144Hz", "165Hz", "240Hz"]),
+            "Tấm nền": pick(rng, &["IPS", "VA", "OLED"]),
+        }),
+        "Đồng hồ thông minh" => json!({
+            "Màn hình": pick(rng, &["AMOLED 1.4\"", "Retina LTPO"]),
+            "Pin": format!("{} ngày", rng.gen_range(1..14)),
+            "Chống nước": pick(rng, &["5ATM", "IP68"]),
+        }),
+        _ => json!({
+            "Bảo hành": pick(rng, &["6 tháng", "12 tháng", "18 tháng"]),
+            "Xuất xứ": pick(rng, &["Trung Quốc", "Việt Nam", "Thái Lan"]),
+        }),
+    }
+}
+
+// --- SEED ---
+
+struct SeedProduct {
+    id: String,
+    price: Money,
+}
+
+pub async fn run(pool: &MySqlPool, opts: &SeedOptions) -> Result<(), String> {
+    let existing: (i64,) = sqlx
+        ::query_as("SELECT COUNT(*) FROM users WHERE email LIKE ?")
+        .bind(format!("%@{}", DEMO_EMAIL_DOMAIN))
+        .fetch_one(pool).await
+        .map_err(|e| format!("Lỗi DB: {}", e))?;
+    if existing.0 > 0 {
+        return Err(format!("DB đã có dữ liệu demo (@{}), hãy seed trên DB trống", DEMO_EMAIL_DOMAIN));
+    }
+
+    let mut rng = StdRng::seed_from_u64(opts.seed);
+    // Hash 1 lần rồi dùng chung cho mọi tài khoản demo
+    let password_hash = tokio::task::spawn_blocking(|| bcrypt::hash(DEMO_PASSWORD, bcrypt::DEFAULT_COST))
+        .await
+        .map_err(|e| e.to_string())?
+        .map_err(|e| e.to_string())?;
+
+    let mut tx = pool.begin().await.map_err(|e| e.to_string())?;
+    let res = seed_all(&mut tx, opts, &mut rng, &password_hash).await;
+
+    match res {
+        Ok(summary) => {
+            tx.commit().await.map_err(|e| e.to_string())?;
+            println!("✅ \x1b[32mĐã seed: {}\x1b[0m", summary);
+            println!("   Đăng nhập: admin@{} / {} (các user demo dùng chung mật khẩu)", DEMO_EMAIL_DOMAIN, DEMO_PASSWORD);
+            Ok(())
+        }
+        Err(e) => {
+            let _ = tx.rollback().await;
+            Err(format!("Lỗi seed: {}", e))
+        }
+    }
+}
+
+async fn seed_all(
+    tx: &mut Transaction<'_, MySql>,
+    opts: &SeedOptions,
+    rng: &mut StdRng,
+    password_hash: &str,
+) -> Result<String, sqlx::Error> {
+    // 1. Settings mặc định (không ghi đè giá trị admin đã chỉnh)
+    for (id, value) in [
+        ("site_name", "ElectroShop"),
+        ("hotline", "1900 1234"),
+        ("contact_email", "support@demo.local"),
+        ("point_ratio", "1000"),
+        ("level_silver", "1000"),
+        ("level_gold", "5000"),
+        ("level_diamond", "10000"),
+    ] {
+        sqlx::query("INSERT IGNORE INTO settings (id, value) VALUES (?, ?)")
+            .bind(id)
+            .bind(value)
+            .execute(&mut **tx).await?;
+    }
+
+    // 2. Danh mục (dùng lại nếu đã có cùng tên)
+    let mut category_ids = vec![];
+    for template in CATEGORIES {
+        let existing: Option<(String,)> = sqlx::query_as("SELECT id FROM categories WHERE name = ?")
+            .bind(template.name)
+            .fetch_optional(&mut **tx).await?;
+        let id = match existing {
+            Some((id,)) => id,
+            None => {
+                let id = suid();
+                sqlx::query("INSERT INTO categories (id, name) VALUES (?, ?)")
+                    .bind(&id)
+                    .bind(template.name)
+                    .execute(&mut **tx).await?;
+                id
+            }
+        };
+        category_ids.push(id);
+    }
+
+    // 3. Sản phẩm
+    let mut products: Vec<SeedProduct> = Vec::with_capacity(opts.products);
+    for i in 0..opts.products {
+        let idx = rng.gen_range(0..CATEGORIES.len());
+        let template = &CATEGORIES[idx];
+        let name = format!(
+            "{} {} {}",
+            template.brands.choose(rng).unwrap(),
+            template.lines.choose(rng).unwrap(),
+            ["", "2024", "Plus", "Lite", "Max"].choose(rng).unwrap()
+        ).trim().to_string();
+
+        // Giá làm tròn tới 10.000đ cho giống thật
+        let price = Money::vnd(rng.gen_range(template.price_range.0..=template.price_range.1) / 10 * 10_000);
+        // ~10% hết hàng để demo "báo khi có hàng"
+        let stock: i32 = if rng.gen_bool(0.1) { 0 } else { rng.gen_range(5..200) };
+
+        let id = suid();
+        let images: Vec<StoredImage> = (0..rng.gen_range(1..=4))
+            .map(|n| demo_image(&format!("shop-{}-{}-{}", opts.seed, i, n)))
+            .collect();
+        let description = format!(
+            "{} chính hãng, bảo hành 12 tháng. Hỗ trợ trả góp 0%, giao nhanh 2h tại {}.",
+            name,
+            CITIES.choose(rng).unwrap()
+        );
+
+        sqlx::query(
+            "INSERT INTO products (id, category_id, name, price, stock, images, description, specs)
+             VALUES (?, ?, ?, ?, ?, ?, ?, ?)"
+        )
+        .bind(&id)
+        .bind(&category_ids[idx])
+        .bind(&name)
+        .bind(price)
+        .bind(stock)
+        .bind(sqlx::types::Json(&images))
+        .bind(&description)
+        .bind(sqlx::types::Json(specs_for(template.name, rng)))
+        .execute(&mut **tx).await?;
+
+        products.push(SeedProduct { id, price });
+    }
+
+    // 4. Users (+ 1 admin)
+    let mut user_ids = vec![];
+    for i in 0..=opts.users {
+        let id = suid();
+        let (email, name, role) = if i == 0 {
+            (format!("admin@{}", DEMO_EMAIL_DOMAIN), "Quản trị viên".to_string(), "admin")
+        } else {
+            let name = format!("{} {}", LAST_NAMES.choose(rng).unwrap(), FIRST_NAMES.choose(rng).unwrap());
+            (format!("user{}@{}", i, DEMO_EMAIL_DOMAIN), name, "user")
+        };
+        let phone = format!("09{:08}", rng.gen_range(0..100_000_000));
+        let address = format!("{} đường số {}, {}", rng.gen_range(1..300), rng.gen_range(1..30), CITIES.choose(rng).unwrap());
+
+        sqlx::query(
+            "INSERT INTO users (id, email, name, password_hash, role, status, email_verified, phone, address)
+             VALUES (?, ?, ?, ?, ?, 'active', 1, ?, ?)"
+        )
+        .bind(&id)
+        .bind(&email)
+        .bind(&name)
+        .bind(password_hash)
+        .bind(role)
+        .bind(&phone)
+        .bind(&address)
+        .execute(&mut **tx).await?;
+
+        if role == "user" {
+            user_ids.push((id, name, phone, address));
+        }
+    }
+
+    // 5. Đơn hàng trong 180 ngày gần đây (+ đánh giá cho đơn đã hoàn thành)
+    let mut reviewed: HashSet<(usize, usize)> = HashSet::new();
+    let mut touched_products: HashSet<usize> = HashSet::new();
+    let mut reviews = 0;
+    let now = chrono::Local::now().naive_local();
+
+    for _ in 0..opts.orders {
+        let user_idx = rng.gen_range(0..user_ids.len());
+        let (user_id, name, phone, address) = &user_ids[user_idx];
+        let status = *["pending", "shipping", "completed", "completed", "completed", "cancelled"].choose(rng).unwrap();
+        let created_at = now - chrono::Duration::minutes(rng.gen_range(0..180 * 24 * 60));
+
+        let count = rng.gen_range(1..=4.min(products.len()));
+        let picked: Vec<usize> = rand::seq::index::sample(rng, products.len(), count).into_vec();
+        let lines: Vec<(usize, i32)> = picked.into_iter().map(|p| (p, rng.gen_range(1..=3))).collect();
+
+        let total = lines
+            .iter()
+            .try_fold(Money::vnd(0), |total, (p, q)| total.checked_add(products[*p].price.checked_mul(*q)?))
+            .map_err(|e| sqlx::Error::Protocol(e.to_string()))?;
+        let points_earned = i32::try_from(total.div_floor(Money::vnd(POINT_UNIT_VND)).unwrap_or(0)).unwrap_or(0);
+
+        let order_id = suid();
+        sqlx::query(
+            "INSERT INTO orders (id, user_id, total_amount, discount_amount, final_amount, points_earned, status,
+                                 shipping_name, shipping_phone, shipping_address, created_at)
+             VALUES (?, ?, ?, 0, ?, ?, ?, ?, ?, ?, ?)"
+        )
+        .bind(&order_id)
+        .bind(user_id)
+        .bind(total)
+        .bind(total)
+        .bind(points_earned)
+        .bind(status)
+        .bind(name)
+        .bind(phone)
+        .bind(address)
+        .bind(created_at)
+        .execute(&mut **tx).await?;
+
+        for (p, quantity) in &lines {
+            sqlx::query("INSERT INTO order_items (id, order_id, product_id, quantity, price) VALUES (?, ?, ?, ?, ?)")
+                .bind(suid())
+                .bind(&order_id)
+                .bind(&products[*p].id)
+                .bind(quantity)
+                .bind(products[*p].price)
+                .execute(&mut **tx).await?;
+        }
+
+        if status != "completed" {
+            continue;
+        }
+
+        sqlx::query("UPDATE users SET points = points + ? WHERE id = ?")
+            .bind(points_earned)
+            .bind(user_id)
+            .execute(&mut **tx).await?;
+
+        for (p, _) in &lines {
+            if !rng.gen_bool(0.6) || !reviewed.insert((user_idx, *p)) {
+                continue;
+            }
+            let (rating, content) = *REVIEW_TEXTS.choose(rng).unwrap();
+            sqlx::query(
+                "INSERT INTO reviews (id, user_id, product_id, rating, content, verified_purchase, status, created_at)
+                 VALUES (?, ?, ?, ?, ?, 1, 'approved', ?)"
+            )
+            .bind(suid())
+            .bind(user_id)
+            .bind(&products[*p].id)
+            .bind(rating)
+            .bind(content)
+            .bind(created_at + chrono::Duration::days(rng.gen_range(2..10)))
+            .execute(&mut **tx).await?;
+            touched_products.insert(*p);
+            reviews += 1;
+        }
+    }
+
+    // 6. Tính lại rating sản phẩm và hạng thành viên
+    for p in &touched_products {
+        recompute_product_rating(tx, &products[*p].id).await?;
+    }
+    for (user_id, ..) in &user_ids {
+        update_user_level(tx, user_id).await;
+    }
+
+    Ok(format!(
+        "{} danh mục, {} sản phẩm, {} user, {} đơn hàng, {} đánh giá",
+        category_ids.len(), products.len(), user_ids.len() + 1, opts.orders, reviews
+    ))
+}
@@ -0,0 +1,66 @@
+// src/storage/local.rs
+// Lưu file vào thư mục trên ổ đĩa, phục vụ qua `nest_service("/storages", ...)` trong main.rs.
+// Chỉ dùng được khi chạy 1 instance (hoặc các instance dùng chung ổ mạng).
+use axum::async_trait;
+use std::path::PathBuf;
+use tokio::fs;
+
+use super::{ validate_key, BlobStore, StorageError };
+
+/// Đường dẫn public mà main.rs mount thư mục upload
+pub const LOCAL_PUBLIC_BASE: &str = "/storages";
+
+pub struct LocalBlobStore {
+    root: PathBuf,
+}
+
+impl LocalBlobStore {
+    /// Tạo thư mục nếu chưa có
+    pub fn new(root: &str) -> Result<Self, StorageError> {
+        let root = PathBuf::from(root);
+        if !root.exists() {
+            std::fs::create_dir_all(&root).map_err(|e| StorageError::Io(e.to_string()))?;
+            println!("Đã tạo thư mục {}", root.display());
+        }
+        Ok(LocalBlobStore { root })
+    }
+
+    fn path_for(&self, key: &str) -> Result<PathBuf, StorageError> {
+        validate_key(key)?;
+        Ok(self.root.join(key))
+    }
+}
+
+#[async_trait]
+impl BlobStore for LocalBlobStore {
+    fn name(&self) -> &'static str {
+        "local"
+    }
+
+    fn public_base(&self) -> &str {
+        LOCAL_PUBLIC_BASE
+    }
+
+    async fn put(&self, key: &str, data: Vec<u8>, _content_type: &str) -> Result<(), StorageError> {
+        let path = self.path_for(key)?;
+        fs::write(path, data).await.map_err(|e| StorageError::Io(e.to_string()))
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), StorageError> {
+        let path = self.path_for(key)?;
+        match fs::remove_file(path).await {
+            Ok(()) => Ok(()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(()), // Xóa rồi coi như xong
+            Err(e) => Err(StorageError::Io(e.to_string())),
+        }
+    }
+
+    async fn exists(&self, key: &str) -> Result<bool, StorageError> {
+        let path = self.path_for(key)?;
+        match fs::metadata(path).await {
+            Ok(meta) => Ok(meta.is_file()),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(false),
+            Err(e) => Err(StorageError::Io(e.to_string())),
+        }
+    }
+}
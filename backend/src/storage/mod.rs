@@ -0,0 +1,82 @@
+// src/storage/mod.rs
+// Trừu tượng hóa nơi lưu file upload (ổ đĩa local hoặc S3-compatible: AWS S3, MinIO, R2...).
+// Handler chỉ làm việc với `key` (tên file trong store); URL public do store sinh ra.
+pub mod local;
+pub mod s3;
+
+use axum::async_trait;
+use crate::config::StorageConfig;
+use std::fmt;
+use std::sync::Arc;
+
+pub use self::local::LocalBlobStore;
+pub use self::s3::S3BlobStore;
+
+#[derive(Debug)]
+pub enum StorageError {
+    /// Key rỗng, chứa `..`, ký tự lạ...
+    InvalidKey(String),
+    /// Lỗi ghi/đọc ổ đĩa
+    Io(String),
+    /// Lỗi gọi S3 (mạng, sai chữ ký, bucket không tồn tại...)
+    Upstream(String),
+}
+
+impl fmt::Display for StorageError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            StorageError::InvalidKey(key) => write!(f, "Key không hợp lệ: {}", key),
+            StorageError::Io(msg) => write!(f, "Lỗi đọc/ghi file: {}", msg),
+            StorageError::Upstream(msg) => write!(f, "Lỗi kết nối storage: {}", msg),
+        }
+    }
+}
+
+#[async_trait]
+pub trait BlobStore: Send + Sync {
+    /// Tên backend ("local" | "s3"), dùng khi log
+    fn name(&self) -> &'static str;
+
+    /// Tiền tố URL public, không có '/' ở cuối (VD: `/storages`, `https://cdn.example.com/shop`)
+    fn public_base(&self) -> &str;
+
+    async fn put(&self, key: &str, data: Vec<u8>, content_type: &str) -> Result<(), StorageError>;
+
+    async fn delete(&self, key: &str) -> Result<(), StorageError>;
+
+    async fn exists(&self, key: &str) -> Result<bool, StorageError>;
+
+    /// URL public để client tải file
+    fn public_url(&self, key: &str) -> String {
+        format!("{}/{}", self.public_base(), key)
+    }
+
+    /// Ngược lại của `public_url`: URL không thuộc store này -> None
+    fn key_from_url(&self, url: &str) -> Option<String> {
+        let key = url.strip_prefix(self.public_base())?.strip_prefix('/')?;
+        validate_key(key).ok()?;
+        Some(key.to_string())
+    }
+}
+
+/// Chỉ chấp nhận key phẳng gồm chữ/số ASCII, '-', '_', '.' (không thư mục con, không `..`)
+pub fn validate_key(key: &str) -> Result<(), StorageError> {
+    let valid = !key.is_empty()
+        && key.len() <= 255
+        && !key.starts_with('.')
+        && !key.contains("..")
+        && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.');
+    if valid {
+        Ok(())
+    } else {
+        Err(StorageError::InvalidKey(key.to_string()))
+    }
+}
+
+/// Tạo store theo cấu hình (đã validate trong Config::load)
+pub fn from_config(config: &StorageConfig) -> Result<Arc<dyn BlobStore>, StorageError> {
+    match config.backend.as_str() {
+        "s3" => Ok(Arc::new(S3BlobStore::new(&config.s3)?)),
+        _ => Ok(Arc::new(LocalBlobStore::new(&config.upload_dir)?)),
+    }
+}
@@ -0,0 +1,206 @@
+// src/storage/s3.rs
+// Lưu file lên S3-compatible storage (AWS S3, MinIO, Cloudflare R2...) qua REST API,
+// ký request bằng AWS Signature V4. Chỉ cần PUT / HEAD / DELETE object nên không kéo cả SDK.
+use axum::async_trait;
+use chrono::Utc;
+use hmac::{ Hmac, Mac };
+use reqwest::{ Client, Method, StatusCode, Url };
+use sha2::{ Digest, Sha256 };
+use std::time::Duration;
+
+use super::{ validate_key, BlobStore, StorageError };
+use crate::config::S3Config;
+
+type HmacSha256 = Hmac<Sha256>;
+
+// File upload có tên unique (suid) nên cache vĩnh viễn được
+const CACHE_CONTROL: &str = "public, max-age=31536000, immutable";
+
+pub struct S3BlobStore {
+    http: Client,
+    endpoint: Url,
+    bucket: String,
+    region: String,
+    access_key: String,
+    secret_key: String,
+    path_style: bool,
+    public_base: String,
+}
+
+impl S3BlobStore {
+    pub fn new(config: &S3Config) -> Result<Self, StorageError> {
+        let endpoint = Url::parse(config.endpoint.trim_end_matches('/'))
+            .map_err(|e| StorageError::Upstream(format!("S3_ENDPOINT không hợp lệ: {}", e)))?;
+        let public_base = match &config.public_url {
+            Some(url) => url.trim_end_matches('/').to_string(),
+            None if config.path_style => format!("{}/{}", endpoint.as_str().trim_end_matches('/'), config.bucket),
+            None => format!(
+                "{}://{}.{}",
+                endpoint.scheme(),
+                config.bucket,
+                host_header(&endpoint)
+            ),
+        };
+        let http = Client::builder()
+            .timeout(Duration::from_secs(30))
+            .build()
+            .map_err(|e| StorageError::Upstream(e.to_string()))?;
+
+        Ok(S3BlobStore {
+            http,
+            endpoint,
+            bucket: config.bucket.clone(),
+            region: config.region.clone(),
+            access_key: config.access_key.clone(),
+            secret_key: config.secret_key.clone(),
+            path_style: config.path_style,
+            public_base,
+        })
+    }
+
+    /// URL của object: path-style `<endpoint>/<bucket>/<key>`, virtual-host `<bucket>.<host>/<key>`
+    fn object_url(&self, key: &str) -> Result<Url, StorageError> {
+        validate_key(key)?;
+        let mut url = self.endpoint.clone();
+        if self.path_style {
+            url.set_path(&format!("/{}/{}", self.bucket, key));
+        } else {
+            let host = format!("{}.{}", self.bucket, self.endpoint.host_str().unwrap_or_default());
+            url.set_host(Some(&host)).map_err(|e| StorageError::Upstream(e.to_string()))?;
+            url.set_path(&format!("/{}", key));
+        }
+        Ok(url)
+    }
+
+    /// Gửi request đã ký SigV4. `body` rỗng với HEAD / DELETE
+    async fn send(
+        &self,
+        method: Method,
+        key: &str,
+        body: Vec<u8>,
+        content_type: Option<&str>
+    ) -> Result<reqwest::Response, StorageError> {
+        let url = self.object_url(key)?;
+        let now = Utc::now();
+        let amz_date = now.format("%Y%m%dT%H%M%SZ").to_string();
+        let date = now.format("%Y%m%d").to_string();
+        let payload_hash = hex::encode(Sha256::digest(&body));
+        let host = host_header(&url);
+
+        // Canonical request: các header ký phải sắp xếp theo tên
+        let signed_headers = "host;x-amz-content-sha256;x-amz-date";
+        let canonical_request = format!(
+            "{}\n{}\n\nhost:{}\nx-amz-content-sha256:{}\nx-amz-date:{}\n\n{}\n{}",
+            method.as_str(),
+            url.path(), // key đã được validate_key -> không cần encode thêm
+            host,
+            payload_hash,
+            amz_date,
+            signed_headers,
+            payload_hash
+        );
+        let scope = format!("{}/{}/s3/aws4_request", date, self.region);
+        let string_to_sign = format!(
+            "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+            amz_date,
+            scope,
+            hex::encode(Sha256::digest(canonical_request.as_bytes()))
+        );
+
+        let k_date = hmac(format!("AWS4{}", self.secret_key).as_bytes(), date.as_bytes());
+        let k_region = hmac(&k_date, self.region.as_bytes());
+        let k_service = hmac(&k_region, b"s3");
+        let k_signing = hmac(&k_service, b"aws4_request");
+        let signature = hex::encode(hmac(&k_signing, string_to_sign.as_bytes()));
+
+        let authorization = format!(
+            "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+            self.access_key,
+            scope,
+            signed_headers,
+            signature
+        );
+
+        let mut req = self.http
+            .request(method, url)
+            .header("x-amz-date", amz_date)
+            .header("x-amz-content-sha256", payload_hash)
+            .header(reqwest::header::AUTHORIZATION, authorization);
+        if let Some(content_type) = content_type {
+            req = req
+                .header(reqwest::header::CONTENT_TYPE, content_type)
+                .header(reqwest::header::CACHE_CONTROL, CACHE_CONTROL);
+        }
+        if !body.is_empty() {
+            req = req.body(body);
+        }
+
+        req.send().await.map_err(|e| StorageError::Upstream(e.to_string()))
+    }
+}
+
+#[async_trait]
+impl BlobStore for S3BlobStore {
+    fn name(&self) -> &'static str {
+        "s3"
+    }
+
+    fn public_base(&self) -> &str {
+        &self.public_base
+    }
+
+    async fn put(&self, key: &str, data: Vec<u8>, content_type: &str) -> Result<(), StorageError> {
+        let res = self.send(Method::PUT, key, data, Some(content_type)).await?;
+        if res.status().is_success() {
+            Ok(())
+        } else {
+            Err(upstream_error(res).await)
+        }
+    }
+
+    async fn delete(&self, key: &str) -> Result<(), StorageError> {
+        let res = self.send(Method::DELETE, key, Vec::new(), None).await?;
+        // S3 trả 204 cả khi object không tồn tại
+        if res.status().is_success() || res.status() == StatusCode::NOT_FOUND {
+            Ok(())
+        } else {
+            Err(upstream_error(res).await)
+        }
+    }
+
+    async fn exists(&self, key: &str) -> Result<bool, StorageError> {
+        let res = self.send(Method::HEAD, key, Vec::new(), None).await?;
+        match res.status() {
+            s if s.is_success() => Ok(true),
+            StatusCode::NOT_FOUND => Ok(false),
+            _ => Err(upstream_error(res).await),
+        }
+    }
+}
+
+fn hmac(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut mac = HmacSha256::new_from_slice(key).expect("HMAC nhận key mọi độ dài");
+    mac.update(data);
+    mac.finalize().into_bytes().to_vec()
+}
+
+/// Giá trị header Host: kèm port nếu không phải port mặc định (MinIO thường là :9000)
+fn host_header(url: &Url) -> String {
+    let host = url.host_str().unwrap_or_default();
+    match url.port() {
+        Some(port) => format!("{}:{}", host, port),
+        None => host.to_string(),
+    }
+}
+
+async fn upstream_error(res: reqwest::Response) -> StorageError {
+    let status = res.status();
+    let body = res.text().await.unwrap_or_default();
+    // Body lỗi của S3 là XML <Error><Code>..</Code><Message>..</Message></Error>
+    let code = body
+        .split("<Code>")
+        .nth(1)
+        .and_then(|s| s.split("</Code>").next())
+        .unwrap_or("");
+    StorageError::Upstream(format!("S3 trả về {} {}", status, code))
+}
@@ -0,0 +1,60 @@
+// src/utils/audit.rs
+// Ghi nhật ký thao tác của admin (ai, làm gì, trên đối tượng nào, trước/sau ra sao)
+use serde_json::Value;
+use sqlx::MySql;
+use crate::utils::suid;
+
+pub struct AuditEntry<'a> {
+    pub actor_id: &'a str,
+    pub action: &'a str,      // VD: "users.update", "products.delete"
+    pub target_type: &'a str, // VD: "user", "product", "order"
+    pub target_id: &'a str,
+    pub before: Option<Value>,
+    pub after: Option<Value>,
+    pub reason: Option<&'a str>,
+    pub ip: Option<&'a str>,
+}
+
+/// Ghi một dòng audit. Nhận Executor để có thể chạy chung transaction với thao tác chính.
+pub async fn record_audit<'e, E>(executor: E, entry: AuditEntry<'_>) -> Result<(), sqlx::Error>
+where
+    E: sqlx::Executor<'e, Database = MySql>,
+{
+    sqlx::query(
+        "INSERT INTO admin_audit_logs (id, actor_id, action, target_type, target_id, before_data, after_data, reason, ip)
+         VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)"
+    )
+    .bind(suid())
+    .bind(entry.actor_id)
+    .bind(entry.action)
+    .bind(entry.target_type)
+    .bind(entry.target_id)
+    .bind(entry.before.map(sqlx::types::Json))
+    .bind(entry.after.map(sqlx::types::Json))
+    .bind(entry.reason)
+    .bind(entry.ip)
+    .execute(executor)
+    .await?;
+
+    Ok(())
+}
+
+/// So sánh 2 object JSON, chỉ giữ lại các trường bị thay đổi -> (before, after)
+pub fn diff(before: &Value, after: &Value) -> (Value, Value) {
+    match (before, after) {
+        (Value::Object(b), Value::Object(a)) => {
+            let mut old = serde_json::Map::new();
+            let mut new = serde_json::Map::new();
+            for key in b.keys().chain(a.keys().filter(|k| !b.contains_key(*k))) {
+                let old_value = b.get(key).cloned().unwrap_or(Value::Null);
+                let new_value = a.get(key).cloned().unwrap_or(Value::Null);
+                if old_value != new_value {
+                    old.insert(key.clone(), old_value);
+                    new.insert(key.clone(), new_value);
+                }
+            }
+            (Value::Object(old), Value::Object(new))
+        }
+        _ => (before.clone(), after.clone()),
+    }
+}
@@ -1,110 +1,505 @@
-use lettre::{Message, SmtpTransport, Transport};
-use lettre::transport::smtp::authentication::Credentials;
-use lettre::message::header::ContentType; // <-- Thêm cái này
-use std::env;
-
-// Hàm gửi mail chung (đã nâng cấp lên HTML)
-fn send_email(to_email: String, subject: String, body_html: String) {
-    let gmail_user = env::var("GMAIL_USER").expect("Thiếu GMAIL_USER");
-    let gmail_pass = env::var("GMAIL_PASS").expect("Thiếu GMAIL_PASS");
-
-    let email = Message::builder()
-        .from(gmail_user.parse().unwrap())
-        .to(to_email.parse().unwrap())
-        .subject(subject)
-        .header(ContentType::TEXT_HTML) // <-- QUAN TRỌNG: Báo cho Gmail biết đây là HTML
-        .body(body_html)
-        .unwrap();
-
-    let creds = Credentials::new(gmail_user, gmail_pass);
-    let mailer = SmtpTransport::relay("smtp.gmail.com")
-        .unwrap()
-        .credentials(creds)
-        .build();
-
-    std::thread::spawn(move || {
-        match mailer.send(&email) {
-            Ok(_) => println!("Email sent to {}", to_email),
-            Err(e) => eprintln!("Error sending email: {:?}", e),
+// src/utils/email.rs
+// Các hàm send_* không gửi ngay mà render template rồi ghi vào bảng email_outbox (cùng transaction
+// với thay đổi nghiệp vụ nếu truyền `&mut *tx`). Worker trong utils::outbox gửi thật qua `deliver`
+// bằng transport trong crate::mail (SMTP / file .eml / bộ nhớ).
+use lettre::message::Mailbox;
+use serde::Serialize;
+use serde_json::{ json, Value };
+use sqlx::MySql;
+use std::fmt;
+use std::sync::{ Arc, OnceLock };
+use crate::mail::{ DeliveryError, EmailAttachment, MailTransport, OutgoingEmail };
+use crate::utils::outbox::{ self, OutboxAttachment };
+use crate::utils::suid;
+use crate::utils::Money;
+use crate::utils::money::MoneyError;
+use crate::utils::templates::{ self, normalize_language, RenderedEmail, DEFAULT_LANGUAGE };
+
+// Người gửi khi không cấu hình SMTP_FROM / SMTP_USER (transport file / memory)
+const FALLBACK_SENDER: &str = "ElectroShop <no-reply@localhost>";
+
+// Transport dựng 1 lần lúc khởi động (main.rs), dùng chung cho mọi lần gửi
+struct Mailer {
+    transport: Arc<dyn MailTransport>,
+    from: Mailbox,
+}
+
+static MAILER: OnceLock<Mailer> = OnceLock::new();
+
+impl Mailer {
+    /// `from` trống -> FALLBACK_SENDER (địa chỉ đã validate trong Config::load)
+    fn new(transport: Arc<dyn MailTransport>, from: Option<&str>) -> Self {
+        let from = from
+            .and_then(|f| f.parse::<Mailbox>().ok())
+            .unwrap_or_else(|| FALLBACK_SENDER.parse().expect("FALLBACK_SENDER hợp lệ"));
+        Mailer { transport, from }
+    }
+
+    fn deliver(
+        &self,
+        to_email: &str,
+        subject: &str,
+        html: &str,
+        text: &str,
+        attachments: Vec<EmailAttachment>
+    ) -> Result<(), DeliveryError> {
+        let to = to_email
+            .parse::<Mailbox>()
+            .map_err(|e| DeliveryError::permanent(format!("Địa chỉ email không hợp lệ: {}", e)))?;
+
+        self.transport.send(&OutgoingEmail {
+            from: self.from.clone(),
+            to,
+            subject: subject.to_string(),
+            html: html.to_string(),
+            text: text.to_string(),
+            attachments,
+        })
+    }
+}
+
+/// Gắn transport gửi mail
+pub fn init(transport: Arc<dyn MailTransport>, from: Option<&str>) {
+    let _ = MAILER.set(Mailer::new(transport, from));
+}
+
+/// Đã có transport để gửi chưa
+pub fn is_enabled() -> bool {
+    MAILER.get().is_some()
+}
+
+/// Gửi thật 1 email qua transport đã cấu hình. Blocking -> worker gọi trong spawn_blocking
+pub fn deliver(
+    to_email: &str,
+    subject: &str,
+    html: &str,
+    text: &str,
+    attachments: Vec<EmailAttachment>
+) -> Result<(), DeliveryError> {
+    MAILER
+        .get()
+        .ok_or_else(|| DeliveryError::temporary("Chưa cấu hình gửi mail".to_string()))?
+        .deliver(to_email, subject, html, text, attachments)
+}
+
+/// Lỗi ghi email vào outbox. Caller rollback transaction như mọi lỗi DB khác
+#[derive(Debug)]
+pub enum EmailError {
+    /// Template (thường là bản admin sửa) render lỗi
+    Render(String),
+    Db(sqlx::Error),
+}
+
+impl fmt::Display for EmailError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            EmailError::Render(msg) => write!(f, "Lỗi render email: {}", msg),
+            EmailError::Db(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl From<sqlx::Error> for EmailError {
+    fn from(e: sqlx::Error) -> Self {
+        EmailError::Db(e)
+    }
+}
+
+// Tính tiền trong nội dung mail lỗi (tràn số) -> không render được mail
+impl From<MoneyError> for EmailError {
+    fn from(e: MoneyError) -> Self {
+        EmailError::Render(e.to_string())
+    }
+}
+
+// Email đã render theo ngôn ngữ người nhận, chờ ghi vào outbox
+#[derive(Debug)]
+struct QueuedEmail {
+    to_email: String,
+    template: &'static str,
+    lang: &'static str,
+    content: RenderedEmail,
+    attachments: Vec<OutboxAttachment>,
+}
+
+fn render_email(
+    to_email: String,
+    lang: &str,
+    template: &'static str,
+    ctx: Value,
+    attachments: Vec<OutboxAttachment>
+) -> Result<QueuedEmail, EmailError> {
+    let lang = normalize_language(lang);
+    let content = templates::render(template, lang, &ctx).map_err(|e| {
+        println!("Lỗi render email {}: {}", template, e);
+        EmailError::Render(e)
+    })?;
+    Ok(QueuedEmail { to_email, template, lang, content, attachments })
+}
+
+// Ghi email đã render vào outbox; địa chỉ sai thì ghi thẳng trạng thái dead.
+// Không tự báo worker: caller gọi outbox::wake() sau khi commit, nếu không worker thức dậy sớm
+// sẽ chưa thấy email (transaction chưa commit) và ngủ thêm hết POLL_INTERVAL.
+async fn enqueue<'e, E>(executor: E, email: QueuedEmail) -> Result<(), EmailError>
+where
+    E: sqlx::Executor<'e, Database = MySql>,
+{
+    let (status, last_error) = match email.to_email.parse::<Mailbox>() {
+        Ok(_) => (outbox::STATUS_PENDING, None),
+        Err(e) => (outbox::STATUS_DEAD, Some(format!("Địa chỉ email không hợp lệ: {}", e))),
+    };
+
+    sqlx::query(
+        "INSERT INTO email_outbox (id, to_email, template, lang, subject, html_body, text_body, attachments, status, last_error)
+         VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?, ?)"
+    )
+    .bind(suid())
+    .bind(&email.to_email)
+    .bind(email.template)
+    .bind(email.lang)
+    .bind(&email.content.subject)
+    .bind(&email.content.html)
+    .bind(&email.content.text)
+    .bind((!email.attachments.is_empty()).then_some(sqlx::types::Json(&email.attachments)))
+    .bind(status)
+    .bind(last_error)
+    .execute(executor).await?;
+
+    Ok(())
+}
+
+// Render template rồi ghi vào outbox
+async fn send_template<'e, E>(executor: E, to_email: String, lang: &str, template: &'static str, ctx: Value) -> Result<(), EmailError>
+where
+    E: sqlx::Executor<'e, Database = MySql>,
+{
+    enqueue(executor, render_email(to_email, lang, template, ctx, vec![])?).await
+}
+
+/// Ngôn ngữ email của người nhận (cột users.language), khách chưa có tài khoản -> tiếng Việt
+pub async fn recipient_language<'e, E>(executor: E, email: &str) -> &'static str
+where
+    E: sqlx::Executor<'e, Database = MySql>,
+{
+    let lang: Option<(String,)> = sqlx::query_as("SELECT language FROM users WHERE email = ?")
+        .bind(email)
+        .fetch_optional(executor).await
+        .unwrap_or(None);
+    lang.map(|(l,)| normalize_language(&l)).unwrap_or(DEFAULT_LANGUAGE)
+}
+
+/// 1 dòng sản phẩm trong email đơn hàng
+#[derive(Debug, Clone)]
+pub struct OrderEmailItem {
+    pub name: String,
+    pub quantity: i32,
+    pub price: Money,
+}
+
+/// Thông tin giao hàng trong email đơn hàng
+#[derive(Debug, Clone, Serialize)]
+pub struct OrderEmailShipping {
+    pub name: String,
+    pub phone: String,
+    pub address: String,
+    pub note: Option<String>,
+}
+
+/// Nội dung đơn vừa đặt, dùng chung cho email xác nhận và email báo nhân viên
+#[derive(Debug, Clone)]
+pub struct OrderPlacedEmail {
+    pub order_id: String,
+    pub items: Vec<OrderEmailItem>,
+    pub shipping: OrderEmailShipping,
+    pub total: Money,
+}
+
+// Tiền trong template là chuỗi đã format theo ngôn ngữ người nhận
+fn order_items_ctx(items: &[OrderEmailItem], lang: &str) -> Result<Value, EmailError> {
+    let items = items
+        .iter()
+        .map(|item| Ok(json!({
+            "name": item.name,
+            "quantity": item.quantity,
+            "price": item.price.format(lang),
+            "line_total": item.price.checked_mul(item.quantity)?.format(lang),
+        })))
+        .collect::<Result<Vec<Value>, EmailError>>()?;
+    Ok(json!(items))
+}
+
+fn order_placed_ctx(order: &OrderPlacedEmail, lang: &str) -> Result<Value, EmailError> {
+    Ok(json!({
+        "order_id": order.order_id,
+        "items": order_items_ctx(&order.items, lang)?,
+        "shipping": order.shipping,
+        "total": order.total.format(lang),
+    }))
+}
+
+// Xác nhận đặt hàng thành công
+pub async fn send_order_confirmation_email<'e, E>(executor: E, to_email: String, lang: &str, order: &OrderPlacedEmail) -> Result<(), EmailError>
+where
+    E: sqlx::Executor<'e, Database = MySql>,
+{
+    let ctx = order_placed_ctx(order, normalize_language(lang))?;
+    send_template(executor, to_email, lang, "order_confirmation", ctx).await
+}
+
+// Đơn hàng đang giao
+fn order_shipping_email(to_email: String, lang: &str, order_id: String, items: &[OrderEmailItem], total_amount: Money) -> Result<QueuedEmail, EmailError> {
+    let lang = normalize_language(lang);
+    render_email(to_email, lang, "order_shipping", json!({
+        "order_id": order_id,
+        "items": order_items_ctx(items, lang)?,
+        "total": total_amount.format(lang),
+    }), vec![])
+}
+
+pub async fn send_order_shipping_email<'e, E>(executor: E, to_email: String, lang: &str, order_id: String, items: Vec<OrderEmailItem>, total_amount: Money) -> Result<(), EmailError>
+where
+    E: sqlx::Executor<'e, Database = MySql>,
+{
+    enqueue(executor, order_shipping_email(to_email, lang, order_id, &items, total_amount)?).await
+}
+
+// Cảm ơn sau khi giao thành công, kèm hóa đơn PDF của đơn (lưu tham chiếu, worker dựng file lúc gửi)
+fn order_thank_you_email(to_email: String, lang: &str, order_id: String, points: i32) -> Result<QueuedEmail, EmailError> {
+    let invoice = OutboxAttachment::Invoice { order_id: order_id.clone() };
+    render_email(to_email, lang, "order_thank_you", json!({ "order_id": order_id, "points": points }), vec![invoice])
+}
+
+pub async fn send_order_thank_you_email<'e, E>(executor: E, to_email: String, lang: &str, order_id: String, points: i32) -> Result<(), EmailError>
+where
+    E: sqlx::Executor<'e, Database = MySql>,
+{
+    enqueue(executor, order_thank_you_email(to_email, lang, order_id, points)?).await
+}
+
+// Sản phẩm đã có hàng trở lại
+pub async fn send_back_in_stock_email<'e, E>(executor: E, to_email: String, lang: &str, product_id: String, product_name: String) -> Result<(), EmailError>
+where
+    E: sqlx::Executor<'e, Database = MySql>,
+{
+    send_template(executor, to_email, lang, "back_in_stock", json!({
+        "product_id": product_id,
+        "product_name": product_name,
+    })).await
+}
+
+// Sản phẩm giảm giá
+pub async fn send_price_drop_email<'e, E>(executor: E, to_email: String, lang: &str, product_id: String, product_name: String, old_price: Money, new_price: Money) -> Result<(), EmailError>
+where
+    E: sqlx::Executor<'e, Database = MySql>,
+{
+    let lang = normalize_language(lang);
+    send_template(executor, to_email, lang, "price_drop", json!({
+        "product_id": product_id,
+        "product_name": product_name,
+        "old_price": old_price.format(lang),
+        "new_price": new_price.format(lang),
+    })).await
+}
+
+// Shop đã trả lời đánh giá
+pub async fn send_review_reply_email<'e, E>(executor: E, to_email: String, lang: &str, product_name: String, rating: i32, review_content: String, reply_content: String) -> Result<(), EmailError>
+where
+    E: sqlx::Executor<'e, Database = MySql>,
+{
+    send_template(executor, to_email, lang, "review_reply", json!({
+        "product_name": product_name,
+        "stars": "★".repeat(rating.clamp(0, 5) as usize),
+        "review_content": review_content,
+        "reply_content": reply_content,
+    })).await
+}
+
+// Link xác thực email
+pub async fn send_verify_email<'e, E>(executor: E, to_email: String, lang: &str, link: String, ttl_hours: i64) -> Result<(), EmailError>
+where
+    E: sqlx::Executor<'e, Database = MySql>,
+{
+    send_template(executor, to_email, lang, "verify_email", json!({ "link": link, "ttl_hours": ttl_hours })).await
+}
+
+// Link đặt lại mật khẩu
+pub async fn send_password_reset_email<'e, E>(executor: E, to_email: String, lang: &str, link: String, ttl_minutes: i64) -> Result<(), EmailError>
+where
+    E: sqlx::Executor<'e, Database = MySql>,
+{
+    send_template(executor, to_email, lang, "password_reset", json!({ "link": link, "ttl_minutes": ttl_minutes })).await
+}
+
+// Mã OTP đăng nhập
+pub async fn send_login_otp_email<'e, E>(executor: E, to_email: String, lang: &str, code: String, ttl_minutes: i64) -> Result<(), EmailError>
+where
+    E: sqlx::Executor<'e, Database = MySql>,
+{
+    send_template(executor, to_email, lang, "login_otp", json!({ "code": code, "ttl_minutes": ttl_minutes })).await
+}
+
+// Báo nhân viên: có đơn hàng mới
+pub async fn send_staff_new_order_email<'e, E>(executor: E, to_email: String, lang: &str, customer_email: &str, order: &OrderPlacedEmail) -> Result<(), EmailError>
+where
+    E: sqlx::Executor<'e, Database = MySql>,
+{
+    let mut ctx = order_placed_ctx(order, normalize_language(lang))?;
+    ctx["customer_email"] = json!(customer_email);
+    send_template(executor, to_email, lang, "staff_new_order", ctx).await
+}
+
+// Báo nhân viên: có tin nhắn liên hệ mới
+pub async fn send_staff_new_contact_email<'e, E>(executor: E, to_email: String, lang: &str, email: String, message: String, registered: bool) -> Result<(), EmailError>
+where
+    E: sqlx::Executor<'e, Database = MySql>,
+{
+    send_template(executor, to_email, lang, "staff_new_contact", json!({
+        "email": email,
+        "message": message,
+        "registered": registered,
+    })).await
+}
+
+// Báo nhân viên: sản phẩm sắp hết hàng
+pub async fn send_staff_low_stock_email<'e, E>(executor: E, to_email: String, lang: &str, product_id: String, product_name: String, stock: i32, threshold: i32) -> Result<(), EmailError>
+where
+    E: sqlx::Executor<'e, Database = MySql>,
+{
+    send_template(executor, to_email, lang, "staff_low_stock", json!({
+        "product_id": product_id,
+        "product_name": product_name,
+        "stock": stock,
+        "threshold": threshold,
+    })).await
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mail::MemoryTransport;
+    use crate::utils::invoice::{ self, Invoice, InvoiceLine, InvoiceSeller };
+    use rust_decimal::Decimal;
+
+    // Mailer riêng cho mỗi test (MAILER toàn cục chỉ gắn được 1 lần)
+    fn memory_mailer() -> (MemoryTransport, Mailer) {
+        let transport = MemoryTransport::default();
+        let mailer = Mailer::new(Arc::new(transport.clone()), Some("Shop <shop@example.com>"));
+        (transport, mailer)
+    }
+
+    // Như worker outbox: gửi nội dung đã ghi trong outbox kèm file đính kèm đã dựng
+    fn deliver_queued(mailer: &Mailer, email: &QueuedEmail, attachments: Vec<EmailAttachment>) {
+        mailer
+            .deliver(&email.to_email, &email.content.subject, &email.content.html, &email.content.text, attachments)
+            .unwrap();
+    }
+
+    fn items() -> Vec<OrderEmailItem> {
+        vec![
+            OrderEmailItem { name: "Laptop <Pro>".to_string(), quantity: 1, price: Money::vnd(23_990_000) },
+            OrderEmailItem { name: "Chuột".to_string(), quantity: 2, price: Money::vnd(1_000_000) },
+        ]
+    }
+
+    fn sample_invoice(order_id: &str) -> Invoice {
+        let lines: Vec<InvoiceLine> = items()
+            .into_iter()
+            .map(|i| InvoiceLine { name: i.name, quantity: i.quantity, price: i.price })
+            .collect();
+        let subtotal = lines
+            .iter()
+            .try_fold(Money::vnd(0), |total, l| total.checked_add(l.amount()?))
+            .unwrap();
+        Invoice {
+            order_id: order_id.to_string(),
+            user_id: "u1".to_string(),
+            status: "completed".to_string(),
+            created_at: None,
+            seller: InvoiceSeller { name: "ElectroShop".to_string(), ..Default::default() },
+            buyer_email: "khach@example.com".to_string(),
+            buyer_name: "Nguyễn Văn A".to_string(),
+            buyer_phone: "0900000000".to_string(),
+            shipping_address: "1 Lê Lợi, Q.1, TP.HCM".to_string(),
+            note: None,
+            lines,
+            subtotal,
+            discount: Money::vnd(0),
+            total: subtotal,
+            vat_rate: Decimal::from(10),
         }
-    });
-}
-
-// Hàm tạo nội dung HTML cho đơn hàng đang giao
-pub fn send_order_shipping_email(to_email: String, order_id: String, items_rows_html: String, total_amount: String) {
-    let subject = format!("📦 Đơn hàng #{} đang được vận chuyển!", order_id);
-    
-    // Dùng HTML để vẽ bảng
-    let body = format!(r#"
-        <!DOCTYPE html>
-        <html>
-        <head>
-            <style>
-                body {{ font-family: Arial, sans-serif; line-height: 1.6; color: #333; }}
-                .container {{ max-width: 600px; margin: 0 auto; padding: 20px; border: 1px solid #ddd; border-radius: 10px; }}
-                h2 {{ color: #2563EB; }}
-                table {{ width: 100%; border-collapse: collapse; margin-top: 20px; }}
-                th, td {{ padding: 12px; border-bottom: 1px solid #ddd; text-align: left; }}
-                th {{ background-color: #f8f9fa; }}
-                .total {{ text-align: right; font-size: 18px; font-weight: bold; color: #d9534f; margin-top: 20px; }}
-                .footer {{ margin-top: 30px; font-size: 12px; color: #777; text-align: center; }}
-            </style>
-        </head>
-        <body>
-            <div class="container">
-                <h2>Đơn hàng đang trên đường đến bạn!</h2>
-                <p>Xin chào,</p>
-                <p>Đơn hàng <b>#{}</b> của bạn đã được đóng gói và bàn giao cho đơn vị vận chuyển.</p>
-                
-                <h3>Chi tiết đơn hàng:</h3>
-                <table>
-                    <thead>
-                        <tr>
-                            <th>Sản phẩm</th>
-                            <th style="text-align: center;">SL</th>
-                            <th style="text-align: right;">Đơn giá</th>
-                            <th style="text-align: right;">Thành tiền</th>
-                        </tr>
-                    </thead>
-                    <tbody>
-                        {} 
-                    </tbody>
-                </table>
-
-                <div class="total">
-                    Tổng thanh toán: {}
-                </div>
-
-                <p>Vui lòng chú ý điện thoại để nhận hàng nhé!</p>
-                
-                <div class="footer">
-                    Trân trọng,<br>
-                    <b>ElectroShop Team</b>
-                </div>
-            </div>
-        </body>
-        </html>
-    "#, order_id, items_rows_html, total_amount);
-
-    send_email(to_email, subject, body);
-}
-
-// Hàm cảm ơn (cũng chuyển sang HTML cho đẹp)
-pub fn send_order_thank_you_email(to_email: String, order_id: String, points: i32) {
-    let subject = format!("✅ Cảm ơn bạn đã mua sắm (Đơn #{})", order_id);
-    let body = format!(r#"
-        <div style="font-family: Arial; padding: 20px; border: 1px solid #eee; border-radius: 8px;">
-            <h2 style="color: #16a34a;">Giao hàng thành công!</h2>
-            <p>Xin chào,</p>
-            <p>Cảm ơn bạn đã xác nhận nhận hàng thành công đơn <b>#{}</b>.</p>
-            <p style="background-color: #ecfdf5; color: #065f46; padding: 15px; border-radius: 5px; text-align: center; font-weight: bold;">
-                🎉 Bạn đã được cộng +{} điểm thưởng vào ví.
-            </p>
-            <p>Hy vọng bạn hài lòng với sản phẩm. Đừng quên để lại đánh giá nhé!</p>
-            <br>
-            <p>ElectroShop Team</p>
-        </div>
-    "#, order_id, points);
-
-    send_email(to_email, subject, body);
-}
\ No newline at end of file
+    }
+
+    #[test]
+    fn shipping_email_is_delivered() {
+        let (transport, mailer) = memory_mailer();
+        let email = order_shipping_email("khach@example.com".to_string(), "vi", "ORD1".to_string(), &items(), Money::vnd(25_990_000)).unwrap();
+        assert_eq!(email.template, "order_shipping");
+        assert!(email.attachments.is_empty());
+        deliver_queued(&mailer, &email, vec![]);
+
+        let sent = transport.take();
+        assert_eq!(sent.len(), 1);
+        assert_eq!(sent[0].to.email.to_string(), "khach@example.com");
+        assert_eq!(sent[0].from.email.to_string(), "shop@example.com");
+        assert!(sent[0].subject.contains("#ORD1"), "{}", sent[0].subject);
+        assert!(sent[0].html.contains("25.990.000 ₫"));
+        // Tên sản phẩm được escape trong HTML, giữ nguyên trong bản text
+        assert!(sent[0].html.contains("Laptop &lt;Pro&gt;"));
+        assert!(sent[0].text.contains("Laptop <Pro>"));
+        assert!(transport.take().is_empty());
+    }
+
+    #[test]
+    fn shipping_email_formats_money_in_recipient_language() {
+        let (transport, mailer) = memory_mailer();
+        let email = order_shipping_email("customer@example.com".to_string(), "en", "ORD2".to_string(), &items(), Money::vnd(25_990_000)).unwrap();
+        deliver_queued(&mailer, &email, vec![]);
+
+        let sent = transport.messages();
+        assert_eq!(sent[0].subject, "📦 Order #ORD2 is on its way!");
+        assert!(sent[0].html.contains("₫25,990,000"));
+    }
+
+    #[test]
+    fn thank_you_email_is_delivered_with_invoice() {
+        let (transport, mailer) = memory_mailer();
+        let email = order_thank_you_email("khach@example.com".to_string(), "vi", "ORD1".to_string(), 25_990).unwrap();
+        assert_eq!(email.template, "order_thank_you");
+        assert!(matches!(
+            email.attachments.as_slice(),
+            [OutboxAttachment::Invoice { order_id }] if order_id == "ORD1"
+        ));
+
+        let data = sample_invoice("ORD1");
+        let pdf = invoice::render_pdf(&data).unwrap();
+        deliver_queued(&mailer, &email, vec![EmailAttachment {
+            filename: data.filename(),
+            content_type: "application/pdf".to_string(),
+            data: pdf,
+        }]);
+
+        let sent = transport.take();
+        assert_eq!(sent.len(), 1);
+        assert!(sent[0].subject.contains("#ORD1"));
+        assert!(sent[0].text.contains("25990"));
+        assert_eq!(sent[0].attachments.len(), 1);
+        assert_eq!(sent[0].attachments[0].filename, "hoa-don-ORD1.pdf");
+        assert!(sent[0].attachments[0].data.starts_with(b"%PDF"));
+        // Dựng được MIME multipart/mixed để gửi SMTP
+        assert!(sent[0].to_message().is_ok());
+    }
+
+    #[test]
+    fn render_error_is_returned() {
+        let err = render_email("khach@example.com".to_string(), "vi", "no_such_template", json!({}), vec![]).unwrap_err();
+        assert!(matches!(err, EmailError::Render(_)));
+    }
+
+    #[test]
+    fn invalid_recipient_is_rejected_permanently() {
+        let (transport, mailer) = memory_mailer();
+        let err = mailer.deliver("not-an-email", "s", "<p>h</p>", "t", vec![]).unwrap_err();
+        assert!(err.permanent);
+        assert!(transport.messages().is_empty());
+    }
+}
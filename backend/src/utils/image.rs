@@ -0,0 +1,139 @@
+// src/utils/image.rs
+// Xử lý ảnh upload: xoay theo EXIF, tạo các bản thumb/medium/large dạng WebP.
+// Encode lại từ pixel nên toàn bộ metadata (EXIF, GPS...) của ảnh gốc bị loại bỏ.
+use image::imageops::FilterType;
+use image::{ DynamicImage, ImageDecoder, ImageReader, Limits };
+use serde::{ Deserialize, Serialize };
+use std::io::Cursor;
+
+const WEBP_QUALITY: f32 = 80.0;
+// Chặn ảnh "bom giải nén" (file nhỏ nhưng kích thước pixel khổng lồ)
+const MAX_SOURCE_DIMENSION: u32 = 12_000;
+const MAX_DECODE_ALLOC: u64 = 512 * 1024 * 1024;
+
+/// (tên, cạnh dài tối đa). Ảnh nhỏ hơn thì giữ nguyên kích thước, không phóng to
+pub const VARIANTS: [(&str, u32); 3] = [("thumb", 320), ("medium", 800), ("large", 1600)];
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImageVariant {
+    pub url: String,
+    pub width: u32,
+    pub height: u32,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImageVariants {
+    pub thumb: ImageVariant,
+    pub medium: ImageVariant,
+    pub large: ImageVariant,
+}
+
+/// Ảnh đã lưu. Dữ liệu cũ chỉ là chuỗi URL -> đọc được cả 2 dạng, ghi ra luôn là object
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(from = "StoredImageRepr")]
+pub struct StoredImage {
+    pub url: String, // Bản lớn nhất (large), dùng làm ảnh mặc định
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub width: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub height: Option<u32>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub variants: Option<ImageVariants>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum StoredImageRepr {
+    Url(String),
+    Full {
+        url: String,
+        width: Option<u32>,
+        height: Option<u32>,
+        variants: Option<ImageVariants>,
+    },
+}
+
+impl From<StoredImageRepr> for StoredImage {
+    fn from(repr: StoredImageRepr) -> Self {
+        match repr {
+            StoredImageRepr::Url(url) => StoredImage { url, width: None, height: None, variants: None },
+            StoredImageRepr::Full { url, width, height, variants } => StoredImage { url, width, height, variants },
+        }
+    }
+}
+
+/// Một bản đã encode, chờ ghi ra đĩa
+pub struct EncodedVariant {
+    pub name: &'static str,
+    pub width: u32,
+    pub height: u32,
+    pub data: Vec<u8>,
+}
+
+#[derive(Debug)]
+pub enum ImageError {
+    Decode(String),
+    TooLarge,
+}
+
+impl std::fmt::Display for ImageError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ImageError::Decode(e) => write!(f, "Không đọc được ảnh: {}", e),
+            ImageError::TooLarge => write!(f, "Ảnh vượt quá {0}x{0} pixel", MAX_SOURCE_DIMENSION),
+        }
+    }
+}
+
+/// Giải mã + tạo các bản WebP. Tốn CPU -> gọi trong spawn_blocking
+pub fn process_image(data: &[u8]) -> Result<Vec<EncodedVariant>, ImageError> {
+    let mut limits = Limits::default();
+    limits.max_image_width = Some(MAX_SOURCE_DIMENSION);
+    limits.max_image_height = Some(MAX_SOURCE_DIMENSION);
+    limits.max_alloc = Some(MAX_DECODE_ALLOC);
+
+    let mut reader = ImageReader::new(Cursor::new(data))
+        .with_guessed_format()
+        .map_err(|e| ImageError::Decode(e.to_string()))?;
+    reader.limits(limits);
+
+    let mut decoder = reader.into_decoder().map_err(map_decode_error)?;
+    // Ảnh chụp điện thoại thường lưu xoay qua EXIF -> xoay thật trước khi bỏ EXIF
+    let orientation = decoder.orientation().ok();
+    let mut img = DynamicImage::from_decoder(decoder).map_err(map_decode_error)?;
+    if let Some(orientation) = orientation {
+        img.apply_orientation(orientation);
+    }
+
+    // WebP chỉ nhận RGB/RGBA 8 bit
+    let img = if img.color().has_alpha() {
+        DynamicImage::ImageRgba8(img.to_rgba8())
+    } else {
+        DynamicImage::ImageRgb8(img.to_rgb8())
+    };
+
+    let mut out = Vec::with_capacity(VARIANTS.len());
+    for (name, max_side) in VARIANTS {
+        let resized = if img.width().max(img.height()) > max_side {
+            img.resize(max_side, max_side, FilterType::Lanczos3)
+        } else {
+            img.clone()
+        };
+        let encoder = webp::Encoder::from_image(&resized).map_err(|e| ImageError::Decode(e.to_string()))?;
+        out.push(EncodedVariant {
+            name,
+            width: resized.width(),
+            height: resized.height(),
+            data: encoder.encode(WEBP_QUALITY).to_vec(),
+        });
+    }
+
+    Ok(out)
+}
+
+fn map_decode_error(e: image::ImageError) -> ImageError {
+    match e {
+        image::ImageError::Limits(_) => ImageError::TooLarge,
+        other => ImageError::Decode(other.to_string()),
+    }
+}
@@ -0,0 +1,482 @@
+// src/utils/invoice.rs
+// Hóa đơn bán hàng PDF theo mẫu hóa đơn GTGT: thông tin cửa hàng lấy từ settings, người mua / giao hàng
+// từ orders, các dòng hàng từ order_items. Giá bán đã gồm thuế -> tách tiền trước thuế và thuế GTGT
+// theo invoice_vat_rate. Font DejaVu nhúng sẵn trong binary để in được tiếng Việt có dấu; chỉ nhúng 1 font
+// (chữ đậm vẽ bằng tô + viền) cho file nhẹ vì PDF còn được đính kèm email.
+use crate::utils::Money;
+use crate::utils::money::MoneyError;
+use printpdf::{
+    lopdf, path::PaintMode, Color, IndirectFontRef, Line, Mm, PdfDocument, PdfDocumentReference, PdfLayerReference,
+    Point, Rect, Rgb, TextRenderingMode,
+};
+use rust_decimal::Decimal;
+use sqlx::{ FromRow, MySqlConnection };
+use std::io::Cursor;
+
+const FONT: &[u8] = include_bytes!("../../assets/fonts/DejaVuSans.ttf");
+
+pub const SETTING_COMPANY_NAME: &str = "company_name";
+pub const SETTING_COMPANY_ADDRESS: &str = "company_address";
+pub const SETTING_COMPANY_TAX_CODE: &str = "company_tax_code";
+pub const SETTING_VAT_RATE: &str = "invoice_vat_rate";
+
+// Chưa cấu hình invoice_vat_rate thì dùng thuế suất phổ thông 10%
+const DEFAULT_VAT_RATE: i64 = 10;
+
+/// Đơn vị bán hàng in trên hóa đơn
+#[derive(Debug, Clone, Default)]
+pub struct InvoiceSeller {
+    pub name: String,
+    pub address: String,
+    pub tax_code: String,
+    pub phone: String,
+    pub email: String,
+}
+
+/// 1 dòng hàng (đơn giá tại thời điểm đặt)
+#[derive(Debug, Clone, FromRow)]
+pub struct InvoiceLine {
+    pub name: String,
+    pub quantity: i32,
+    pub price: Money,
+}
+
+impl InvoiceLine {
+    pub fn amount(&self) -> Result<Money, MoneyError> {
+        self.price.checked_mul(self.quantity)
+    }
+}
+
+#[derive(Debug, FromRow)]
+struct InvoiceOrderRow {
+    user_id: String,
+    email: String,
+    status: String,
+    total_amount: Money,
+    discount_amount: Option<Money>,
+    final_amount: Money,
+    shipping_name: Option<String>,
+    shipping_phone: Option<String>,
+    shipping_address: Option<String>,
+    note: Option<String>,
+    created_at: Option<chrono::NaiveDateTime>,
+}
+
+#[derive(Debug, Clone)]
+pub struct Invoice {
+    pub order_id: String,
+    pub user_id: String,
+    pub status: String,
+    pub created_at: Option<chrono::NaiveDateTime>,
+    pub seller: InvoiceSeller,
+    pub buyer_email: String,
+    pub buyer_name: String,
+    pub buyer_phone: String,
+    pub shipping_address: String,
+    pub note: Option<String>,
+    pub lines: Vec<InvoiceLine>,
+    pub subtotal: Money,   // orders.total_amount
+    pub discount: Money,   // orders.discount_amount
+    pub total: Money,      // orders.final_amount (đã gồm thuế)
+    pub vat_rate: Decimal, // %
+}
+
+impl Invoice {
+    /// Tiền hàng chưa thuế = tổng thanh toán / (1 + thuế suất), làm tròn đến đồng
+    pub fn pre_tax_amount(&self) -> Money {
+        self.total.scale(Decimal::ONE_HUNDRED / (Decimal::ONE_HUNDRED + self.vat_rate)).round()
+    }
+
+    pub fn vat_amount(&self) -> Result<Money, MoneyError> {
+        self.total.checked_sub(self.pre_tax_amount())
+    }
+
+    pub fn filename(&self) -> String {
+        format!("hoa-don-{}.pdf", self.order_id)
+    }
+}
+
+/// Đọc đủ dữ liệu để in hóa đơn. Không có đơn -> None (quyền xem do nơi gọi kiểm tra qua `user_id`)
+pub async fn load_invoice(conn: &mut MySqlConnection, order_id: &str) -> Result<Option<Invoice>, sqlx::Error> {
+    let order: Option<InvoiceOrderRow> = sqlx
+        ::query_as(
+            "SELECT o.user_id, u.email, o.status, o.total_amount, o.discount_amount, o.final_amount,
+                    o.shipping_name, o.shipping_phone, o.shipping_address, o.note, o.created_at
+             FROM orders o
+             JOIN users u ON o.user_id = u.id
+             WHERE o.id = ?"
+        )
+        .bind(order_id)
+        .fetch_optional(&mut *conn).await?;
+    let order = match order {
+        Some(order) => order,
+        None => return Ok(None),
+    };
+
+    let lines: Vec<InvoiceLine> = sqlx
+        ::query_as(
+            "SELECT p.name, oi.quantity, oi.price
+             FROM order_items oi
+             JOIN products p ON oi.product_id = p.id
+             WHERE oi.order_id = ?
+             ORDER BY oi.id"
+        )
+        .bind(order_id)
+        .fetch_all(&mut *conn).await?;
+
+    let settings: Vec<(String, Option<String>)> = sqlx
+        ::query_as(
+            "SELECT id, value FROM settings
+             WHERE id IN ('site_name', 'hotline', 'contact_email', 'company_name', 'company_address',
+                          'company_tax_code', 'invoice_vat_rate')"
+        )
+        .fetch_all(&mut *conn).await?;
+    let setting = |key: &str| {
+        settings
+            .iter()
+            .find(|(id, _)| id == key)
+            .and_then(|(_, v)| v.as_deref())
+            .map(str::trim)
+            .unwrap_or("")
+            .to_string()
+    };
+
+    // Chưa khai báo tên pháp nhân thì in tên cửa hàng
+    let mut seller_name = setting(SETTING_COMPANY_NAME);
+    if seller_name.is_empty() {
+        seller_name = setting("site_name");
+    }
+    let vat_rate = setting(SETTING_VAT_RATE)
+        .parse::<Decimal>()
+        .ok()
+        .filter(|r| !r.is_sign_negative())
+        .unwrap_or(Decimal::from(DEFAULT_VAT_RATE));
+
+    Ok(Some(Invoice {
+        order_id: order_id.to_string(),
+        user_id: order.user_id,
+        status: order.status,
+        created_at: order.created_at,
+        seller: InvoiceSeller {
+            name: seller_name,
+            address: setting(SETTING_COMPANY_ADDRESS),
+            tax_code: setting(SETTING_COMPANY_TAX_CODE),
+            phone: setting("hotline"),
+            email: setting("contact_email"),
+        },
+        buyer_email: order.email,
+        buyer_name: order.shipping_name.unwrap_or_default(),
+        buyer_phone: order.shipping_phone.unwrap_or_default(),
+        shipping_address: order.shipping_address.unwrap_or_default(),
+        note: order.note.filter(|n| !n.trim().is_empty()),
+        lines,
+        subtotal: order.total_amount,
+        discount: order.discount_amount.unwrap_or(Money::vnd(0)),
+        total: order.final_amount,
+        vat_rate,
+    }))
+}
+
+// --- VẼ PDF ---
+
+const PAGE_W: f32 = 210.0;
+const PAGE_H: f32 = 297.0;
+const MARGIN: f32 = 15.0;
+const RIGHT: f32 = PAGE_W - MARGIN;
+
+// Cột bảng hàng hóa (mm): STT | Tên hàng | SL (căn phải) | Đơn giá (căn phải) | Thành tiền (căn phải)
+const COL_NO: f32 = MARGIN + 2.0;
+const COL_NAME: f32 = MARGIN + 11.0;
+const COL_NAME_END: f32 = 118.0;
+const COL_QTY_END: f32 = 132.0;
+const COL_PRICE_END: f32 = 162.0;
+const COL_AMOUNT_END: f32 = RIGHT - 2.0;
+
+const BODY_SIZE: f32 = 9.0;
+const SMALL_SIZE: f32 = 7.5;
+const LINE_GAP: f32 = 4.6;
+
+fn pt_to_mm(pt: f32) -> f32 {
+    pt * 25.4 / 72.0
+}
+
+// Font đã nạp vào PDF + bảng glyph để đo độ rộng chữ
+struct Font {
+    pdf: IndirectFontRef,
+    face: ttf_parser::Face<'static>,
+}
+
+impl Font {
+    fn load(doc: &PdfDocumentReference, data: &'static [u8]) -> Result<Self, String> {
+        let pdf = doc.add_external_font(Cursor::new(data)).map_err(|e| e.to_string())?;
+        let face = ttf_parser::Face::parse(data, 0).map_err(|e| e.to_string())?;
+        Ok(Font { pdf, face })
+    }
+
+    /// Độ rộng (mm) của chuỗi ở cỡ chữ `size` (pt)
+    fn width(&self, text: &str, size: f32) -> f32 {
+        let units = self.face.units_per_em() as f32;
+        let fallback = units / 2.0;
+        let advance: f32 = text
+            .chars()
+            .map(|c| {
+                self.face
+                    .glyph_index(c)
+                    .and_then(|g| self.face.glyph_hor_advance(g))
+                    .map(|a| a as f32)
+                    .unwrap_or(fallback)
+            })
+            .sum();
+        pt_to_mm(advance / units * size)
+    }
+
+    /// Ngắt dòng theo từ cho vừa `max_width` (mm); từ quá dài thì cắt theo ký tự
+    fn wrap(&self, text: &str, size: f32, max_width: f32) -> Vec<String> {
+        let mut lines: Vec<String> = Vec::new();
+        let mut current = String::new();
+        for word in text.split_whitespace() {
+            let candidate = if current.is_empty() { word.to_string() } else { format!("{} {}", current, word) };
+            if self.width(&candidate, size) <= max_width {
+                current = candidate;
+                continue;
+            }
+            if !current.is_empty() {
+                lines.push(std::mem::take(&mut current));
+            }
+            for c in word.chars() {
+                current.push(c);
+                if self.width(&current, size) > max_width && current.chars().count() > 1 {
+                    current.pop();
+                    lines.push(std::mem::take(&mut current));
+                    current.push(c);
+                }
+            }
+        }
+        if !current.is_empty() || lines.is_empty() {
+            lines.push(current);
+        }
+        lines
+    }
+}
+
+// Con trỏ vẽ: `y` tính từ mép dưới trang (gốc tọa độ PDF), hết chỗ thì sang trang mới
+struct Writer {
+    doc: PdfDocumentReference,
+    layer: PdfLayerReference,
+    font: Font,
+    y: f32,
+}
+
+impl Writer {
+    fn new(title: &str) -> Result<Self, String> {
+        let (doc, page, layer) = PdfDocument::new(title, Mm(PAGE_W), Mm(PAGE_H), "Layer 1");
+        let font = Font::load(&doc, FONT)?;
+        let layer = doc.get_page(page).get_layer(layer);
+        Ok(Writer { doc, layer, font, y: PAGE_H - MARGIN })
+    }
+
+    fn new_page(&mut self) {
+        let (page, layer) = self.doc.add_page(Mm(PAGE_W), Mm(PAGE_H), "Layer 1");
+        self.layer = self.doc.get_page(page).get_layer(layer);
+        self.y = PAGE_H - MARGIN;
+    }
+
+    /// Còn đủ `height` mm trên trang hiện tại không
+    fn fits(&self, height: f32) -> bool {
+        self.y - height >= MARGIN
+    }
+
+    /// Chữ đậm: tô + viền mảnh theo cỡ chữ
+    fn text(&self, text: &str, size: f32, x: f32, y: f32, bold: bool) {
+        if bold {
+            self.layer.set_text_rendering_mode(TextRenderingMode::FillStroke);
+            self.layer.set_outline_thickness(size * 0.04);
+        }
+        self.layer.use_text(text, size, Mm(x), Mm(y), &self.font.pdf);
+        if bold {
+            self.layer.set_text_rendering_mode(TextRenderingMode::Fill);
+        }
+    }
+
+    fn text_right(&self, text: &str, size: f32, right: f32, y: f32, bold: bool) {
+        let x = right - self.font.width(text, size);
+        self.text(text, size, x, y, bold);
+    }
+
+    fn text_center(&self, text: &str, size: f32, y: f32, bold: bool) {
+        let x = (PAGE_W - self.font.width(text, size)) / 2.0;
+        self.text(text, size, x, y, bold);
+    }
+
+    fn hline(&self, y: f32, thickness: f32) {
+        self.layer.set_outline_thickness(thickness);
+        self.layer.add_line(Line {
+            points: vec![(Point::new(Mm(MARGIN), Mm(y)), false), (Point::new(Mm(RIGHT), Mm(y)), false)],
+            is_closed: false,
+        });
+    }
+
+    fn shade(&self, top: f32, bottom: f32) {
+        self.layer.set_fill_color(Color::Rgb(Rgb::new(0.93, 0.93, 0.93, None)));
+        self.layer.add_rect(Rect::new(Mm(MARGIN), Mm(bottom), Mm(RIGHT), Mm(top)).with_mode(PaintMode::Fill));
+        self.layer.set_fill_color(Color::Rgb(Rgb::new(0.0, 0.0, 0.0, None)));
+    }
+
+    /// Dòng "Nhãn: giá trị", giá trị dài tự xuống dòng
+    fn field(&mut self, label: &str, value: &str) {
+        let label = format!("{} ", label);
+        let label_w = self.font.width(&label, BODY_SIZE);
+        let lines = self.font.wrap(value, BODY_SIZE, RIGHT - MARGIN - label_w);
+        if !self.fits(LINE_GAP * lines.len() as f32) {
+            self.new_page();
+        }
+        self.y -= LINE_GAP;
+        self.text(&label, BODY_SIZE, MARGIN, self.y, true);
+        for (i, line) in lines.iter().enumerate() {
+            if i > 0 {
+                self.y -= LINE_GAP;
+            }
+            self.text(line, BODY_SIZE, MARGIN + label_w, self.y, false);
+        }
+    }
+
+    fn table_header(&mut self) {
+        let top = self.y;
+        self.shade(top, top - 10.0);
+        let (vi, en) = (top - 4.3, top - 8.0);
+        for (x_or_right, right_align, title, sub) in [
+            (COL_NO, false, "STT", "No."),
+            (COL_NAME, false, "Tên hàng hóa, dịch vụ", "Description"),
+            (COL_QTY_END, true, "SL", "Qty"),
+            (COL_PRICE_END, true, "Đơn giá", "Unit price"),
+            (COL_AMOUNT_END, true, "Thành tiền", "Amount"),
+        ] {
+            if right_align {
+                self.text_right(title, BODY_SIZE, x_or_right, vi, true);
+                self.text_right(sub, SMALL_SIZE, x_or_right, en, false);
+            } else {
+                self.text(title, BODY_SIZE, x_or_right, vi, true);
+                self.text(sub, SMALL_SIZE, x_or_right, en, false);
+            }
+        }
+        self.y = top - 10.0;
+        self.hline(self.y, 0.6);
+    }
+}
+
+/// Dựng file PDF. Đồng bộ và dùng Rc bên trong -> gọi trong spawn_blocking, không giữ qua `.await`
+pub fn render_pdf(invoice: &Invoice) -> Result<Vec<u8>, String> {
+    let mut w = Writer::new(&format!("Hóa đơn {}", invoice.order_id))?;
+
+    // 1. Đơn vị bán hàng
+    let seller = &invoice.seller;
+    w.y -= 5.0;
+    w.text(&seller.name, 12.0, MARGIN, w.y, true);
+    if !seller.tax_code.is_empty() {
+        w.field("Mã số thuế (Tax code):", &seller.tax_code);
+    }
+    if !seller.address.is_empty() {
+        w.field("Địa chỉ (Address):", &seller.address);
+    }
+    let contact: Vec<String> = [
+        (!seller.phone.is_empty()).then(|| format!("Điện thoại (Tel): {}", seller.phone)),
+        (!seller.email.is_empty()).then(|| format!("Email: {}", seller.email)),
+    ]
+    .into_iter()
+    .flatten()
+    .collect();
+    if !contact.is_empty() {
+        w.y -= LINE_GAP;
+        w.text(&contact.join("   "), BODY_SIZE, MARGIN, w.y, false);
+    }
+    w.y -= 3.0;
+    w.hline(w.y, 0.8);
+
+    // 2. Tiêu đề + số hóa đơn
+    w.y -= 10.0;
+    w.text_center("HÓA ĐƠN BÁN HÀNG", 16.0, w.y, true);
+    w.y -= 5.5;
+    w.text_center("(SALES INVOICE - VAT INCLUDED)", SMALL_SIZE + 0.5, w.y, false);
+    w.y -= 6.0;
+    let date = invoice.created_at.map(|d| d.format("%d/%m/%Y %H:%M").to_string()).unwrap_or_default();
+    w.text_center(&format!("Số (No.): {}      Ngày (Date): {}", invoice.order_id, date), BODY_SIZE, w.y, false);
+    if invoice.status == "cancelled" {
+        // Đơn hủy vẫn in được để đối chiếu, ghi rõ trạng thái
+        w.y -= 5.0;
+        w.text_center("ĐƠN HÀNG ĐÃ HỦY (CANCELLED)", BODY_SIZE, w.y, true);
+    }
+    w.y -= 4.0;
+
+    // 3. Người mua / giao hàng
+    w.field("Người mua hàng (Buyer):", &invoice.buyer_name);
+    w.field("Email:", &invoice.buyer_email);
+    w.field("Điện thoại (Tel):", &invoice.buyer_phone);
+    w.field("Địa chỉ giao hàng (Shipping address):", &invoice.shipping_address);
+    if let Some(note) = &invoice.note {
+        w.field("Ghi chú (Note):", note);
+    }
+    w.y -= 5.0;
+
+    // 4. Bảng hàng hóa, sang trang thì vẽ lại tiêu đề bảng
+    if !w.fits(10.0 + 8.0) {
+        w.new_page();
+    }
+    w.table_header();
+    for (i, line) in invoice.lines.iter().enumerate() {
+        let name_lines = w.font.wrap(&line.name, BODY_SIZE, COL_NAME_END - COL_NAME - 2.0);
+        let row_h = LINE_GAP * name_lines.len() as f32 + 2.4;
+        if !w.fits(row_h) {
+            w.new_page();
+            w.table_header();
+        }
+        let first = w.y - LINE_GAP;
+        w.text(&(i + 1).to_string(), BODY_SIZE, COL_NO, first, false);
+        for (j, name) in name_lines.iter().enumerate() {
+            w.text(name, BODY_SIZE, COL_NAME, first - LINE_GAP * j as f32, false);
+        }
+        w.text_right(&line.quantity.to_string(), BODY_SIZE, COL_QTY_END, first, false);
+        w.text_right(&line.price.to_string(), BODY_SIZE, COL_PRICE_END, first, false);
+        let amount = line.amount().map_err(|e| e.to_string())?;
+        w.text_right(&amount.to_string(), BODY_SIZE, COL_AMOUNT_END, first, false);
+        w.y -= row_h;
+        w.hline(w.y, 0.2);
+    }
+
+    // 5. Tổng tiền (giá đã gồm thuế -> tách ngược tiền thuế)
+    let rate = invoice.vat_rate.normalize();
+    let totals = [
+        ("Cộng tiền hàng (Subtotal):".to_string(), invoice.subtotal.to_string(), false),
+        ("Chiết khấu (Discount):".to_string(), (-invoice.discount).to_string(), false),
+        ("Tiền hàng chưa thuế (Pre-tax amount):".to_string(), invoice.pre_tax_amount().to_string(), false),
+        (format!("Thuế GTGT {}% (VAT):", rate), invoice.vat_amount().map_err(|e| e.to_string())?.to_string(), false),
+        ("Tổng thanh toán (Total):".to_string(), invoice.total.to_string(), true),
+    ];
+    if !w.fits(LINE_GAP * totals.len() as f32 + 16.0) {
+        w.new_page();
+    }
+    w.y -= 2.0;
+    for (label, value, bold) in totals {
+        w.y -= LINE_GAP + if bold { 1.5 } else { 0.0 };
+        let size = if bold { BODY_SIZE + 1.5 } else { BODY_SIZE };
+        // Số tiền lớn lấn sang cột đơn giá thì đẩy nhãn sang trái
+        let label_end = COL_PRICE_END.min(COL_AMOUNT_END - w.font.width(&value, size) - 3.0);
+        w.text_right(&label, size, label_end, w.y, bold);
+        w.text_right(&value, size, COL_AMOUNT_END, w.y, bold);
+    }
+
+    w.y -= 10.0;
+    w.text_center("Giá bán đã bao gồm thuế GTGT. Cảm ơn quý khách đã mua hàng!", SMALL_SIZE + 0.5, w.y, false);
+
+    let bytes = w.doc.save_to_bytes().map_err(|e| e.to_string())?;
+    compress(&bytes)
+}
+
+// printpdf để nguyên font (~750KB) không nén -> nén lại mọi stream bằng Flate
+fn compress(pdf: &[u8]) -> Result<Vec<u8>, String> {
+    let mut doc = lopdf::Document::load_mem(pdf).map_err(|e| e.to_string())?;
+    doc.compress();
+    let mut out = Vec::new();
+    doc.save_to(&mut out).map_err(|e| e.to_string())?;
+    Ok(out)
+}
+
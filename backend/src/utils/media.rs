@@ -0,0 +1,187 @@
+// src/utils/media.rs
+// Thư viện media: chống trùng file theo hash nội dung, ghi nhận nơi đang dùng (product/review/return)
+// và dọn các file không còn ai tham chiếu sau thời gian ân hạn.
+use crate::storage::BlobStore;
+use crate::utils::image::StoredImage;
+use crate::utils::suid;
+use sha2::{ Digest, Sha256 };
+use sqlx::{ MySql, MySqlPool, QueryBuilder, Transaction };
+use std::collections::HashSet;
+use std::sync::Arc;
+use std::time::Duration;
+
+pub const REF_PRODUCT: &str = "product";
+pub const REF_REVIEW: &str = "review";
+
+// Mỗi lượt GC xử lý tối đa bấy nhiêu media, còn lại để lượt sau
+const GC_BATCH_SIZE: i64 = 200;
+const GC_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+/// SHA-256 (hex) của file gốc
+pub fn content_hash(data: &[u8]) -> String {
+    hex::encode(Sha256::digest(data))
+}
+
+pub struct NewMedia<'a> {
+    pub content_hash: &'a str,
+    pub image: &'a StoredImage,
+    pub blob_keys: &'a [String],
+    pub original_name: &'a str,
+    pub content_type: &'a str,
+    pub size: usize,
+    pub uploaded_by: &'a str,
+}
+
+/// Đã có file cùng nội dung -> trả về ảnh cũ và gia hạn (để GC không xóa ngay sau khi upload lại)
+pub async fn find_by_hash(db: &MySqlPool, hash: &str) -> Result<Option<(String, StoredImage)>, sqlx::Error> {
+    let touched = sqlx::query("UPDATE media SET last_used_at = NOW() WHERE content_hash = ?")
+        .bind(hash)
+        .execute(db).await?;
+    if touched.rows_affected() == 0 {
+        return Ok(None);
+    }
+    let row: Option<(String, sqlx::types::Json<StoredImage>)> = sqlx::query_as(
+        "SELECT id, image FROM media WHERE content_hash = ?"
+    )
+    .bind(hash)
+    .fetch_optional(db).await?;
+    Ok(row.map(|(id, image)| (id, image.0)))
+}
+
+/// Ghi media mới. Trả về None nếu request khác vừa ghi cùng hash (upload song song) -> gọi find_by_hash
+pub async fn insert_media(db: &MySqlPool, media: NewMedia<'_>) -> Result<Option<String>, sqlx::Error> {
+    let id = suid();
+    let res = sqlx::query(
+        "INSERT IGNORE INTO media (id, content_hash, url, image, blob_keys, original_name, content_type, size, uploaded_by)
+         VALUES (?, ?, ?, ?, ?, ?, ?, ?, ?)"
+    )
+    .bind(&id)
+    .bind(media.content_hash)
+    .bind(&media.image.url)
+    .bind(sqlx::types::Json(media.image))
+    .bind(sqlx::types::Json(media.blob_keys))
+    .bind(media.original_name)
+    .bind(media.content_type)
+    .bind(media.size as i64)
+    .bind(media.uploaded_by)
+    .execute(db).await?;
+    Ok(if res.rows_affected() == 1 { Some(id) } else { None })
+}
+
+/// Đặt lại danh sách media mà (ref_type, ref_id) đang dùng, theo URL ảnh chính.
+/// URL không có trong bảng media (ảnh ngoài, dữ liệu cũ trước khi có media) thì bỏ qua.
+pub async fn sync_refs(
+    tx: &mut Transaction<'_, MySql>,
+    ref_type: &str,
+    ref_id: &str,
+    urls: &[String]
+) -> Result<(), sqlx::Error> {
+    let old: Vec<(String,)> = sqlx::query_as(
+        "SELECT media_id FROM media_refs WHERE ref_type = ? AND ref_id = ?"
+    )
+    .bind(ref_type)
+    .bind(ref_id)
+    .fetch_all(&mut **tx).await?;
+
+    sqlx::query("DELETE FROM media_refs WHERE ref_type = ? AND ref_id = ?")
+        .bind(ref_type)
+        .bind(ref_id)
+        .execute(&mut **tx).await?;
+
+    let mut new: Vec<(String,)> = Vec::new();
+    if !urls.is_empty() {
+        let mut qb: QueryBuilder<MySql> = QueryBuilder::new("SELECT id FROM media WHERE url IN (");
+        let mut sep = qb.separated(", ");
+        for url in urls {
+            sep.push_bind(url);
+        }
+        sep.push_unseparated(")");
+        new = qb.build_query_as().fetch_all(&mut **tx).await?;
+    }
+
+    if !new.is_empty() {
+        let mut qb: QueryBuilder<MySql> = QueryBuilder::new(
+            "INSERT IGNORE INTO media_refs (media_id, ref_type, ref_id) "
+        );
+        qb.push_values(&new, |mut b, (media_id,)| {
+            b.push_bind(media_id).push_bind(ref_type).push_bind(ref_id);
+        });
+        qb.build().execute(&mut **tx).await?;
+    }
+
+    // Media vừa bị bỏ hay vừa được gắn đều tính lại thời gian ân hạn từ bây giờ
+    let touched: HashSet<&String> = old.iter().chain(new.iter()).map(|(id,)| id).collect();
+    if !touched.is_empty() {
+        let mut qb: QueryBuilder<MySql> = QueryBuilder::new("UPDATE media SET last_used_at = NOW() WHERE id IN (");
+        let mut sep = qb.separated(", ");
+        for id in touched {
+            sep.push_bind(id);
+        }
+        sep.push_unseparated(")");
+        qb.build().execute(&mut **tx).await?;
+    }
+
+    Ok(())
+}
+
+/// Xóa media không còn tham chiếu và không được dùng lại trong `grace_hours` giờ.
+/// Xóa dòng DB trước (kiểm tra lại điều kiện trong câu DELETE), sau đó mới xóa file.
+pub async fn collect_garbage(db: &MySqlPool, store: &dyn BlobStore, grace_hours: u32) -> Result<usize, sqlx::Error> {
+    let candidates: Vec<(String, sqlx::types::Json<Vec<String>>)> = sqlx::query_as(
+        "SELECT m.id, m.blob_keys FROM media m
+         WHERE m.last_used_at < NOW() - INTERVAL ? HOUR
+           AND NOT EXISTS (SELECT 1 FROM media_refs r WHERE r.media_id = m.id)
+         ORDER BY m.last_used_at
+         LIMIT ?"
+    )
+    .bind(grace_hours)
+    .bind(GC_BATCH_SIZE)
+    .fetch_all(db).await?;
+
+    let mut removed = 0;
+    for (id, blob_keys) in candidates {
+        // Có thể vừa được gắn vào sản phẩm / upload lại giữa 2 câu query -> điều kiện kiểm tra lại
+        let res = sqlx::query(
+            "DELETE FROM media
+             WHERE id = ? AND last_used_at < NOW() - INTERVAL ? HOUR
+               AND NOT EXISTS (SELECT 1 FROM media_refs r WHERE r.media_id = media.id)"
+        )
+        .bind(&id)
+        .bind(grace_hours)
+        .execute(db).await;
+
+        match res {
+            Ok(r) if r.rows_affected() == 1 => {}
+            Ok(_) => continue,
+            Err(e) => {
+                // FK media_refs chặn xóa khi vừa có tham chiếu mới
+                println!("GC media {}: {:?}", id, e);
+                continue;
+            }
+        }
+
+        for key in &blob_keys.0 {
+            if let Err(e) = store.delete(key).await {
+                println!("GC media {}: không xóa được {}: {}", id, key, e);
+            }
+        }
+        removed += 1;
+    }
+
+    Ok(removed)
+}
+
+/// Chạy GC định kỳ (mỗi giờ) trong nền
+pub fn spawn_gc(db: MySqlPool, store: Arc<dyn BlobStore>, grace_hours: u32) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(GC_INTERVAL);
+        loop {
+            interval.tick().await;
+            match collect_garbage(&db, store.as_ref(), grace_hours).await {
+                Ok(0) => {}
+                Ok(n) => println!("🧹 Đã dọn {} media không dùng", n),
+                Err(e) => println!("Lỗi GC media: {:?}", e),
+            }
+        }
+    });
+}
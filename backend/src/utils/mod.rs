@@ -1,5 +1,14 @@
 // src/utils/mod.rs
 pub mod suid;
 pub mod email;
+pub mod audit;
+pub mod image;
+pub mod media;
+pub mod templates;
+pub mod outbox;
+pub mod notify;
+pub mod invoice;
+pub mod money;
 pub use self::suid::suid;
-pub use self::email::{ send_order_shipping_email, send_order_thank_you_email};
\ No newline at end of file
+pub use self::money::Money;
+pub use self::email::{ send_order_shipping_email, send_order_thank_you_email, send_back_in_stock_email, send_price_drop_email, send_review_reply_email, send_verify_email, send_password_reset_email, send_login_otp_email, send_order_confirmation_email, recipient_language, EmailError, OrderEmailItem, OrderEmailShipping, OrderPlacedEmail };
\ No newline at end of file
@@ -0,0 +1,344 @@
+// src/utils/money.rs
+// Tiền tệ: số tiền Decimal (không qua f64) kèm loại tiền. Cộng trừ nhân chính xác, làm tròn theo số chữ số
+// lẻ của loại tiền (VND: 0) kiểu "làm tròn nửa lên" (0.5 -> 1), format theo ngôn ngữ người xem.
+// Ra API là chuỗi ("23990000") để client JS không mất chính xác; lưu DB là cột DECIMAL.
+// Cộng trừ nhân qua checked_* (trả Result), không có toán tử + - * để handler không thể panic vì tiền.
+use rust_decimal::prelude::ToPrimitive;
+use rust_decimal::{ Decimal, RoundingStrategy };
+use serde::{ Deserialize, Deserializer, Serialize, Serializer };
+use sqlx::encode::IsNull;
+use sqlx::error::BoxDynError;
+use sqlx::mysql::{ MySql, MySqlTypeInfo, MySqlValueRef };
+use std::cmp::Ordering;
+use std::fmt;
+use std::ops::Neg;
+
+/// Thêm loại tiền mới: bổ sung minor_units / symbol bên dưới
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Currency {
+    Vnd,
+}
+
+/// Loại tiền của cửa hàng: giá trong DB và request không ghi kèm loại tiền
+pub const SHOP_CURRENCY: Currency = Currency::Vnd;
+
+impl Currency {
+    /// Số chữ số sau dấu thập phân
+    pub fn minor_units(self) -> u32 {
+        match self {
+            Currency::Vnd => 0,
+        }
+    }
+
+    fn symbol(self) -> &'static str {
+        match self {
+            Currency::Vnd => "₫",
+        }
+    }
+}
+
+/// Lỗi tính tiền của các hàm checked_*
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum MoneyError {
+    /// Cộng / trừ / so 2 loại tiền khác nhau (không tự quy đổi)
+    CurrencyMismatch,
+    /// Vượt giới hạn của Decimal
+    Overflow,
+}
+
+impl fmt::Display for MoneyError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MoneyError::CurrencyMismatch => write!(f, "Không thể tính toán giữa 2 loại tiền khác nhau"),
+            MoneyError::Overflow => write!(f, "Số tiền quá lớn"),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Money {
+    amount: Decimal,
+    currency: Currency,
+}
+
+impl Money {
+    pub fn new(amount: Decimal, currency: Currency) -> Self {
+        Money { amount, currency }
+    }
+
+    pub fn vnd(amount: impl Into<Decimal>) -> Self {
+        Money::new(amount.into(), Currency::Vnd)
+    }
+
+    pub fn is_negative(&self) -> bool {
+        self.amount.is_sign_negative() && !self.amount.is_zero()
+    }
+
+    /// Làm tròn về đơn vị nhỏ nhất của loại tiền, 0.5 làm tròn ra xa số 0
+    pub fn round(self) -> Self {
+        let amount = self
+            .amount
+            .round_dp_with_strategy(self.currency.minor_units(), RoundingStrategy::MidpointAwayFromZero);
+        Money::new(amount, self.currency)
+    }
+
+    /// Số tiền có lẻ hơn đơn vị nhỏ nhất không (vd 1000.5 VND)
+    pub fn has_sub_minor_units(&self) -> bool {
+        self.amount.normalize().scale() > self.currency.minor_units()
+    }
+
+    /// Nhân với hệ số (tỷ lệ thuế, % giảm giá...), kết quả chưa làm tròn
+    pub fn scale(self, factor: Decimal) -> Self {
+        Money::new(self.amount * factor, self.currency)
+    }
+
+    pub fn checked_add(self, rhs: Money) -> Result<Money, MoneyError> {
+        self.same_currency(&rhs)?;
+        let amount = self.amount.checked_add(rhs.amount).ok_or(MoneyError::Overflow)?;
+        Ok(Money::new(amount, self.currency))
+    }
+
+    pub fn checked_sub(self, rhs: Money) -> Result<Money, MoneyError> {
+        self.same_currency(&rhs)?;
+        let amount = self.amount.checked_sub(rhs.amount).ok_or(MoneyError::Overflow)?;
+        Ok(Money::new(amount, self.currency))
+    }
+
+    /// Đơn giá x số lượng
+    pub fn checked_mul(self, quantity: i32) -> Result<Money, MoneyError> {
+        let amount = self.amount.checked_mul(Decimal::from(quantity)).ok_or(MoneyError::Overflow)?;
+        Ok(Money::new(amount, self.currency))
+    }
+
+    /// Số lần trọn vẹn `unit` nằm trong số tiền (vd số điểm: mỗi 1.000 ₫ = 1 điểm), làm tròn xuống.
+    /// `unit` = 0 -> 0; kết quả vượt i64 -> Overflow
+    pub fn div_floor(&self, unit: Money) -> Result<i64, MoneyError> {
+        self.same_currency(&unit)?;
+        if unit.amount.is_zero() {
+            return Ok(0);
+        }
+        self.amount
+            .checked_div(unit.amount)
+            .and_then(|q| q.floor().to_i64())
+            .ok_or(MoneyError::Overflow)
+    }
+
+    /// Chuỗi trả về API: số tiền đã làm tròn, đủ số chữ số lẻ, không phân cách hàng nghìn
+    pub fn to_api_string(self) -> String {
+        format!("{:.*}", self.currency.minor_units() as usize, self.round().amount)
+    }
+
+    /// Hiển thị theo ngôn ngữ: vi "1.234.567 ₫", "1.234,56 US$"; en "₫1,234,567", "$1,234.56"
+    pub fn format(&self, lang: &str) -> String {
+        let english = lang == "en";
+        let (thousands, decimal) = if english { (',', '.') } else { ('.', ',') };
+
+        let rounded = self.round();
+        let digits = format!("{:.*}", self.currency.minor_units() as usize, rounded.amount.abs());
+        let (int_part, frac_part) = match digits.split_once('.') {
+            Some((i, f)) => (i, Some(f)),
+            None => (digits.as_str(), None),
+        };
+
+        let mut number = String::new();
+        let len = int_part.len();
+        for (i, c) in int_part.chars().enumerate() {
+            if i > 0 && (len - i).is_multiple_of(3) {
+                number.push(thousands);
+            }
+            number.push(c);
+        }
+        if let Some(frac) = frac_part {
+            number.push(decimal);
+            number.push_str(frac);
+        }
+
+        let sign = if rounded.is_negative() { "-" } else { "" };
+        let symbol = self.currency.symbol();
+        if english {
+            format!("{}{}{}", sign, symbol, number)
+        } else {
+            format!("{}{} {}", sign, number, symbol)
+        }
+    }
+
+    fn same_currency(&self, other: &Money) -> Result<(), MoneyError> {
+        if self.currency != other.currency {
+            return Err(MoneyError::CurrencyMismatch);
+        }
+        Ok(())
+    }
+}
+
+/// Mặc định hiển thị tiếng Việt
+impl fmt::Display for Money {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.format("vi"))
+    }
+}
+
+impl PartialOrd for Money {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        if self.currency != other.currency {
+            return None;
+        }
+        self.amount.partial_cmp(&other.amount)
+    }
+}
+
+impl Neg for Money {
+    type Output = Money;
+
+    fn neg(self) -> Money {
+        Money::new(-self.amount, self.currency)
+    }
+}
+
+impl Serialize for Money {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&self.to_api_string())
+    }
+}
+
+/// Nhận chuỗi hoặc số từ request, hiểu là SHOP_CURRENCY
+impl<'de> Deserialize<'de> for Money {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        <Decimal as Deserialize>::deserialize(deserializer).map(|amount| Money::new(amount, SHOP_CURRENCY))
+    }
+}
+
+// --- SQLX: cột DECIMAL <-> Money (SHOP_CURRENCY) ---
+
+impl sqlx::Type<MySql> for Money {
+    fn type_info() -> MySqlTypeInfo {
+        <Decimal as sqlx::Type<MySql>>::type_info()
+    }
+
+    fn compatible(ty: &MySqlTypeInfo) -> bool {
+        <Decimal as sqlx::Type<MySql>>::compatible(ty)
+    }
+}
+
+impl<'r> sqlx::Decode<'r, MySql> for Money {
+    fn decode(value: MySqlValueRef<'r>) -> Result<Self, BoxDynError> {
+        Ok(Money::new(<Decimal as sqlx::Decode<MySql>>::decode(value)?, SHOP_CURRENCY))
+    }
+}
+
+impl<'q> sqlx::Encode<'q, MySql> for Money {
+    fn encode_by_ref(&self, buf: &mut Vec<u8>) -> IsNull {
+        <Decimal as sqlx::Encode<MySql>>::encode_by_ref(&self.amount, buf)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    fn dec(s: &str) -> Decimal {
+        Decimal::from_str(s).unwrap()
+    }
+
+    #[test]
+    fn format_vnd() {
+        assert_eq!(Money::vnd(1_234_567).format("vi"), "1.234.567 ₫");
+        assert_eq!(Money::vnd(1_234_567).format("en"), "₫1,234,567");
+        assert_eq!(Money::vnd(0).format("vi"), "0 ₫");
+        assert_eq!(Money::vnd(999).format("vi"), "999 ₫");
+        assert_eq!(Money::vnd(1000).format("vi"), "1.000 ₫");
+        assert_eq!(Money::vnd(-1500).format("vi"), "-1.500 ₫");
+        assert_eq!(Money::vnd(-1500).format("en"), "-₫1,500");
+        // Ngôn ngữ lạ -> kiểu tiếng Việt
+        assert_eq!(Money::vnd(1500).format("fr"), "1.500 ₫");
+        assert_eq!(Money::vnd(1500).to_string(), "1.500 ₫");
+    }
+
+    #[test]
+    fn format_vnd_rounds_half_away_from_zero() {
+        assert_eq!(Money::vnd(dec("1000.5")).format("vi"), "1.001 ₫");
+        assert_eq!(Money::vnd(dec("1000.49")).format("vi"), "1.000 ₫");
+        assert_eq!(Money::vnd(dec("-1000.5")).format("vi"), "-1.001 ₫");
+        assert_eq!(Money::vnd(dec("999.5")).format("vi"), "1.000 ₫");
+        // Làm tròn về 0 thì không còn dấu âm
+        assert_eq!(Money::vnd(dec("-0.4")).format("vi"), "0 ₫");
+    }
+
+    #[test]
+    fn api_string() {
+        assert_eq!(Money::vnd(dec("23990000.00")).to_api_string(), "23990000");
+        assert_eq!(Money::vnd(dec("1000.5")).to_api_string(), "1001");
+        assert_eq!(Money::vnd(-1500).to_api_string(), "-1500");
+    }
+
+    #[test]
+    fn div_floor() {
+        let unit = Money::vnd(1000);
+        assert_eq!(Money::vnd(1999).div_floor(unit), Ok(1));
+        assert_eq!(Money::vnd(2000).div_floor(unit), Ok(2));
+        assert_eq!(Money::vnd(999).div_floor(unit), Ok(0));
+        assert_eq!(Money::vnd(-1).div_floor(unit), Ok(-1));
+        assert_eq!(Money::vnd(1999).div_floor(Money::vnd(0)), Ok(0));
+        assert_eq!(Money::vnd(Decimal::MAX).div_floor(Money::vnd(1)), Err(MoneyError::Overflow));
+    }
+
+    #[test]
+    fn checked_arithmetic() {
+        assert_eq!(Money::vnd(1500).checked_add(Money::vnd(500)), Ok(Money::vnd(2000)));
+        assert_eq!(Money::vnd(1500).checked_sub(Money::vnd(2000)), Ok(Money::vnd(-500)));
+        assert_eq!(Money::vnd(1500).checked_mul(3), Ok(Money::vnd(4500)));
+        assert_eq!(Money::vnd(Decimal::MAX).checked_add(Money::vnd(1)), Err(MoneyError::Overflow));
+        assert_eq!(Money::vnd(Decimal::MIN).checked_sub(Money::vnd(1)), Err(MoneyError::Overflow));
+        assert_eq!(Money::vnd(Decimal::MAX).checked_mul(2), Err(MoneyError::Overflow));
+    }
+
+    #[test]
+    fn negate() {
+        assert_eq!(-Money::vnd(2550), Money::vnd(-2550));
+        assert_eq!(-Money::vnd(-1), Money::vnd(1));
+    }
+
+    #[test]
+    fn compare() {
+        assert!(Money::vnd(1000) < Money::vnd(2000));
+        // Decimal so sánh theo giá trị, không theo scale
+        assert_eq!(Money::vnd(dec("1000.00")), Money::vnd(1000));
+    }
+
+    #[test]
+    fn rounding_helpers() {
+        assert!(Money::vnd(dec("10.5")).has_sub_minor_units());
+        assert!(!Money::vnd(dec("10.00")).has_sub_minor_units());
+        assert_eq!(
+            Money::vnd(1_100_000).scale(Decimal::ONE_HUNDRED / dec("110")).round(),
+            Money::vnd(1_000_000)
+        );
+        assert!(Money::vnd(-1).is_negative());
+        assert!(!Money::vnd(dec("-0")).is_negative());
+    }
+
+    #[test]
+    fn serialize_as_api_string() {
+        assert_eq!(serde_json::to_string(&Money::vnd(dec("23990000.00"))).unwrap(), r#""23990000""#);
+    }
+
+    #[test]
+    fn deserialize_string_or_number() {
+        let m: Money = serde_json::from_str(r#""23990000.00""#).unwrap();
+        assert_eq!(m, Money::vnd(23_990_000));
+        let m: Money = serde_json::from_str("1500").unwrap();
+        assert_eq!(m, Money::vnd(1500));
+        assert!(serde_json::from_str::<Money>(r#""abc""#).is_err());
+    }
+
+    #[test]
+    fn serde_round_trip() {
+        for money in [Money::vnd(0), Money::vnd(23_990_000), Money::vnd(-1500)] {
+            let json = serde_json::to_string(&money).unwrap();
+            assert_eq!(serde_json::from_str::<Money>(&json).unwrap(), money);
+        }
+        // Lẻ hơn đơn vị nhỏ nhất bị làm tròn khi ra API
+        let json = serde_json::to_string(&Money::vnd(dec("1000.5"))).unwrap();
+        assert_eq!(serde_json::from_str::<Money>(&json).unwrap(), Money::vnd(1001));
+    }
+}
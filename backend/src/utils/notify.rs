@@ -0,0 +1,111 @@
+// src/utils/notify.rs
+// Email báo nhân viên (đơn mới, liên hệ mới, sắp hết hàng). Người nhận cấu hình trong bảng settings,
+// nhiều địa chỉ phân cách bằng dấu phẩy, để trống là tắt. Ghi vào outbox cùng transaction với thay đổi.
+use crate::utils::email::{
+    recipient_language, send_staff_low_stock_email, send_staff_new_contact_email, send_staff_new_order_email,
+    EmailError, OrderPlacedEmail,
+};
+use sqlx::MySqlConnection;
+
+pub const SETTING_NEW_ORDER_RECIPIENTS: &str = "notify_new_order_emails";
+pub const SETTING_CONTACT_RECIPIENTS: &str = "notify_contact_emails";
+pub const SETTING_LOW_STOCK_RECIPIENTS: &str = "notify_low_stock_emails";
+pub const SETTING_LOW_STOCK_THRESHOLD: &str = "low_stock_threshold";
+pub const RECIPIENT_SETTINGS: [&str; 3] = [
+    SETTING_NEW_ORDER_RECIPIENTS,
+    SETTING_CONTACT_RECIPIENTS,
+    SETTING_LOW_STOCK_RECIPIENTS,
+];
+
+// Chưa cấu hình low_stock_threshold thì dùng mốc này
+const DEFAULT_LOW_STOCK_THRESHOLD: i32 = 5;
+
+async fn setting(conn: &mut MySqlConnection, key: &str) -> Option<String> {
+    let row: Option<(Option<String>,)> = sqlx::query_as("SELECT value FROM settings WHERE id = ?")
+        .bind(key)
+        .fetch_optional(conn).await
+        .unwrap_or(None);
+    row.and_then(|(v,)| v)
+}
+
+/// Tách danh sách email trong settings: "a@x.com, b@x.com" -> [a@x.com, b@x.com]
+pub fn parse_recipients(value: &str) -> Vec<String> {
+    let mut list: Vec<String> = Vec::new();
+    for email in value.split([',', ';', '\n']).map(str::trim).filter(|e| !e.is_empty()) {
+        if !list.iter().any(|e| e.eq_ignore_ascii_case(email)) {
+            list.push(email.to_string());
+        }
+    }
+    list
+}
+
+async fn recipients(conn: &mut MySqlConnection, key: &str) -> Vec<String> {
+    setting(conn, key).await.map(|v| parse_recipients(&v)).unwrap_or_default()
+}
+
+/// Tồn kho <= ngưỡng này là "sắp hết hàng"
+pub async fn low_stock_threshold(conn: &mut MySqlConnection) -> i32 {
+    setting(conn, SETTING_LOW_STOCK_THRESHOLD).await
+        .and_then(|v| v.trim().parse().ok())
+        .unwrap_or(DEFAULT_LOW_STOCK_THRESHOLD)
+}
+
+/// Có đơn hàng mới
+pub async fn new_order(conn: &mut MySqlConnection, customer_email: &str, order: &OrderPlacedEmail) -> Result<(), EmailError> {
+    for to in recipients(conn, SETTING_NEW_ORDER_RECIPIENTS).await {
+        let lang = recipient_language(&mut *conn, &to).await;
+        send_staff_new_order_email(&mut *conn, to, lang, customer_email, order).await?;
+    }
+    Ok(())
+}
+
+/// Có tin nhắn liên hệ mới. `registered`: người gửi đang đăng nhập
+pub async fn new_contact(
+    conn: &mut MySqlConnection,
+    email: &str,
+    message: &str,
+    registered: bool
+) -> Result<(), EmailError> {
+    for to in recipients(conn, SETTING_CONTACT_RECIPIENTS).await {
+        let lang = recipient_language(&mut *conn, &to).await;
+        send_staff_new_contact_email(&mut *conn, to, lang, email.to_string(), message.to_string(), registered).await?;
+    }
+    Ok(())
+}
+
+/// Gọi sau khi đổi tồn kho (đặt hàng, admin sửa / điều chỉnh kho).
+/// Chỉ báo khi vừa chạm ngưỡng (trên ngưỡng -> dưới hoặc bằng), không báo lại mỗi lần bán thêm
+pub async fn stock_changed(
+    conn: &mut MySqlConnection,
+    product_id: &str,
+    old_stock: i32,
+    new_stock: i32
+) -> Result<(), EmailError> {
+    let threshold = low_stock_threshold(conn).await;
+    if !(old_stock > threshold && new_stock <= threshold) {
+        return Ok(());
+    }
+    let to_list = recipients(conn, SETTING_LOW_STOCK_RECIPIENTS).await;
+    if to_list.is_empty() {
+        return Ok(());
+    }
+
+    let name: Option<(String,)> = sqlx::query_as("SELECT name FROM products WHERE id = ?")
+        .bind(product_id)
+        .fetch_optional(&mut *conn).await?;
+    let product_name = name.map(|(n,)| n).unwrap_or_default();
+
+    for to in to_list {
+        let lang = recipient_language(&mut *conn, &to).await;
+        send_staff_low_stock_email(
+            &mut *conn,
+            to,
+            lang,
+            product_id.to_string(),
+            product_name.clone(),
+            new_stock,
+            threshold,
+        ).await?;
+    }
+    Ok(())
+}
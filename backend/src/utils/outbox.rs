@@ -0,0 +1,274 @@
+// src/utils/outbox.rs
+// Worker gửi email từ bảng email_outbox: nhận 1 lô (claim bằng token để nhiều instance không gửi trùng),
+// gửi lần lượt, lỗi tạm thời thì hẹn lại theo exponential backoff, lỗi vĩnh viễn / quá số lần thì chuyển dead.
+use crate::mail::{ DeliveryError, EmailAttachment };
+use crate::utils::email;
+use crate::utils::invoice;
+use crate::utils::suid;
+use serde::{ Deserialize, Serialize };
+use sqlx::types::Json;
+use sqlx::{ FromRow, MySql, MySqlPool };
+use std::sync::OnceLock;
+use std::time::Duration;
+use tokio::sync::Notify;
+
+pub const STATUS_PENDING: &str = "pending";
+pub const STATUS_SENDING: &str = "sending";
+pub const STATUS_SENT: &str = "sent";
+pub const STATUS_DEAD: &str = "dead";
+pub const STATUSES: [&str; 4] = [STATUS_PENDING, STATUS_SENDING, STATUS_SENT, STATUS_DEAD];
+
+// Gửi lỗi bấy nhiêu lần thì bỏ (dead), tổng thời gian chờ ~ 1 ngày
+const MAX_ATTEMPTS: i32 = 10;
+const BACKOFF_BASE_SECS: i64 = 30;
+const BACKOFF_MAX_SECS: i64 = 6 * 60 * 60;
+
+const BATCH_SIZE: i64 = 20;
+const POLL_INTERVAL: Duration = Duration::from_secs(10);
+// Worker giữ email quá thời gian này (chết giữa chừng) thì instance khác được nhận lại.
+// Gia hạn trước mỗi email, phải dài hơn hẳn timeout SMTP (mail::smtp) + dựng file đính kèm
+const CLAIM_TIMEOUT_SECS: i64 = 5 * 60;
+// Email đã gửi giữ lại bấy nhiêu ngày để tra cứu
+const SENT_RETENTION_DAYS: i64 = 30;
+const PURGE_INTERVAL: Duration = Duration::from_secs(60 * 60);
+
+#[derive(Debug, FromRow)]
+struct OutboxMessage {
+    id: String,
+    to_email: String,
+    subject: String,
+    html_body: String,
+    text_body: String,
+    attachments: Option<Json<Vec<OutboxAttachment>>>,
+    attempts: i32,
+}
+
+/// File đính kèm lưu trong outbox dạng tham chiếu (cột attachments), worker dựng nội dung lúc gửi
+/// để bảng không phình vì file nhị phân
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum OutboxAttachment {
+    /// Hóa đơn PDF của đơn hàng (utils::invoice)
+    Invoice { order_id: String },
+}
+
+static WAKE: OnceLock<Notify> = OnceLock::new();
+
+fn wake_signal() -> &'static Notify {
+    WAKE.get_or_init(Notify::new)
+}
+
+/// Báo worker có email mới, không phải chờ hết POLL_INTERVAL. Gọi sau khi transaction ghi email đã commit
+pub fn wake() {
+    wake_signal().notify_one();
+}
+
+/// Thời gian chờ trước lần thử thứ `attempts + 1`: 30s, 1 phút, 2 phút... tối đa 6 giờ
+fn backoff_secs(attempts: i32) -> i64 {
+    let exp = (attempts - 1).clamp(0, 20) as u32;
+    BACKOFF_BASE_SECS.saturating_mul(2i64.pow(exp)).min(BACKOFF_MAX_SECS)
+}
+
+// Dựng nội dung file đính kèm. Đơn không còn -> lỗi vĩnh viễn, lỗi DB / render -> thử lại sau
+async fn resolve_attachments(db: &MySqlPool, attachments: &[OutboxAttachment]) -> Result<Vec<EmailAttachment>, DeliveryError> {
+    let mut files = Vec::with_capacity(attachments.len());
+    for attachment in attachments {
+        match attachment {
+            OutboxAttachment::Invoice { order_id } => {
+                let mut conn = db.acquire().await.map_err(|e| DeliveryError::temporary(e.to_string()))?;
+                let data = invoice::load_invoice(&mut conn, order_id).await;
+                drop(conn);
+                let data = data
+                    .map_err(|e| DeliveryError::temporary(e.to_string()))?
+                    .ok_or_else(|| DeliveryError::permanent(format!("Không tìm thấy đơn hàng {} để xuất hóa đơn", order_id)))?;
+                let filename = data.filename();
+                // printpdf dùng Rc và nén font -> chạy ngoài runtime
+                let pdf = tokio::task
+                    ::spawn_blocking(move || invoice::render_pdf(&data)).await
+                    .map_err(|e| DeliveryError::temporary(e.to_string()))?
+                    .map_err(|e| DeliveryError::temporary(format!("Lỗi dựng hóa đơn PDF: {}", e)))?;
+                files.push(EmailAttachment {
+                    filename,
+                    content_type: "application/pdf".to_string(),
+                    data: pdf,
+                });
+            }
+        }
+    }
+    Ok(files)
+}
+
+/// Nhận và gửi 1 lô email đến hạn. Trả về số email đã xử lý
+pub async fn process_batch(db: &MySqlPool) -> Result<usize, sqlx::Error> {
+    let token = suid();
+    // Nhận lại email 'sending' quá hạn (worker trước chết giữa chừng) cũng tính 1 lần thử,
+    // để email làm sập worker không bị nhận lại mãi. MySQL gán SET từ trái sang phải
+    // -> attempts phải đứng trước status.
+    sqlx::query(
+        "UPDATE email_outbox
+         SET attempts = IF(status = 'sending', attempts + 1, attempts),
+             status = 'sending', claim_token = ?, locked_until = NOW() + INTERVAL ? SECOND
+         WHERE (status = 'pending' AND next_attempt_at <= NOW())
+            OR (status = 'sending' AND locked_until < NOW())
+         ORDER BY next_attempt_at
+         LIMIT ?"
+    )
+    .bind(&token)
+    .bind(CLAIM_TIMEOUT_SECS)
+    .bind(BATCH_SIZE)
+    .execute(db).await?;
+
+    let messages: Vec<OutboxMessage> = sqlx::query_as(
+        "SELECT id, to_email, subject, html_body, text_body, attachments, attempts
+         FROM email_outbox WHERE claim_token = ? AND status = 'sending'"
+    )
+    .bind(&token)
+    .fetch_all(db).await?;
+
+    let count = messages.len();
+    for msg in messages {
+        let OutboxMessage { id, to_email, subject, html_body, text_body, attachments, attempts } = msg;
+
+        if attempts >= MAX_ATTEMPTS {
+            println!("Email {} tới {} làm worker dừng giữa chừng quá nhiều lần -> dead", id, to_email);
+            finish(db, &id, &token, Err((STATUS_DEAD, attempts, "Worker dừng giữa chừng quá nhiều lần"))).await?;
+            continue;
+        }
+
+        // Gia hạn khóa ngay trước khi gửi: các email cuối lô không bị instance khác nhận lại
+        // trong lúc email trước còn chờ SMTP. Khóa đã mất (bị nhận lại) thì bỏ qua, không gửi trùng
+        let extended = sqlx::query(
+            "UPDATE email_outbox SET locked_until = NOW() + INTERVAL ? SECOND
+             WHERE id = ? AND claim_token = ? AND status = 'sending'"
+        )
+        .bind(CLAIM_TIMEOUT_SECS)
+        .bind(&id)
+        .bind(&token)
+        .execute(db).await?;
+        if extended.rows_affected() == 0 {
+            println!("Email {} đã bị worker khác nhận lại, bỏ qua", id);
+            continue;
+        }
+
+        let to = to_email.clone();
+        let attachments = attachments.map(|Json(a)| a).unwrap_or_default();
+        let result = match resolve_attachments(db, &attachments).await {
+            // Transport (lettre SMTP, ghi file) là blocking -> chạy ngoài runtime
+            Ok(files) => tokio::task
+                ::spawn_blocking(move || email::deliver(&to, &subject, &html_body, &text_body, files)).await
+                .unwrap_or_else(|e| Err(DeliveryError::temporary(e.to_string()))),
+            Err(e) => Err(e),
+        };
+
+        match result {
+            Ok(()) => {
+                println!("Email sent to {}", to_email);
+                finish(db, &id, &token, Ok(())).await?;
+            }
+            Err(e) => {
+                let attempts = attempts + 1;
+                let dead = e.permanent || attempts >= MAX_ATTEMPTS;
+                println!(
+                    "Lỗi gửi email {} tới {} (lần {}): {}{}",
+                    id, to_email, attempts, e.message, if dead { " -> dead" } else { "" }
+                );
+                let status = if dead { STATUS_DEAD } else { STATUS_PENDING };
+                finish(db, &id, &token, Err((status, attempts, &e.message))).await?;
+            }
+        }
+    }
+
+    Ok(count)
+}
+
+// Ghi kết quả gửi, chỉ khi lô vẫn thuộc worker này (claim_token): worker chậm không ghi đè
+// trạng thái của worker đã nhận lại email. Err = (status mới, số lần thử, lỗi)
+async fn finish(
+    db: &MySqlPool,
+    id: &str,
+    token: &str,
+    result: Result<(), (&str, i32, &str)>
+) -> Result<(), sqlx::Error> {
+    let res = match result {
+        Ok(()) => sqlx::query(
+                "UPDATE email_outbox
+                 SET status = 'sent', sent_at = NOW(), last_error = NULL, claim_token = NULL, locked_until = NULL
+                 WHERE id = ? AND claim_token = ?"
+            )
+            .bind(id)
+            .bind(token)
+            .execute(db).await?,
+        Err((status, attempts, error)) => sqlx::query(
+                "UPDATE email_outbox
+                 SET status = ?, attempts = ?, last_error = ?, next_attempt_at = NOW() + INTERVAL ? SECOND,
+                     claim_token = NULL, locked_until = NULL
+                 WHERE id = ? AND claim_token = ?"
+            )
+            .bind(status)
+            .bind(attempts)
+            .bind(error)
+            .bind(backoff_secs(attempts))
+            .bind(id)
+            .bind(token)
+            .execute(db).await?,
+    };
+    if res.rows_affected() == 0 {
+        println!("Email {} đã bị worker khác nhận lại, không ghi kết quả", id);
+    }
+    Ok(())
+}
+
+/// Đưa email (thường là dead) về hàng đợi, gửi lại ngay từ lần thử đầu. Trả về false nếu không có / đang gửi
+pub async fn requeue<'e, E>(executor: E, id: &str) -> Result<bool, sqlx::Error>
+where
+    E: sqlx::Executor<'e, Database = MySql>,
+{
+    let res = sqlx::query(
+        "UPDATE email_outbox
+         SET status = 'pending', attempts = 0, next_attempt_at = NOW(), claim_token = NULL, locked_until = NULL
+         WHERE id = ? AND status <> 'sending'"
+    )
+    .bind(id)
+    .execute(executor).await?;
+    Ok(res.rows_affected() == 1)
+}
+
+/// Xóa email đã gửi quá SENT_RETENTION_DAYS ngày
+async fn purge_sent(db: &MySqlPool) -> Result<u64, sqlx::Error> {
+    let res = sqlx::query("DELETE FROM email_outbox WHERE status = 'sent' AND sent_at < NOW() - INTERVAL ? DAY")
+        .bind(SENT_RETENTION_DAYS)
+        .execute(db).await?;
+    Ok(res.rows_affected())
+}
+
+/// Chạy worker gửi email trong nền (gọi khi đã có transport gửi mail)
+pub fn spawn_worker(db: MySqlPool) {
+    tokio::spawn(async move {
+        let mut purge = tokio::time::interval(PURGE_INTERVAL);
+        loop {
+            // Lô đầy -> có thể còn email đến hạn, xử lý tiếp luôn
+            loop {
+                match process_batch(&db).await {
+                    Ok(n) if n as i64 >= BATCH_SIZE => continue,
+                    Ok(_) => break,
+                    Err(e) => {
+                        println!("Lỗi worker email: {:?}", e);
+                        break;
+                    }
+                }
+            }
+
+            tokio::select! {
+                _ = wake_signal().notified() => {}
+                _ = tokio::time::sleep(POLL_INTERVAL) => {}
+                _ = purge.tick() => {
+                    match purge_sent(&db).await {
+                        Ok(0) => {}
+                        Ok(n) => println!("🧹 Đã xóa {} email cũ trong outbox", n),
+                        Err(e) => println!("Lỗi dọn email outbox: {:?}", e),
+                    }
+                }
+            }
+        }
+    });
+}
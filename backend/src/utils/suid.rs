@@ -96,7 +96,7 @@ pub fn to_base64url(num: u64) -> String {
     let bytes = num.to_be_bytes(); 
     
     // Mã hóa 8 bytes thành Base64URL
-    let mut encoded = BASE64URL_ENGINE.encode(&bytes);
+    let mut encoded = BASE64URL_ENGINE.encode(bytes);
 
     // Cắt bớt phần sau, thường là 11 ký tự như YouTube ID (8 bytes => 11 ký tự Base64)
     if encoded.len() > 11 {
@@ -0,0 +1,296 @@
+// src/utils/templates.rs
+// Template email (MiniJinja): mỗi template có 3 phần subject / html / text, theo từng ngôn ngữ (vi, en).
+// Bản mặc định nằm trong backend/templates/email (nhúng vào binary lúc build),
+// admin sửa qua API thì lưu vào bảng email_templates và ghi đè bản mặc định.
+// File .html được auto-escape nên dữ liệu người dùng (tên sản phẩm, nội dung đánh giá...) không chèn được HTML.
+use minijinja::Environment;
+use serde::{ Deserialize, Serialize };
+use serde_json::{ json, Value };
+use sqlx::{ FromRow, MySqlPool };
+use std::collections::HashMap;
+use std::sync::{ OnceLock, RwLock };
+use std::time::Duration;
+
+pub const LANGUAGES: [&str; 2] = ["vi", "en"];
+pub const DEFAULT_LANGUAGE: &str = "vi";
+
+// Đồng bộ bản sửa từ DB (khi chạy nhiều instance, admin sửa ở instance khác)
+const OVERRIDE_REFRESH_INTERVAL: Duration = Duration::from_secs(60);
+
+const LAYOUT: &str = include_str!("../../templates/email/layout.html");
+
+/// Thông tin template cho trang quản trị + dữ liệu mẫu để preview
+pub struct TemplateDef {
+    pub name: &'static str,
+    pub description: &'static str,
+    pub sample: fn() -> Value,
+}
+
+pub const TEMPLATES: &[TemplateDef] = &[
+    TemplateDef { name: "order_confirmation", description: "Khách vừa đặt hàng thành công", sample: sample_order_confirmation },
+    TemplateDef { name: "order_shipping", description: "Đơn hàng chuyển sang đang giao", sample: sample_order_shipping },
+    TemplateDef { name: "order_thank_you", description: "Khách xác nhận đã nhận hàng", sample: sample_order_thank_you },
+    TemplateDef { name: "back_in_stock", description: "Sản phẩm theo dõi có hàng trở lại", sample: sample_product_watch },
+    TemplateDef { name: "price_drop", description: "Sản phẩm theo dõi giảm giá", sample: sample_product_watch },
+    TemplateDef { name: "review_reply", description: "Shop trả lời đánh giá", sample: sample_review_reply },
+    TemplateDef { name: "verify_email", description: "Link xác thực email", sample: sample_link },
+    TemplateDef { name: "password_reset", description: "Link đặt lại mật khẩu", sample: sample_link },
+    TemplateDef { name: "login_otp", description: "Mã OTP đăng nhập", sample: sample_login_otp },
+    TemplateDef { name: "staff_new_order", description: "Báo nhân viên: có đơn hàng mới", sample: sample_staff_new_order },
+    TemplateDef { name: "staff_new_contact", description: "Báo nhân viên: có tin nhắn liên hệ mới", sample: sample_staff_new_contact },
+    TemplateDef { name: "staff_low_stock", description: "Báo nhân viên: sản phẩm sắp hết hàng", sample: sample_staff_low_stock },
+];
+
+/// Nội dung nguồn của 1 template (chưa render)
+#[derive(Debug, Clone, Serialize, Deserialize, FromRow)]
+pub struct TemplateSource {
+    pub subject: String,
+    pub html_body: String,
+    pub text_body: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct RenderedEmail {
+    pub subject: String,
+    pub html: String,
+    pub text: String,
+}
+
+struct Builtin {
+    name: &'static str,
+    lang: &'static str,
+    subject: &'static str,
+    html: &'static str,
+    text: &'static str,
+}
+
+macro_rules! builtin {
+    ($lang:literal, $name:literal) => {
+        Builtin {
+            name: $name,
+            lang: $lang,
+            subject: include_str!(concat!("../../templates/email/", $lang, "/", $name, ".subject.txt")),
+            html: include_str!(concat!("../../templates/email/", $lang, "/", $name, ".html")),
+            text: include_str!(concat!("../../templates/email/", $lang, "/", $name, ".txt")),
+        }
+    };
+}
+
+const BUILTINS: &[Builtin] = &[
+    builtin!("vi", "order_confirmation"),
+    builtin!("vi", "order_shipping"),
+    builtin!("vi", "order_thank_you"),
+    builtin!("vi", "back_in_stock"),
+    builtin!("vi", "price_drop"),
+    builtin!("vi", "review_reply"),
+    builtin!("vi", "verify_email"),
+    builtin!("vi", "password_reset"),
+    builtin!("vi", "login_otp"),
+    builtin!("vi", "staff_new_order"),
+    builtin!("vi", "staff_new_contact"),
+    builtin!("vi", "staff_low_stock"),
+    builtin!("en", "order_confirmation"),
+    builtin!("en", "order_shipping"),
+    builtin!("en", "order_thank_you"),
+    builtin!("en", "back_in_stock"),
+    builtin!("en", "price_drop"),
+    builtin!("en", "review_reply"),
+    builtin!("en", "verify_email"),
+    builtin!("en", "password_reset"),
+    builtin!("en", "login_otp"),
+    builtin!("en", "staff_new_order"),
+    builtin!("en", "staff_new_contact"),
+    builtin!("en", "staff_low_stock"),
+];
+
+type OverrideMap = HashMap<(String, String), TemplateSource>;
+
+static OVERRIDES: OnceLock<RwLock<OverrideMap>> = OnceLock::new();
+
+fn overrides() -> &'static RwLock<OverrideMap> {
+    OVERRIDES.get_or_init(|| RwLock::new(HashMap::new()))
+}
+
+/// Ngôn ngữ không hỗ trợ -> dùng tiếng Việt
+pub fn normalize_language(lang: &str) -> &'static str {
+    LANGUAGES.iter().copied().find(|l| *l == lang).unwrap_or(DEFAULT_LANGUAGE)
+}
+
+pub fn find_template(name: &str) -> Option<&'static TemplateDef> {
+    TEMPLATES.iter().find(|t| t.name == name)
+}
+
+/// Bản mặc định (file trong repo)
+pub fn builtin_source(name: &str, lang: &str) -> Option<TemplateSource> {
+    BUILTINS.iter().find(|b| b.name == name && b.lang == lang).map(|b| TemplateSource {
+        subject: b.subject.to_string(),
+        html_body: b.html.to_string(),
+        text_body: b.text.to_string(),
+    })
+}
+
+/// Bản admin đã sửa (nếu có)
+pub fn override_source(name: &str, lang: &str) -> Option<TemplateSource> {
+    overrides().read().unwrap().get(&(name.to_string(), lang.to_string())).cloned()
+}
+
+/// Bản đang dùng: bản admin sửa, không có thì bản mặc định
+pub fn current_source(name: &str, lang: &str) -> Option<TemplateSource> {
+    override_source(name, lang).or_else(|| builtin_source(name, lang))
+}
+
+pub fn set_override(name: &str, lang: &str, source: TemplateSource) {
+    overrides().write().unwrap().insert((name.to_string(), lang.to_string()), source);
+}
+
+pub fn remove_override(name: &str, lang: &str) {
+    overrides().write().unwrap().remove(&(name.to_string(), lang.to_string()));
+}
+
+/// Render 1 bộ nguồn với dữ liệu `ctx` (thêm biến `lang` cho layout)
+pub fn render_source(source: &TemplateSource, lang: &str, ctx: &Value) -> Result<RenderedEmail, String> {
+    let mut env = Environment::new();
+    // Tên file quyết định auto-escape: .html -> escape HTML, .txt -> giữ nguyên
+    env.add_template("layout.html", LAYOUT).map_err(|e| e.to_string())?;
+    env.add_template("subject.txt", &source.subject).map_err(|e| format!("subject: {}", e))?;
+    env.add_template("body.html", &source.html_body).map_err(|e| format!("html: {}", e))?;
+    env.add_template("body.txt", &source.text_body).map_err(|e| format!("text: {}", e))?;
+
+    let mut ctx = ctx.clone();
+    if let Value::Object(map) = &mut ctx {
+        map.insert("lang".to_string(), json!(lang));
+    }
+
+    let render = |name: &str| -> Result<String, String> {
+        env.get_template(name)
+            .and_then(|t| t.render(&ctx))
+            .map_err(|e| format!("{}: {}", name, e))
+    };
+
+    Ok(RenderedEmail {
+        // Subject chỉ được 1 dòng
+        subject: render("subject.txt")?.split_whitespace().collect::<Vec<_>>().join(" "),
+        html: render("body.html")?,
+        text: render("body.txt")?,
+    })
+}
+
+/// Render template đang dùng theo tên + ngôn ngữ
+pub fn render(name: &str, lang: &str, ctx: &Value) -> Result<RenderedEmail, String> {
+    let lang = normalize_language(lang);
+    let source = current_source(name, lang).ok_or_else(|| format!("Không có template {}", name))?;
+    render_source(&source, lang, ctx)
+}
+
+/// Nạp bản admin đã sửa từ DB
+pub async fn load_overrides(db: &MySqlPool) -> Result<(), sqlx::Error> {
+    let rows: Vec<(String, String, String, String, String)> = sqlx::query_as(
+        "SELECT name, lang, subject, html_body, text_body FROM email_templates"
+    )
+    .fetch_all(db).await?;
+
+    let map: OverrideMap = rows
+        .into_iter()
+        .map(|(name, lang, subject, html_body, text_body)| ((name, lang), TemplateSource { subject, html_body, text_body }))
+        .collect();
+    *overrides().write().unwrap() = map;
+    Ok(())
+}
+
+/// Nạp lần đầu rồi đồng bộ định kỳ trong nền
+pub fn spawn_override_refresh(db: MySqlPool) {
+    tokio::spawn(async move {
+        let mut interval = tokio::time::interval(OVERRIDE_REFRESH_INTERVAL);
+        loop {
+            interval.tick().await;
+            if let Err(e) = load_overrides(&db).await {
+                println!("Lỗi nạp email template: {:?}", e);
+            }
+        }
+    });
+}
+
+// --- DỮ LIỆU MẪU CHO PREVIEW ---
+
+fn sample_shipping() -> Value {
+    json!({
+        "name": "Nguyễn Văn A",
+        "phone": "0901234567",
+        "address": "12 Nguyễn Huệ, Quận 1, TP.HCM",
+        "note": "Giao giờ hành chính"
+    })
+}
+
+fn sample_order_confirmation() -> Value {
+    let mut ctx = sample_order_shipping();
+    ctx["shipping"] = sample_shipping();
+    ctx
+}
+
+fn sample_staff_new_order() -> Value {
+    let mut ctx = sample_order_confirmation();
+    ctx["customer_email"] = json!("khach@example.com");
+    ctx
+}
+
+fn sample_staff_new_contact() -> Value {
+    json!({
+        "email": "khach@example.com",
+        "message": "Shop cho mình hỏi laptop ASUS Zenbook còn bản 32GB RAM không?\nCảm ơn shop.",
+        "registered": true
+    })
+}
+
+fn sample_staff_low_stock() -> Value {
+    json!({
+        "product_id": "P9x8Y7z6W5v",
+        "product_name": "Tai nghe Sony WH-1000XM5",
+        "stock": 3,
+        "threshold": 5
+    })
+}
+
+fn sample_order_shipping() -> Value {
+    json!({
+        "order_id": "A1b2C3d4E5f",
+        "items": [
+            { "name": "iPhone 15 Pro Max 256GB", "quantity": 1, "price": "29.990.000 ₫", "line_total": "29.990.000 ₫" },
+            { "name": "Ốp lưng <MagSafe> & dán cường lực", "quantity": 2, "price": "350.000 ₫", "line_total": "700.000 ₫" }
+        ],
+        "total": "30.690.000 ₫"
+    })
+}
+
+fn sample_order_thank_you() -> Value {
+    json!({ "order_id": "A1b2C3d4E5f", "points": 306 })
+}
+
+fn sample_product_watch() -> Value {
+    json!({
+        "product_id": "P9x8Y7z6W5v",
+        "product_name": "Tai nghe Sony WH-1000XM5",
+        "old_price": "8.490.000 ₫",
+        "new_price": "6.990.000 ₫"
+    })
+}
+
+fn sample_review_reply() -> Value {
+    json!({
+        "product_name": "Laptop ASUS Zenbook 14 OLED",
+        "stars": "★★★★",
+        "review_content": "Máy đẹp, pin tốt. Giao hàng hơi chậm.",
+        "reply_content": "Cảm ơn bạn! Shop sẽ cải thiện thời gian giao hàng."
+    })
+}
+
+fn sample_link() -> Value {
+    json!({
+        "link": "https://example.com/verify-email?token=sample-token",
+        "ttl_hours": 24,
+        "ttl_minutes": 30
+    })
+}
+
+fn sample_login_otp() -> Value {
+    json!({ "code": "482913", "ttl_minutes": 10 })
+}
@@ -135,7 +135,7 @@ export default function Layout() {
                       >
                         <User size={16} className="text-gray-400" /> Tài khoản
                       </Link>
-                      {userInfo.role === "admin" && (
+                      {userInfo.role && userInfo.role !== "user" && (
                         <Link
                           to="/admin"
                           className="w-full text-left px-4 py-2.5 hover:bg-blue-50 text-blue-600 text-sm font-bold flex items-center gap-2 rounded-lg transition-colors"
@@ -244,7 +244,7 @@ export default function Layout() {
                   >
                     <User size={18} /> Tài khoản của tôi
                   </Link>
-                  {userInfo.role === "admin" && (
+                  {userInfo.role && userInfo.role !== "user" && (
                     <Link
                       to="/admin"
                       onClick={() => setIsMobileMenuOpen(false)}
@@ -23,7 +23,19 @@ export default function MainContent() {
         try {
             // 1. Check Auth & Lấy Giỏ Hàng (Chạy song song cho nhanh)
             // Lưu ý: Logic ở đây là check auth trước, nếu OK mới lấy cart
-            const userRes = await fetch(`${domain}/api/auth/me`, { credentials: 'include' });
+            let userRes = await fetch(`${domain}/api/auth/me`, { credentials: 'include' });
+
+            // Access token hết hạn -> thử đổi refresh token lấy token mới rồi gọi lại
+            if (userRes.status === 401) {
+                const refreshRes = await fetch(`${domain}/api/auth/refresh`, { method: 'POST', credentials: 'include' });
+                // 409: tab khác đang refresh cùng lúc, chờ cookie mới rồi gọi lại
+                if (refreshRes.status === 409) {
+                    await new Promise((resolve) => setTimeout(resolve, 500));
+                }
+                if (refreshRes.ok || refreshRes.status === 409) {
+                    userRes = await fetch(`${domain}/api/auth/me`, { credentials: 'include' });
+                }
+            }
             
             if (userRes.ok) {
                 const userData = await userRes.json();
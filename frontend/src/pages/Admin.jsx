@@ -22,7 +22,7 @@ import {
 } from "lucide-react";
 import { useStore } from "../store";
 import { Button, Card, Badge } from "../components/UI";
-import { formatCurrency } from "../utils";
+import { formatCurrency, imageUrl } from "../utils";
 
 // --- 1. COMPONENT UPLOAD ẢNH ---
 const ImageUploader = ({ images = [], onImagesChange, domain }) => {
@@ -39,12 +39,28 @@ const ImageUploader = ({ images = [], onImagesChange, domain }) => {
         const res = await fetch(`${domain}/api/upload`, {
           method: "POST",
           body: formData,
+          credentials: "include",
         });
-        if (res.ok) {
-          const newLinks = await res.json();
-          onImagesChange([...images, ...newLinks]);
-        } else {
-          alert("Upload thất bại");
+        const data = await res.json().catch(() => null);
+        if (data?.files?.length) {
+          // Lưu cả url + kích thước + các bản thumb/medium/large
+          onImagesChange([
+            ...images,
+            ...data.files.map(({ url, width, height, variants }) => ({
+              url,
+              width,
+              height,
+              variants,
+            })),
+          ]);
+        }
+        if (data?.errors?.length) {
+          alert(
+            "Một số ảnh không upload được:\n" +
+              data.errors.map((e) => `${e.file_name}: ${e.message}`).join("\n")
+          );
+        } else if (!res.ok) {
+          alert(data?.message || "Upload thất bại");
         }
       } catch (e) {
         console.error(e);
@@ -91,13 +107,13 @@ const ImageUploader = ({ images = [], onImagesChange, domain }) => {
 
       {images.length > 0 && (
         <div className="grid grid-cols-4 gap-3 mt-3">
-          {images.map((link, idx) => (
+          {images.map((img, idx) => (
             <div
               key={idx}
               className="relative group aspect-square border rounded-lg overflow-hidden bg-gray-100"
             >
               <img
-                src={`${domain}${link}`}
+                src={imageUrl(domain, img, "thumb")}
                 className="w-full h-full object-cover"
                 alt="preview"
               />
@@ -148,7 +164,7 @@ export default function Admin() {
   });
 
   useEffect(() => {
-    if (userInfo?.role === "admin") fetchData(activeTab);
+    if (userInfo?.role && userInfo.role !== "user") fetchData(activeTab);
   }, [activeTab, userInfo, domain]);
 
   // --- FETCH DATA LOGIC ---
@@ -190,7 +206,7 @@ export default function Admin() {
     setLoading(false);
   };
 
-  if (userInfo?.role !== "admin")
+  if (!userInfo?.role || userInfo.role === "user")
     return (
       <div className="h-screen flex items-center justify-center text-red-500 font-bold text-xl">
         ⛔ Truy cập bị từ chối.
@@ -340,12 +356,16 @@ export default function Admin() {
       value,
     }));
     try {
-      await fetch(`${domain}/api/admin/settings`, {
+      const res = await fetch(`${domain}/api/admin/settings`, {
         method: "POST",
         headers: { "Content-Type": "application/json" },
         body: JSON.stringify({ settings: settingsArray }),
         credentials: "include",
       });
+      if (!res.ok) {
+        alert(await res.json().catch(() => "Lỗi cập nhật"));
+        return;
+      }
       alert("Cập nhật cấu hình thành công!");
     } catch (e) {
       alert("Lỗi cập nhật");
@@ -552,9 +572,8 @@ export default function Admin() {
                       <td className="p-4 flex items-center gap-3">
                         <img
                           src={
-                            p.images?.[0]
-                              ? `${domain}${p.images[0]}`
-                              : "https://placehold.co/50"
+                            imageUrl(domain, p.images?.[0], "thumb") ||
+                            "https://placehold.co/50"
                           }
                           className="w-12 h-12 rounded border object-cover bg-gray-100"
                         />
@@ -907,6 +926,95 @@ export default function Admin() {
                 </Button>
               </div>
             </Card>
+
+            <h2 className="text-2xl font-bold">Email thông báo nhân viên</h2>
+            <Card className="p-6 space-y-4">
+              <p className="text-sm text-gray-500">
+                Nhiều email phân cách bằng dấu phẩy, để trống để tắt thông báo.
+              </p>
+              {[
+                ["notify_new_order_emails", "Đơn hàng mới"],
+                ["notify_contact_emails", "Tin nhắn liên hệ mới"],
+                ["notify_low_stock_emails", "Sản phẩm sắp hết hàng"],
+              ].map(([key, label]) => (
+                <div key={key}>
+                  <label className="label">{label}</label>
+                  <input
+                    className="input"
+                    placeholder="sales@shop.vn, kho@shop.vn"
+                    value={settings[key] || ""}
+                    onChange={(e) =>
+                      setSettings({ ...settings, [key]: e.target.value })
+                    }
+                  />
+                </div>
+              ))}
+              <div>
+                <label className="label">Ngưỡng sắp hết hàng (tồn kho)</label>
+                <input
+                  type="number"
+                  min="0"
+                  className="input"
+                  value={settings.low_stock_threshold || ""}
+                  onChange={(e) =>
+                    setSettings({
+                      ...settings,
+                      low_stock_threshold: e.target.value,
+                    })
+                  }
+                />
+              </div>
+              <div className="pt-4">
+                <Button onClick={() => saveSettings(settings)}>
+                  Lưu thay đổi
+                </Button>
+              </div>
+            </Card>
+
+            <h2 className="text-2xl font-bold">Thông tin xuất hóa đơn</h2>
+            <Card className="p-6 space-y-4">
+              <p className="text-sm text-gray-500">
+                In trên hóa đơn PDF. Để trống tên công ty thì dùng tên cửa hàng.
+              </p>
+              {[
+                ["company_name", "Tên công ty"],
+                ["company_address", "Địa chỉ"],
+                ["company_tax_code", "Mã số thuế"],
+              ].map(([key, label]) => (
+                <div key={key}>
+                  <label className="label">{label}</label>
+                  <input
+                    className="input"
+                    value={settings[key] || ""}
+                    onChange={(e) =>
+                      setSettings({ ...settings, [key]: e.target.value })
+                    }
+                  />
+                </div>
+              ))}
+              <div>
+                <label className="label">Thuế suất GTGT (%) - giá bán đã gồm thuế</label>
+                <input
+                  type="number"
+                  min="0"
+                  max="100"
+                  step="any"
+                  className="input"
+                  value={settings.invoice_vat_rate || ""}
+                  onChange={(e) =>
+                    setSettings({
+                      ...settings,
+                      invoice_vat_rate: e.target.value,
+                    })
+                  }
+                />
+              </div>
+              <div className="pt-4">
+                <Button onClick={() => saveSettings(settings)}>
+                  Lưu thay đổi
+                </Button>
+              </div>
+            </Card>
           </div>
         );
       default:
@@ -3,7 +3,7 @@ import { useNavigate } from "react-router-dom";
 import { Minus, Plus, Trash2 } from "lucide-react";
 import { useStore, actions } from "../store";
 import { Button } from "../components/UI";
-import { formatCurrency, LEVELS } from "../utils";
+import { formatCurrency, LEVELS, imageUrl } from "../utils";
 
 export default function Cart() {
   const [state, dispatch] = useStore();
@@ -100,9 +100,8 @@ export default function Cart() {
               >
                 <img
                   src={
-                    item.image
-                      ? `${domain}${item.image}`
-                      : "https://placehold.co/100"
+                    imageUrl(domain, item.image, "thumb") ||
+                    "https://placehold.co/100"
                   }
                   className="w-16 h-16 object-contain bg-gray-100 rounded"
                   alt={item.name}
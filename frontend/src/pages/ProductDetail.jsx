@@ -3,7 +3,7 @@ import { useParams, useNavigate } from 'react-router-dom';
 import { ShoppingCart, Minus, Plus, Star, Check, Shield, Truck, Box, User } from 'lucide-react';
 import { useStore, actions } from '../store';
 import { Button, Badge } from '../components/UI';
-import { formatCurrency } from '../utils';
+import { formatCurrency, imageUrl, imageSrcSet } from '../utils';
 
 export default function ProductDetail() {
     const { id } = useParams();
@@ -171,7 +171,9 @@ export default function ProductDetail() {
                 <div className="md:col-span-5">
                     <div className="bg-white rounded-2xl border p-4 flex items-center justify-center mb-4 relative overflow-hidden group h-[400px]">
                         <img 
-                            src={activeImg ? `${domain}${activeImg}` : "https://placehold.co/500"} 
+                            src={imageUrl(domain, activeImg) || "https://placehold.co/500"} 
+                            srcSet={imageSrcSet(domain, activeImg)}
+                            sizes="(min-width: 768px) 40vw, 100vw"
                             className="max-w-full max-h-full object-contain transition-transform duration-500 group-hover:scale-110" 
                             alt={product.name}
                         />
@@ -189,7 +191,7 @@ export default function ProductDetail() {
                                     onClick={() => setActiveImg(img)}
                                     className={`w-20 h-20 flex-shrink-0 border rounded-lg overflow-hidden cursor-pointer p-1 bg-white transition-all ${activeImg === img ? 'border-blue-600 ring-1 ring-blue-600' : 'border-gray-200 hover:border-blue-400'}`}
                                 >
-                                    <img src={`${domain}${img}`} className="w-full h-full object-contain" alt={`thumb-${idx}`}/>
+                                    <img src={imageUrl(domain, img, 'thumb')} className="w-full h-full object-contain" alt={`thumb-${idx}`}/>
                                 </div>
                             ))}
                         </div>
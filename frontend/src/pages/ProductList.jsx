@@ -3,7 +3,7 @@ import { useNavigate, useSearchParams } from "react-router-dom";
 import { Search, Plus } from "lucide-react";
 import { useStore, actions } from "../store";
 import { Button, Card } from "../components/UI";
-import { formatCurrency } from "../utils";
+import { formatCurrency, imageUrl, imageSrcSet } from "../utils";
 
 export default function ProductList() {
   const [state, dispatch] = useStore();
@@ -131,10 +131,12 @@ export default function ProductList() {
                   >
                     <img
                       src={
-                        p.images?.[0]
-                          ? `${domain}${p.images[0]}`
-                          : "https://placehold.co/50"
+                        imageUrl(domain, p.images?.[0], "medium") ||
+                        "https://placehold.co/50"
                       }
+                      srcSet={imageSrcSet(domain, p.images?.[0])}
+                      sizes="(min-width: 1024px) 25vw, (min-width: 640px) 50vw, 100vw"
+                      loading="lazy"
                       className="w-full h-full object-contain mix-blend-multiply"
                       alt={p.name}
                     />
@@ -9,6 +9,7 @@ import {
   Crown,
   TrendingUp,
   ShoppingCart,
+  FileText,
 } from "lucide-react";
 
 export default function Profile() {
@@ -256,6 +257,17 @@ export default function Profile() {
                       <CheckCircle size={16} /> Đã nhận hàng
                     </Button>
                   )}
+
+                  {o.status !== "cancelled" && (
+                    <a
+                      href={`${domain}/api/orders/${o.id}/invoice.pdf`}
+                      target="_blank"
+                      rel="noreferrer"
+                      className="text-xs text-gray-500 hover:text-blue-600 flex items-center gap-1"
+                    >
+                      <FileText size={14} /> Hóa đơn
+                    </a>
+                  )}
                 </div>
               </Card>
             ))}
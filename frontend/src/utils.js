@@ -18,4 +18,22 @@ export const BANK_INFO = {
 
 export const getVietQRUrl = (amount, content) => {
     return `https://img.vietqr.io/image/${BANK_INFO.BANK_ID}-${BANK_INFO.ACCOUNT_NO}-${BANK_INFO.TEMPLATE}.png?amount=${amount}&addInfo=${encodeURIComponent(content)}&accountName=${encodeURIComponent(BANK_INFO.ACCOUNT_NAME)}`;
-};
\ No newline at end of file
+};
+// Ảnh sản phẩm: dữ liệu cũ là chuỗi URL, ảnh mới là object { url, width, height, variants: { thumb, medium, large } }
+const absoluteUrl = (domain, url) => (/^https?:\/\//.test(url) ? url : `${domain}${url}`);
+
+export const imageUrl = (domain, img, size = 'large') => {
+    if (!img) return null;
+    if (typeof img === 'string') return absoluteUrl(domain, img);
+    return absoluteUrl(domain, img.variants?.[size]?.url || img.url);
+};
+
+// srcset theo chiều rộng thật của từng bản (chỉ có với ảnh mới)
+export const imageSrcSet = (domain, img) => {
+    if (!img || typeof img === 'string' || !img.variants) return undefined;
+    return ['thumb', 'medium', 'large']
+        .map((size) => img.variants[size])
+        .filter(Boolean)
+        .map((v) => `${absoluteUrl(domain, v.url)} ${v.width}w`)
+        .join(', ');
+};